@@ -241,6 +241,15 @@ impl<'i> Query<'i> {
         self.projection.is_some()
     }
 
+    /// Whether this query is a lone filter selector on the root, such as `$[?@.a==1]`
+    #[allow(dead_code)]
+    pub fn is_single_filter(&self) -> bool {
+        let mut root = self.root.clone();
+        self.projection.is_none()
+            && root.next().is_some_and(|p| p.as_rule() == Rule::filter)
+            && root.next().is_none()
+    }
+
     /// The top-level `arith_expr` of a projection query, if any (for `eval_projection`).
     #[allow(dead_code)]
     pub(crate) fn projection_expr(&self) -> Option<&Pair<'i, Rule>> {
@@ -527,11 +536,19 @@ fn arith_unary<'i, 'j, S: SelectValue>(
     }
 }
 
-/// RFC 9535 `length()` on a value: chars for strings, element/member count for
-/// arrays/objects, `None` (Nothing) otherwise. Generic over any `SelectValue`.
+/// The length of a string as seen by `length()`: the number of Unicode code points, as
+/// RFC 9535 specifies. This is `JSON.STRLEN ... UNIT CODEPOINTS`, and `[...s].length` (not
+/// `s.length`, which counts UTF-16 code units) in JavaScript.
+fn str_length(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// RFC 9535 `length()` on a value: code points for strings (see [`str_length`]),
+/// element/member count for arrays/objects, `None` (Nothing) otherwise. Generic over any
+/// `SelectValue`.
 fn value_length<V: SelectValue>(v: &V) -> Option<usize> {
     match v.get_type() {
-        SelectValueType::String => v.as_str().map(|s| s.chars().count()),
+        SelectValueType::String => v.as_str().map(str_length),
         SelectValueType::Array | SelectValueType::Object => v.len(),
         _ => None,
     }
@@ -541,8 +558,8 @@ fn function_length<'i, 'j, S: SelectValue>(
     arg: &TermEvaluationResult<'i, 'j, S>,
 ) -> TermEvaluationResult<'i, 'j, S> {
     match arg {
-        TermEvaluationResult::Str(s) => Some(s.chars().count()),
-        TermEvaluationResult::String(s) => Some(s.chars().count()),
+        TermEvaluationResult::Str(s) => Some(str_length(s)),
+        TermEvaluationResult::String(s) => Some(str_length(s)),
         TermEvaluationResult::Value(v) => value_length(v.as_ref()),
         TermEvaluationResult::Literal(l) => value_length(l.as_ref()),
         TermEvaluationResult::NodeList(list) if list.len() == 1 => value_length(list[0].as_ref()),
@@ -1275,6 +1292,37 @@ impl UserPathTracker for PTracker {
     }
 }
 
+impl PTracker {
+    /// The path as an RFC 9535 normalized path, e.g. `$['a'][0]`
+    #[must_use]
+    pub fn to_normalized_path(&self) -> String {
+        let mut path = String::from("$");
+        for element in &self.elements {
+            match element {
+                PTrackerElement::Key(key) => {
+                    path.push_str("['");
+                    for c in key.chars() {
+                        match c {
+                            '\\' => path.push_str("\\\\"),
+                            '\'' => path.push_str("\\'"),
+                            '\u{8}' => path.push_str("\\b"),
+                            '\u{c}' => path.push_str("\\f"),
+                            '\n' => path.push_str("\\n"),
+                            '\r' => path.push_str("\\r"),
+                            '\t' => path.push_str("\\t"),
+                            c if c < ' ' => path.push_str(&format!("\\u{:04x}", c as u32)),
+                            c => path.push(c),
+                        }
+                    }
+                    path.push_str("']");
+                }
+                PTrackerElement::Index(index) => path.push_str(&format!("[{index}]")),
+            }
+        }
+        path
+    }
+}
+
 #[allow(dead_code)]
 /* Used to generate paths trackers. */
 pub struct PTrackerGenerator;
@@ -1646,22 +1694,15 @@ impl<'i, 'j, S: SelectValue> TermEvaluationResult<'i, 'j, S> {
     }
 
     /// Length of `self` as a sized sequence — array element count, object member count,
-    /// or string char count. `None` for anything else (numbers, bools, null). Used by the
-    /// `sizeof`/`empty` operators; a multi-result (nodelist) left operand is handled
-    /// any-of by the callers, so it never reaches here.
+    /// or string code point count, the same as `length()`. `None` for anything else
+    /// (numbers, bools, null). Used by the `sizeof`/`empty` operators; a multi-result
+    /// (nodelist) left operand is handled any-of by the callers, so it never reaches here.
     fn seq_length(&self) -> Option<usize> {
-        fn arr_or_str_len<V: SelectValue>(v: &V) -> Option<usize> {
-            match v.get_type() {
-                SelectValueType::String => v.as_str().map(|s| s.chars().count()),
-                SelectValueType::Array | SelectValueType::Object => v.len(),
-                _ => None,
-            }
-        }
         match self {
-            TermEvaluationResult::Str(s) => Some(s.chars().count()),
-            TermEvaluationResult::String(s) => Some(s.chars().count()),
-            TermEvaluationResult::Value(v) => arr_or_str_len(v.as_ref()),
-            TermEvaluationResult::Literal(l) => arr_or_str_len(l.as_ref()),
+            TermEvaluationResult::Str(s) => Some(str_length(s)),
+            TermEvaluationResult::String(s) => Some(str_length(s)),
+            TermEvaluationResult::Value(v) => value_length(v.as_ref()),
+            TermEvaluationResult::Literal(l) => value_length(l.as_ref()),
             TermEvaluationResult::Results(vs) => Some(vs.len()),
             _ => None,
         }
@@ -1778,6 +1819,9 @@ pub struct CalculationResult<'i, S: SelectValue, UPT: UserPathTracker> {
 #[derive(Debug)]
 struct PathCalculatorData<'i, S: SelectValue, UPT: UserPathTracker> {
     results: Vec<CalculationResult<'i, S, UPT>>,
+    /// In counting mode, the number of matches so far. Matches are then only counted, and
+    /// never collected into `results`.
+    count: Option<usize>,
     root: ValueRef<'i, S>,
     /// Per-query compiled-regex cache (see `RegexCache`). Shared (via `Rc<RefCell<_>>`)
     /// with the data of every `@`/`$` subquery so a constant pattern inside a nested
@@ -1796,6 +1840,7 @@ impl<'i, S: SelectValue, UPT: UserPathTracker> PathCalculatorData<'i, S, UPT> {
     fn new(root: ValueRef<'i, S>) -> Self {
         PathCalculatorData {
             results: Vec::new(),
+            count: None,
             root,
             regex_cache: Rc::new(RefCell::new(HashMap::new())),
             literal_cache: Rc::new(RefCell::new(HashMap::new())),
@@ -1811,6 +1856,7 @@ impl<'i, S: SelectValue, UPT: UserPathTracker> PathCalculatorData<'i, S, UPT> {
     ) -> Self {
         PathCalculatorData {
             results: Vec::new(),
+            count: None,
             root,
             regex_cache,
             literal_cache,
@@ -2554,23 +2600,29 @@ impl<'i, UPTG: UserPathTrackerGenerator> PathCalculator<'i, UPTG> {
                         // with arrays and objects exclusively. [...] Applied
                         // to a primitive value, it selects nothing."
                     }
-                    Rule::EOI => {
-                        calc_data.results.push(CalculationResult {
-                            res: json,
-                            path_tracker: path_tracker.map(|pt| self.generate_path(pt)),
-                        });
-                    }
+                    Rule::EOI => self.add_result(json, path_tracker, calc_data),
                     _ => {
                         trace!("calc_internal: unhandled rule {:?}", curr.as_rule());
                     }
                 }
             }
-            None => {
-                calc_data.results.push(CalculationResult {
-                    res: json,
-                    path_tracker: path_tracker.map(|pt| self.generate_path(pt)),
-                });
-            }
+            None => self.add_result(json, path_tracker, calc_data),
+        }
+    }
+
+    fn add_result<'j, 'k, 'l, S: SelectValue>(
+        &self,
+        json: ValueRef<'j, S>,
+        path_tracker: Option<PathTracker<'l, 'k>>,
+        calc_data: &mut PathCalculatorData<'j, S, UPTG::PT>,
+    ) {
+        if let Some(count) = &mut calc_data.count {
+            *count += 1;
+        } else {
+            calc_data.results.push(CalculationResult {
+                res: json,
+                path_tracker: path_tracker.map(|pt| self.generate_path(pt)),
+            });
         }
     }
 
@@ -2602,6 +2654,19 @@ impl<'i, UPTG: UserPathTrackerGenerator> PathCalculator<'i, UPTG> {
         calc_data.results.drain(..).collect()
     }
 
+    /// Counts the matches of the query on the given json without collecting them, so no value
+    /// reference or path is ever built for a match
+    pub fn count_on_root<'j: 'i, S: SelectValue>(
+        &self,
+        json: ValueRef<'j, S>,
+        root: Pairs<'i, Rule>,
+    ) -> usize {
+        let mut calc_data = PathCalculatorData::<S, UPTG::PT>::new(json.clone());
+        calc_data.count = Some(0);
+        self.calc_internal(root, json, None, &mut calc_data);
+        calc_data.count.unwrap_or_default()
+    }
+
     pub fn calc_with_paths<'j: 'i, S: SelectValue>(
         &self,
         json: ValueRef<'j, S>,
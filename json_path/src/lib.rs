@@ -90,6 +90,16 @@ pub fn calc_once<'j, 'p, S: SelectValue>(q: Query<'j>, json: &'p S) -> Vec<Value
     .collect()
 }
 
+/// A version of `calc_once` that only counts the matches, without collecting them.
+pub fn calc_once_count<S: SelectValue>(q: Query, json: &S) -> usize {
+    let root = q.root;
+    PathCalculator::<DummyTrackerGenerator> {
+        query: None,
+        tracker_generator: None,
+    }
+    .count_on_root(ValueRef::Borrowed(json), root)
+}
+
 /// Calc once for a projection query (e.g. `$.a + 1`, `$arr.length()`, `$.obj.keys()`,
 /// `$.a.append(x)`): returns the flat list of computed values as impl-independent
 /// `serde_json::Value`s (empty for Nothing). A scalar projection yields a 1-element list;
@@ -585,6 +595,25 @@ mod json_path_tests {
         verify_json!(path:"$.a[?length(@) > 2]", json:{"a":[[1,2,3],[1],"abcd","x"]}, results:[[1,2,3],"abcd"]);
     }
 
+    #[test]
+    fn test_function_length_code_points() {
+        setup();
+        // string length counts code points, not bytes or UTF-16 code units
+        verify_json!(path:"$.a[?length(@) == 2]", json:{"a":["\u{e9}\u{e9}","\u{1f600}","\u{1f600}!"]}, results:["\u{e9}\u{e9}","\u{1f600}!"]);
+    }
+
+    #[test]
+    fn test_calc_once_count() {
+        setup();
+        let json = json!({"a":[{"x":1},{"x":2},{"x":3}],"b":{"x":4}});
+        let count = |path: &str| crate::calc_once_count(json_path::compile(path).unwrap(), &json);
+        assert_eq!(count("$.a[?(@.x>1)]"), 2);
+        assert_eq!(count("$..x"), 4);
+        assert_eq!(count("$[?count(@.*) == 3]"), 1);
+        assert_eq!(count("$.c"), 0);
+        assert_eq!(count("$"), 1);
+    }
+
     #[test]
     fn test_function_length_object() {
         setup();
@@ -1464,6 +1493,26 @@ mod json_path_tests {
         assert_eq!(paths2[0], vec!["\\\\".to_string()]);
     }
 
+    #[test]
+    fn test_normalized_path() {
+        setup();
+        use crate::calc_once_with_paths;
+        let test_json = json!({"a": [{"b'c": 1, "d\\e\n\u{1}": 2}]});
+        let paths = calc_once_with_paths(json_path::compile("$..*").unwrap(), &test_json)
+            .into_iter()
+            .map(|e| e.path_tracker.unwrap().to_normalized_path())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "$['a']",
+                "$['a'][0]",
+                r"$['a'][0]['b\'c']",
+                r"$['a'][0]['d\\e\n\u0001']",
+            ]
+        );
+    }
+
     /// Guards the invariant used by `calc_once_paths` / `calc_paths`: with `PTrackerGenerator`,
     /// every match has a path tracker and the path list is the same length as the value list.
     #[test]
@@ -1637,6 +1686,29 @@ mod json_path_tests {
         }
     }
 
+    #[test]
+    fn single_filter_classification() {
+        setup();
+        for path in ["$[?@>1]", "$[?(@.a==1 && @.b)]", "$[?(@>1)]"] {
+            let q = json_path::compile(path).unwrap();
+            assert!(q.is_single_filter(), "`{path}` should be a single filter");
+        }
+        for path in [
+            "$",
+            "$[*]",
+            "$[0]",
+            "$[?(@>1)]..x[?(@)]",
+            "$[?@>1].a",
+            "$.a[?@>1]",
+        ] {
+            let q = json_path::compile(path).unwrap();
+            assert!(
+                !q.is_single_filter(),
+                "`{path}` should not be a single filter"
+            );
+        }
+    }
+
     #[test]
     fn projection_no_space_is_field_not_arithmetic() {
         setup();
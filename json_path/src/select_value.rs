@@ -8,7 +8,9 @@
  */
 
 use serde::{Serialize, Serializer};
-use std::{ffi::c_void, fmt::Debug};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::{cmp::Ordering, ffi::c_void, fmt::Debug};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SelectValueType {
@@ -169,5 +171,82 @@ pub fn is_equal<T1: SelectValue, T2: SelectValue>(a: &T1, b: &T2) -> bool {
         }
 }
 
+/// Feeds `v` into `state` consistently with [`is_equal`]: values that are equal hash the same,
+/// including objects holding the same keys in a different order.
+pub fn hash_value<T: SelectValue, H: Hasher>(v: &T, state: &mut H) {
+    let t = v.get_type();
+    std::mem::discriminant(&t).hash(state);
+    match t {
+        SelectValueType::Null => {}
+        SelectValueType::Bool => v.get_bool().hash(state),
+        SelectValueType::Long => v.get_long().hash(state),
+        // 0.0 and -0.0 are equal, so they must hash the same
+        SelectValueType::Double => v
+            .get_double()
+            .map(|d| if d == 0.0 { 0.0f64 } else { d }.to_bits())
+            .hash(state),
+        SelectValueType::String => v.as_str().hash(state),
+        SelectValueType::Array => {
+            v.len().hash(state);
+            v.values()
+                .into_iter()
+                .flatten()
+                .for_each(|e| hash_value(e.as_ref(), state));
+        }
+        SelectValueType::Object => {
+            // Combine the entries with a commutative operation so key order does not matter
+            let entries = v.items().into_iter().flatten().fold(0u64, |acc, (k, e)| {
+                let mut entry = DefaultHasher::new();
+                k.hash(&mut entry);
+                hash_value(e.as_ref(), &mut entry);
+                acc.wrapping_add(entry.finish())
+            });
+            v.len().hash(state);
+            entries.hash(state);
+        }
+    }
+}
+
+/// Total order over JSON values, used to keep arrays sorted:
+/// null < booleans < numbers < strings < arrays < objects.
+/// Numbers compare by value whether they are stored as integers or doubles, strings by their
+/// bytes, arrays element-wise and then by length, and objects only by their number of keys.
+pub fn compare_values<T1: SelectValue, T2: SelectValue>(a: &T1, b: &T2) -> Ordering {
+    const fn rank(t: SelectValueType) -> u8 {
+        match t {
+            SelectValueType::Null => 0,
+            SelectValueType::Bool => 1,
+            SelectValueType::Long | SelectValueType::Double => 2,
+            SelectValueType::String => 3,
+            SelectValueType::Array => 4,
+            SelectValueType::Object => 5,
+        }
+    }
+    fn as_f64<T: SelectValue>(v: &T) -> f64 {
+        match v.get_type() {
+            SelectValueType::Long => v.get_long().unwrap_or_default() as f64,
+            _ => v.get_double().unwrap_or_default(),
+        }
+    }
+
+    match (a.get_type(), b.get_type()) {
+        (SelectValueType::Long, SelectValueType::Long) => a.get_long().cmp(&b.get_long()),
+        (ta, tb) if rank(ta) != rank(tb) => rank(ta).cmp(&rank(tb)),
+        (SelectValueType::Null, _) => Ordering::Equal,
+        (SelectValueType::Bool, _) => a.get_bool().cmp(&b.get_bool()),
+        (SelectValueType::Long | SelectValueType::Double, _) => as_f64(a).total_cmp(&as_f64(b)),
+        (SelectValueType::String, _) => a.as_str().cmp(&b.as_str()),
+        (SelectValueType::Array, _) => match (a.values(), b.values()) {
+            (Some(ait), Some(bit)) => ait
+                .zip(bit)
+                .map(|(a, b)| compare_values(a.as_ref(), b.as_ref()))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            _ => Ordering::Equal,
+        },
+        (SelectValueType::Object, _) => a.len().cmp(&b.len()),
+    }
+}
+
 #[allow(unused)]
 pub const MAX_DEPTH: usize = 128;
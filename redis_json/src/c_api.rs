@@ -25,7 +25,7 @@ use redis_module::{key::KeyFlags, Context, RedisString, Status};
 
 use crate::manager::{Manager, ReadHolder};
 
-pub const REDIS_JSONAPI_LATEST_API_VER: usize = 9;
+pub const REDIS_JSONAPI_LATEST_API_VER: usize = 10;
 
 //
 // structs
@@ -927,6 +927,19 @@ macro_rules! redis_json_module_export_shared_api {
             )
         }
 
+        #[no_mangle]
+        pub extern "C" fn JSONAPI_subscribeToPathEvents(
+            callback: Option<$crate::notify::PathEventCallback>,
+        ) -> c_int {
+            match callback {
+                Some(callback) => {
+                    $crate::notify::subscribe(callback);
+                    redis_module::Status::Ok as c_int
+                }
+                None => redis_module::Status::Err as c_int,
+            }
+        }
+
         // The apiname argument of export_shared_api should be a string literal with static lifetime
         static mut VEC_EXPORT_SHARED_API_NAME : Vec<CString> = Vec::new();
 
@@ -989,6 +1002,8 @@ macro_rules! redis_json_module_export_shared_api {
             getJsonFromHandle: JSONAPI_getJsonFromHandle,
             // V9 entries
             getWithPath: JSONAPI_getWithPath,
+            // V10 entries
+            subscribeToPathEvents: JSONAPI_subscribeToPathEvents,
         };
 
         #[repr(C)]
@@ -1058,6 +1073,8 @@ macro_rules! redis_json_module_export_shared_api {
             pub getJsonFromHandle: extern "C" fn(key: *mut rawmod::RedisModuleKey) -> *mut c_void,
             // V9 entries
             pub getWithPath: extern "C" fn(val: *const c_void, path: *const c_void) -> *const c_void,
+            // V10 entries
+            pub subscribeToPathEvents: extern "C" fn(callback: Option<$crate::notify::PathEventCallback>) -> c_int,
         }
     };
 }
@@ -1103,6 +1120,7 @@ mod tests {
     fn test_json_api_alloc_and_deref() {
         let wrapper_ptr = json_api_alloc_json(RedisIValueJsonKeyManager {
             phantom: PhantomData,
+            patches: Default::default(),
         });
 
         // Simulate C code: cast to RedisJSON* (which is void**)
@@ -1115,6 +1133,7 @@ mod tests {
         json_api_free_json(
             RedisIValueJsonKeyManager {
                 phantom: PhantomData,
+                patches: Default::default(),
             },
             wrapper_ptr,
         );
@@ -1131,6 +1150,7 @@ mod tests {
         let res = json_api_get(
             RedisIValueJsonKeyManager {
                 phantom: PhantomData,
+                patches: Default::default(),
             },
             &doc as *const IValue as *const c_void,
             path.as_ptr(),
@@ -1145,6 +1165,7 @@ mod tests {
     fn ivalue_mngr() -> RedisIValueJsonKeyManager<'static> {
         RedisIValueJsonKeyManager {
             phantom: PhantomData,
+            patches: Default::default(),
         }
     }
 
@@ -1231,12 +1252,14 @@ mod tests {
 
             let result_wrapper = json_api_alloc_json(RedisIValueJsonKeyManager {
                 phantom: PhantomData,
+                patches: Default::default(),
             });
 
             for i in 0..array.len().unwrap() {
                 let status = json_api_get_at(
                     RedisIValueJsonKeyManager {
                         phantom: PhantomData,
+                        patches: Default::default(),
                     },
                     array_ptr,
                     i,
@@ -1253,6 +1276,7 @@ mod tests {
             let status = json_api_get_at(
                 RedisIValueJsonKeyManager {
                     phantom: PhantomData,
+                    patches: Default::default(),
                 },
                 array_ptr,
                 array.len().unwrap(),
@@ -1264,6 +1288,7 @@ mod tests {
             json_api_free_json(
                 RedisIValueJsonKeyManager {
                     phantom: PhantomData,
+                    patches: Default::default(),
                 },
                 result_wrapper,
             );
@@ -1278,6 +1303,7 @@ mod tests {
             let result_ptr = json_api_get_array(
                 RedisIValueJsonKeyManager {
                     phantom: PhantomData,
+                    patches: Default::default(),
                 },
                 value as *const IValue as *const c_void,
                 &mut len,
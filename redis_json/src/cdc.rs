@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Change data capture of JSON writes.
+//!
+//! With the `CDC_PATTERN <glob>` module argument, each write to a key matching the pattern
+//! appends an entry to the stream named by `CDC_STREAM` (`json:cdc` by default), within the
+//! command that made it. The entry has the `key`, the `command` and the `paths` it touched,
+//! a JSON array of normalized paths. With `CDC_VALUES yes`, it also has the `old` and `new`
+//! values of these paths, as JSON objects keyed by path that leave out the paths missing
+//! before or after the write.
+//!
+//! The entry is replicated with the ID it got, and replicas never append entries of their own.
+//!
+//! In cluster mode, the entry must be in the slot of the written key, so each slot has its own
+//! stream, named by suffixing `CDC_STREAM` with the hash tag of the key: the one the key has, or
+//! else the whole key, e.g. `json:cdc{user}` for `{user}:1` and `json:cdc{user:1}` for `user:1`.
+//! The writes to the few keys with no hash tag but a `}`, whose slot no such name shares, are not
+//! captured.
+
+use crate::notify::normalized_path;
+use ijson::IValue;
+use itertools::Itertools;
+use json_path::select_value::{SelectValue, SelectValueType};
+use redis_module::{Context, ContextFlags, RedisLogLevel, RedisString, RedisValue};
+use std::sync::Mutex;
+
+pub const DEFAULT_STREAM: &str = "json:cdc";
+
+struct Config {
+    pattern: Vec<u8>,
+    stream: Vec<u8>,
+    values: bool,
+}
+
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+
+/// Enables the capture of the writes to the keys matching `pattern` into `stream`
+pub fn enable(pattern: &str, stream: &str, values: bool) {
+    *CONFIG.lock().unwrap() = Some(Config {
+        pattern: pattern.as_bytes().to_vec(),
+        stream: stream.as_bytes().to_vec(),
+        values,
+    });
+}
+
+/// The writes to one key, captured until they are appended to the stream
+pub struct Capture {
+    stream: Vec<u8>,
+    values: bool,
+    /// The serialized values of the written paths before their first write, `None` when missing
+    old_values: Vec<(Vec<String>, Option<String>)>,
+}
+
+/// Starts capturing the writes to `key`, if they are to be captured
+pub fn capture(ctx: &Context, key: &RedisString) -> Option<Capture> {
+    // A replica gets the entries of its master
+    if ctx
+        .get_flags()
+        .intersects(ContextFlags::REPLICATED | ContextFlags::SLAVE | ContextFlags::LOADING)
+    {
+        return None;
+    }
+    let config = CONFIG.lock().unwrap();
+    let config = config.as_ref()?;
+    if !glob_match(&config.pattern, key.as_slice()) {
+        return None;
+    }
+    let stream = if ctx.get_flags().contains(ContextFlags::CLUSTER) {
+        let Some(tag) = slot_stream_tag(key.as_slice()) else {
+            ctx.log(
+                RedisLogLevel::Warning,
+                "Not capturing a write to a key whose slot has no change data capture stream",
+            );
+            return None;
+        };
+        [config.stream.as_slice(), b"{", tag, b"}"].concat()
+    } else {
+        config.stream.clone()
+    };
+    if key.as_slice() == stream {
+        return None;
+    }
+    Some(Capture {
+        stream,
+        values: config.values,
+        old_values: Vec::new(),
+    })
+}
+
+impl Capture {
+    /// Keeps the value at `path` in `doc`, which is about to be written
+    pub fn before_write(&mut self, doc: Option<&IValue>, path: &[String]) {
+        if !self.values || self.old_values.iter().any(|(p, _)| p == path) {
+            return;
+        }
+        let old = doc.and_then(|doc| serialize_at(doc, path));
+        self.old_values.push((path.to_vec(), old));
+    }
+
+    /// Appends the entry of the writes to `key`, which touched `paths` of what is now `doc`
+    pub fn append(
+        &mut self,
+        ctx: &Context,
+        command: &str,
+        key: &RedisString,
+        doc: Option<&IValue>,
+        paths: &[Vec<String>],
+    ) {
+        let old_values = std::mem::take(&mut self.old_values);
+        if paths.is_empty() {
+            return;
+        }
+        let paths = paths
+            .iter()
+            .map(|path| (normalized_path(doc, path), path))
+            .unique_by(|(normalized, _)| normalized.clone())
+            .collect_vec();
+
+        let mut fields = vec![
+            ("key", key.as_slice().to_vec()),
+            ("command", command.as_bytes().to_vec()),
+            (
+                "paths",
+                json_array(paths.iter().map(|(normalized, _)| json_string(normalized))),
+            ),
+        ];
+        if self.values {
+            let old = paths.iter().filter_map(|(normalized, path)| {
+                let (_, old) = old_values.iter().find(|(p, _)| p == *path)?;
+                Some((normalized, old.clone()?))
+            });
+            let new = paths
+                .iter()
+                .filter_map(|(normalized, path)| Some((normalized, serialize_at(doc?, path)?)));
+            fields.push(("old", json_object(old)));
+            fields.push(("new", json_object(new)));
+        }
+
+        let mut args = [self.stream.as_slice(), b"*".as_slice()]
+            .into_iter()
+            .chain(
+                fields
+                    .iter()
+                    .flat_map(|(field, value)| [field.as_bytes(), value.as_slice()]),
+            )
+            .collect_vec();
+        match ctx.call("XADD", args.as_slice()) {
+            Ok(RedisValue::SimpleString(id) | RedisValue::BulkString(id)) => {
+                // The ID is what makes the entry of a replica the same
+                args[1] = id.as_bytes();
+                ctx.replicate("XADD", args.as_slice());
+            }
+            Ok(reply) => ctx.log(
+                RedisLogLevel::Warning,
+                &format!("Unexpected reply to the change data capture XADD: {reply:?}"),
+            ),
+            Err(e) => ctx.log(
+                RedisLogLevel::Warning,
+                &format!("Failed appending to the change data capture stream: {e}"),
+            ),
+        }
+    }
+}
+
+/// The hash tag giving the stream of `key` its slot: the one of `key`, or else `key` itself
+/// unless a `}` in it would end the tag early
+fn slot_stream_tag(key: &[u8]) -> Option<&[u8]> {
+    let tag = key
+        .iter()
+        .position(|&c| c == b'{')
+        .and_then(|start| {
+            let len = key[start + 1..].iter().position(|&c| c == b'}')?;
+            Some(&key[start + 1..start + 1 + len])
+        })
+        .filter(|tag| !tag.is_empty());
+    match tag {
+        Some(tag) => Some(tag),
+        None if key.contains(&b'}') => None,
+        None => Some(key),
+    }
+}
+
+/// The serialized value at `path` in `doc`, whose elements are indices of arrays
+fn serialize_at(doc: &IValue, path: &[String]) -> Option<String> {
+    let Some((element, rest)) = path.split_first() else {
+        return serde_json::to_string(doc).ok();
+    };
+    let child = if doc.get_type() == SelectValueType::Array {
+        doc.get_index(element.parse().ok()?)
+    } else {
+        doc.get_key(element)
+    }?;
+    serialize_at(&child, rest)
+}
+
+fn json_string(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+fn json_array(values: impl Iterator<Item = String>) -> Vec<u8> {
+    format!("[{}]", values.format(",")).into_bytes()
+}
+
+fn json_object<'a>(members: impl Iterator<Item = (&'a String, String)>) -> Vec<u8> {
+    let members = members.map(|(name, value)| format!("{}:{value}", json_string(name)));
+    format!("{{{}}}", members.format(",")).into_bytes()
+}
+
+/// Matches `s` against the glob-style `pattern`, as KEYS does: `*` and `?` match any
+/// characters and any one character, `[...]` one of a set or range, and `\` escapes
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| glob_match(rest, &s[i..])),
+        Some((b'?', rest)) => !s.is_empty() && glob_match(rest, &s[1..]),
+        Some((b'[', rest)) => {
+            let Some((&c, s_rest)) = s.split_first() else {
+                return false;
+            };
+            let (negate, mut set) = match rest.split_first() {
+                Some((b'^', set)) => (true, set),
+                _ => (false, rest),
+            };
+            let mut matched = false;
+            loop {
+                match set {
+                    [] => break,
+                    [b']', ..] => {
+                        set = &set[1..];
+                        break;
+                    }
+                    [b'\\', escaped, ..] => {
+                        matched |= *escaped == c;
+                        set = &set[2..];
+                    }
+                    [start, b'-', end, ..] if *end != b']' => {
+                        let (lo, hi) = if start <= end {
+                            (*start, *end)
+                        } else {
+                            (*end, *start)
+                        };
+                        matched |= (lo..=hi).contains(&c);
+                        set = &set[3..];
+                    }
+                    [other, ..] => {
+                        matched |= *other == c;
+                        set = &set[1..];
+                    }
+                }
+            }
+            matched != negate && glob_match(set, s_rest)
+        }
+        Some((b'\\', [escaped, rest @ ..])) => {
+            s.first() == Some(escaped) && glob_match(rest, &s[1..])
+        }
+        Some((p, rest)) => s.first() == Some(p) && glob_match(rest, &s[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"user:*", b"user:1"));
+        assert!(!glob_match(b"user:*", b"order:1"));
+        assert!(glob_match(b"user:?", b"user:1"));
+        assert!(!glob_match(b"user:?", b"user:12"));
+        assert!(glob_match(b"*:[0-9]", b"user:7"));
+        assert!(!glob_match(b"*:[^0-9]", b"user:7"));
+        assert!(glob_match(b"[ab]*", b"b"));
+        assert!(glob_match(b"a\\*", b"a*"));
+        assert!(!glob_match(b"a\\*", b"ab"));
+    }
+
+    #[test]
+    fn test_slot_stream_tag() {
+        assert_eq!(slot_stream_tag(b"{user}:1"), Some(b"user".as_slice()));
+        assert_eq!(slot_stream_tag(b"a{b}{c}"), Some(b"b".as_slice()));
+        assert_eq!(slot_stream_tag(b"user:1"), Some(b"user:1".as_slice()));
+        // No tag, so the whole key is hashed
+        assert_eq!(slot_stream_tag(b"a{b"), Some(b"a{b".as_slice()));
+        assert_eq!(slot_stream_tag(b"a{}b"), None);
+        assert_eq!(slot_stream_tag(b"a}b"), None);
+    }
+
+    #[test]
+    fn test_serialize_at() {
+        let doc: IValue =
+            serde_json::from_value(serde_json::json!({"a": [1, {"b": "x"}]})).unwrap();
+        let path = |elements: &[&str]| elements.iter().map(ToString::to_string).collect_vec();
+        assert_eq!(
+            serialize_at(&doc, &path(&["a", "1", "b"])).unwrap(),
+            "\"x\""
+        );
+        assert_eq!(
+            serialize_at(&doc, &path(&["a"])).unwrap(),
+            "[1,{\"b\":\"x\"}]"
+        );
+        assert_eq!(serialize_at(&doc, &path(&["a", "2"])), None);
+        assert_eq!(serialize_at(&doc, &path(&["c"])), None);
+    }
+}
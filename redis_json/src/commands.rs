@@ -8,21 +8,34 @@
  */
 
 use crate::defrag::defrag_info;
+use crate::expiry;
+use crate::flat;
 use crate::formatter::ReplyFormatOptions;
-use crate::key_value::KeyValue;
+use crate::key_value::{ArrIndexMatcher, ArrIndexQuery, KeyValue};
+use crate::load::{self, KeyTemplate};
 use crate::manager::{
-    err_invalid_path, err_invalid_path_or, err_projection_readonly, Manager, ReadHolder,
-    UpdateInfo, WriteHolder,
+    err_invalid_path, err_invalid_path_or, err_json, err_not_a_number, err_projection_readonly,
+    err_recursion_limit_exceeded, AddUpdateInfo, Manager, ReadHolder, SetUpdateInfo, UpdateInfo,
+    WriteHolder,
 };
-use crate::redisjson::{Format, Path, ReplyFormat, SetOptions, JSON_ROOT_PATH};
-use ijson::FloatType;
-use json_path::select_value::{SelectValue, SelectValueType, ValueRef};
-use redis_module::{Context, RedisValue};
+use crate::notify::normalized_path;
+use crate::redisjson::{Format, Path, ReplyFormat, SetOptions, StrUnit, JSON_ROOT_PATH};
+use crate::wait;
+use ijson::{FloatType, IValue};
+use json_path::select_value::{SelectValue, SelectValueType, ValueRef, MAX_DEPTH};
+use redis_module::raw;
+use redis_module::redisvalue::RedisValueKey;
+use redis_module::{AclPermissions, Context, ContextFlags, NotifyEvent, RedisValue};
 use redis_module::{NextArg, RedisError, RedisResult, RedisString, REDIS_OK};
 use std::cmp::Ordering;
+use std::os::raw::c_int;
+use std::ptr::null_mut;
 use std::str::FromStr;
 
-use json_path::{calc_once_with_paths, compile, json_path::UserPathTracker};
+use json_path::{
+    calc_once_count, calc_once_with_paths, compile, create,
+    json_path::{PTracker, PTrackerElement, Query, UserPathTracker},
+};
 
 use serde_json::{Number, Value};
 
@@ -260,7 +273,8 @@ pub fn json_get_command_impl<M: Manager>(
 }
 
 ///
-/// JSON.SET <key> <path> <json> [NX | XX | FORMAT <format>]
+/// JSON.SET <key> <path> <json> [<path> <json> ...] [NX | XX | FORMAT <format> | CREATEPATH]
+///          [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
 ///
 #[macro_export]
 macro_rules! json_set_command {
@@ -295,6 +309,21 @@ macro_rules! json_set_command {
                         name: "json",
                         arg_type: String,
                     },
+                    {
+                        name: "pair",
+                        arg_type: Block,
+                        flags: [Optional, Multiple],
+                        subargs: [
+                            {
+                                name: "path",
+                                arg_type: String,
+                            },
+                            {
+                                name: "json",
+                                arg_type: String,
+                            }
+                        ]
+                    },
                     {
                         name: "condition",
                         arg_type: OneOf,
@@ -374,6 +403,44 @@ macro_rules! json_set_command {
 
                                 }
                             ]
+                        },
+                        {
+                            name: "createpath",
+                            arg_type: PureToken,
+                            token: "CREATEPATH",
+                            flags: [Optional],
+                        },
+                        {
+                            name: "expiration",
+                            arg_type: OneOf,
+                            flags: [Optional],
+                            subargs: [
+                                {
+                                    name: "seconds",
+                                    arg_type: Integer,
+                                    token: "EX",
+                                },
+                                {
+                                    name: "milliseconds",
+                                    arg_type: Integer,
+                                    token: "PX",
+                                },
+                                {
+                                    name: "unix-time-seconds",
+                                    arg_type: Integer,
+                                    token: "EXAT",
+                                },
+                                {
+                                    name: "unix-time-milliseconds",
+                                    arg_type: Integer,
+                                    token: "PXAT",
+                                },
+                                {
+                                    name: "keepttl",
+                                    arg_type: PureToken,
+                                    token: "KEEPTTL",
+                                }
+                            ]
                         }
                 ],
             }
@@ -385,18 +452,36 @@ pub fn json_set_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
-) -> RedisResult {
-    let mut args = args.into_iter().skip(1);
-
-    let key = args.next_arg()?;
-    let path = Path::new(args.next_str()?);
-    let value = args.next_str()?;
+) -> RedisResult
+where
+    M::O: SelectValue,
+{
+    let [_, key, path, value, ..] = args.as_slice() else {
+        return Err(RedisError::WrongArity);
+    };
+    let mut pairs = vec![(Path::new(path.try_as_str()?), value.try_as_str()?)];
+
+    // More path/value pairs may follow until the first option. Their paths must start with `$`
+    // or `.`, so a bare word is always an option, even where it is also a legacy path
+    let mut options = 4;
+    while let Some(path) = args.get(options).filter(|arg| is_additional_path(arg)) {
+        let value = args.get(options + 1).ok_or(RedisError::WrongArity)?;
+        pairs.push((Path::new(path.try_as_str()?), value.try_as_str()?));
+        options += 2;
+    }
 
     let mut format = Format::JSON;
     let mut set_option = SetOptions::None;
     let mut fpha_type = None;
-    while let Some(s) = args.next() {
+    let mut create_path = false;
+    let mut expiration = None;
+    let mut option_args = args[options..].iter().cloned();
+    while let Some(s) = option_args.next() {
         match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("CREATEPATH") => create_path = true,
+            arg if is_key_expiration_option(arg) && expiration.is_none() => {
+                expiration = Some(parse_key_expiration(arg, &mut option_args, "json.set")?);
+            }
             arg if arg.eq_ignore_ascii_case("NX") && set_option == SetOptions::None => {
                 set_option = SetOptions::NotExists
             }
@@ -404,27 +489,47 @@ pub fn json_set_command_impl<M: Manager>(
                 set_option = SetOptions::AlreadyExists
             }
             arg if arg.eq_ignore_ascii_case("FORMAT") => {
-                format = Format::from_str(args.next_str()?)?;
+                format = Format::from_str(option_args.next_str()?)?;
             }
             arg if arg.eq_ignore_ascii_case("FPHA") => {
-                fpha_type = {
-                    match args.next_str()?.to_uppercase().as_str() {
-                        "BF16" => Some(FloatType::BF16),
-                        "FP16" => Some(FloatType::F16),
-                        "FP32" => Some(FloatType::F32),
-                        "FP64" => Some(FloatType::F64),
-                        _ => return Err(RedisError::Str("ERR invalid FPHA type")),
-                    }
-                }
+                fpha_type = Some(parse_fpha_type(option_args.next_str()?)?);
             }
             _ => return Err(RedisError::Str("ERR syntax error")),
         };
     }
 
-    let mut redis_key = manager.open_key_write(ctx, key)?;
-    let current = redis_key.get_value()?;
+    let mut redis_key = manager.open_key_write(ctx, key.clone())?;
+    let mut pairs: Vec<_> = pairs
+        .into_iter()
+        .map(|(path, value)| Ok((path, manager.from_str(value, format, true, fpha_type)?)))
+        .try_collect::<_, _, RedisError>()?;
+    let key_expiration = ExpiringWrite {
+        key,
+        expiration,
+        args: &args,
+        options,
+    };
+    if pairs.len() > 1 {
+        return json_set_multi(
+            manager,
+            ctx,
+            &mut redis_key,
+            pairs,
+            set_option,
+            create_path,
+            key_expiration,
+        );
+    }
+    // SAFETY: there is at least one pair
+    let (path, val) = pairs.pop().unwrap();
 
-    let val = manager.from_str(value, format, true, fpha_type)?;
+    let current = redis_key.get_value()?;
+    let missing = match current.as_deref() {
+        Some(doc) if create_path && set_option != SetOptions::AlreadyExists => {
+            missing_parents(doc, &path)?
+        }
+        _ => None,
+    };
 
     match (current, set_option) {
         (Some(doc), op) => {
@@ -432,13 +537,16 @@ pub fn json_set_command_impl<M: Manager>(
                 if op != SetOptions::NotExists {
                     redis_key.set_value(vec![], val)?;
                     redis_key.notify_keyspace_event(ctx, "json.set")?;
-                    manager.apply_changes(ctx);
+                    apply_changes_with_expiration(&manager, ctx, &mut redis_key, key_expiration)?;
                     REDIS_OK
                 } else {
                     Ok(RedisValue::Null)
                 }
             } else {
-                let update_info = KeyValue::new(doc).find_paths(path.get_path(), op)?;
+                let (update_info, val) = match missing {
+                    Some(missing) => nested_update(&manager, missing, val)?,
+                    None => (KeyValue::new(doc).find_paths(path.get_path(), op)?, val),
+                };
                 if update_info.is_empty() {
                     Ok(RedisValue::Null)
                 } else {
@@ -448,7 +556,12 @@ pub fn json_set_command_impl<M: Manager>(
                     // But only return OK if all paths are updated, otherwise return null
                     if result.any_updated() {
                         redis_key.notify_keyspace_event(ctx, "json.set")?;
-                        manager.apply_changes(ctx);
+                        apply_changes_with_expiration(
+                            &manager,
+                            ctx,
+                            &mut redis_key,
+                            key_expiration,
+                        )?;
                     }
                     if result.all_updated() {
                         REDIS_OK
@@ -463,7 +576,7 @@ pub fn json_set_command_impl<M: Manager>(
             if path == JSON_ROOT_PATH {
                 redis_key.set_value(Vec::new(), val)?;
                 redis_key.notify_keyspace_event(ctx, "json.set")?;
-                manager.apply_changes(ctx);
+                apply_changes_with_expiration(&manager, ctx, &mut redis_key, key_expiration)?;
                 REDIS_OK
             } else {
                 Err(RedisError::Str(
@@ -474,8 +587,231 @@ pub fn json_set_command_impl<M: Manager>(
     }
 }
 
+fn parse_fpha_type(arg: &str) -> RedisResult<FloatType> {
+    match arg.to_uppercase().as_str() {
+        "BF16" => Ok(FloatType::BF16),
+        "FP16" => Ok(FloatType::F16),
+        "FP32" => Ok(FloatType::F32),
+        "FP64" => Ok(FloatType::F64),
+        _ => Err(RedisError::Str("ERR invalid FPHA type")),
+    }
+}
+
+/// Whether `arg` is the path of a path/value pair following the first one of JSON.SET
+fn is_additional_path(arg: &RedisString) -> bool {
+    matches!(arg.as_slice().first(), Some(b'$' | b'.'))
+}
+
+/// The `EX | PX | EXAT | PXAT | KEEPTTL` option of JSON.SET, JSON.MERGE and JSON.MSET
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum KeyExpiration {
+    /// The key keeps its time to live, as it does without any of these options
+    Keep,
+    /// The key expires at this time, in milliseconds since the Unix epoch
+    At(i64),
+}
+
+fn is_key_expiration_option(arg: &str) -> bool {
+    ["EX", "PX", "EXAT", "PXAT", "KEEPTTL"]
+        .iter()
+        .any(|option| arg.eq_ignore_ascii_case(option))
+}
+
+/// Parses the key expiration `option`, taking its time from `args`
+fn parse_key_expiration<I: Iterator<Item = RedisString>>(
+    option: &str,
+    args: &mut I,
+    command: &str,
+) -> RedisResult<KeyExpiration> {
+    if option.eq_ignore_ascii_case("KEEPTTL") {
+        return Ok(KeyExpiration::Keep);
+    }
+    let time = args.next_i64()?;
+    let at = match option.to_ascii_uppercase().as_str() {
+        _ if time <= 0 => None,
+        "EX" => time
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(expiry::now_ms())),
+        "PX" => time.checked_add(expiry::now_ms()),
+        "EXAT" => time.checked_mul(1000),
+        _ => Some(time),
+    };
+    at.map(KeyExpiration::At).ok_or_else(|| {
+        RedisError::String(format!("ERR invalid expire time in '{command}' command"))
+    })
+}
+
+/// A write given a key expiration option: the key it writes, and its arguments, from the
+/// command name on, with its options from `options` on
+#[derive(Clone, Copy)]
+struct ExpiringWrite<'a> {
+    key: &'a RedisString,
+    expiration: Option<KeyExpiration>,
+    args: &'a [RedisString],
+    options: usize,
+}
+
+/// Sets the expiration time of a written key, when given, and replicates the write without its
+/// key expiration option, followed by a PEXPIREAT of the key, which a replica applies along
+/// with the write
+fn apply_changes_with_expiration<M: Manager>(
+    manager: &M,
+    ctx: &Context,
+    redis_key: &mut M::WriteHolder,
+    write: ExpiringWrite,
+) -> RedisResult<()> {
+    let Some(KeyExpiration::At(at)) = write.expiration else {
+        manager.apply_changes(ctx);
+        return Ok(());
+    };
+    set_key_expiration::<M>(ctx, redis_key, write.key, at)?;
+    let (command, options) = write.args.split_at(write.options);
+    let mut replicated = command[1..].iter().map(RedisString::as_slice).collect_vec();
+    strip_key_expiration(options, &mut replicated);
+    manager.apply_changes_as(ctx, command[0].try_as_str()?, &replicated);
+    replicate_key_expiration(ctx, write.key, at);
+    Ok(())
+}
+
+/// Adds `options` to the arguments `replicated`, except for the key expiration options, whose
+/// time may be relative to the clock of the master: the PEXPIREAT following the write sets it
+fn strip_key_expiration<'a>(options: &'a [RedisString], replicated: &mut Vec<&'a [u8]>) {
+    let mut i = 0;
+    while let Some(option) = options.get(i) {
+        // The options other than the modifiers of JSON.MSET, e.g. CREATEPATH, take no argument
+        let len = mset_modifier_len(option).unwrap_or(1);
+        if !option.try_as_str().is_ok_and(is_key_expiration_option) {
+            let end = (i + len).min(options.len());
+            replicated.extend(options[i..end].iter().map(RedisString::as_slice));
+        }
+        i += len;
+    }
+}
+
+fn set_key_expiration<M: Manager>(
+    ctx: &Context,
+    redis_key: &mut M::WriteHolder,
+    key: &RedisString,
+    at: i64,
+) -> RedisResult<()> {
+    redis_key.set_expire_at(at)?;
+    ctx.notify_keyspace_event(NotifyEvent::GENERIC, "expire", key);
+    Ok(())
+}
+
+/// Replicates the expiration time of `key` as an absolute time, so it doesn't drift on replicas
+fn replicate_key_expiration(ctx: &Context, key: &RedisString, at: i64) {
+    ctx.replicate("PEXPIREAT", &[key.as_slice(), at.to_string().as_bytes()]);
+}
+
+/// JSON.SET with more than one path/value pair. All paths are resolved against the document
+/// before any value is set, and the pairs are set all or none: each path must exist with `XX`,
+/// be missing with `NX`, and be settable without either, and each value must fit below the
+/// nesting limit, or the command replies null without writing anything. No pair may write at,
+/// above or below a path written by another, which would invalidate the paths resolved for it.
+fn json_set_multi<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    redis_key: &mut M::WriteHolder,
+    pairs: Vec<(Path, M::O)>,
+    set_option: SetOptions,
+    create_path: bool,
+    key_expiration: ExpiringWrite,
+) -> RedisResult
+where
+    M::O: SelectValue,
+{
+    if pairs.iter().any(|(path, _)| *path == JSON_ROOT_PATH) {
+        return Err(RedisError::Str(
+            "ERR the root path can't be set along with other paths",
+        ));
+    }
+    if redis_key.get_value()?.is_none() {
+        return if set_option == SetOptions::AlreadyExists {
+            Ok(RedisValue::Null)
+        } else {
+            Err(RedisError::Str(
+                "ERR new objects must be created at the root",
+            ))
+        };
+    }
+
+    let doc: &M::V = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let mut key_value = KeyValue::new(doc);
+    let updates: Vec<_> = pairs
+        .into_iter()
+        .map(|(path, val)| {
+            let missing = if create_path && set_option != SetOptions::AlreadyExists {
+                missing_parents(doc, &path)?
+            } else {
+                None
+            };
+            match missing {
+                Some(missing) => nested_update(&manager, missing, val),
+                None => Ok((key_value.find_paths(path.get_path(), set_option)?, val)),
+            }
+        })
+        .try_collect()?;
+    if updates
+        .iter()
+        .any(|(update_info, _)| update_info.is_empty())
+    {
+        return Ok(RedisValue::Null);
+    }
+    let written = updates
+        .iter()
+        .map(|(update_info, _)| update_info.iter().map(written_path).collect_vec())
+        .collect_vec();
+    // Checked up front, as a value nested too deep would be the only write to fail
+    let too_deep = updates.iter().zip(&written).any(|((_, val), paths)| {
+        let depth = val.calculate_value_depth();
+        paths.iter().any(|path| path.len() + depth >= MAX_DEPTH)
+    });
+    if too_deep {
+        return Ok(RedisValue::Null);
+    }
+    let overlap = written.iter().enumerate().any(|(i, paths)| {
+        written[i + 1..].iter().flatten().any(|other| {
+            paths
+                .iter()
+                .any(|path| path.starts_with(other) || other.starts_with(path))
+        })
+    });
+    if overlap {
+        return Err(RedisError::Str(
+            "ERR the paths set along with each other can't overlap",
+        ));
+    }
+
+    let result = updates
+        .into_iter()
+        .map(|(update_info, val)| apply_updates::<M>(redis_key, val, update_info))
+        .reduce(ApplyUpdatesResult::combine)
+        .unwrap_or(ApplyUpdatesResult::NoneUpdated);
+    if result.any_updated() {
+        redis_key.notify_keyspace_event(ctx, "json.set")?;
+        apply_changes_with_expiration(&manager, ctx, redis_key, key_expiration)?;
+    }
+    if result.all_updated() {
+        REDIS_OK
+    } else {
+        Ok(RedisValue::Null)
+    }
+}
+
+/// The path of the value written by an update
+fn written_path(update_info: &UpdateInfo) -> Vec<String> {
+    match update_info {
+        UpdateInfo::SUI(sui) => sui.path.clone(),
+        UpdateInfo::AUI(aui) => [aui.path.as_slice(), &[aui.key.clone()]].concat(),
+    }
+}
+
 ///
-/// JSON.MERGE <key> <path> <json> [FORMAT <format>]
+/// JSON.MERGE <key> <path> <json> [FORMAT <format>] [CREATEPATH]
+///            [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
 ///
 #[macro_export]
 macro_rules! json_merge_command {
@@ -538,6 +874,44 @@ macro_rules! json_merge_command {
                                 ]
                             }
                         ]
+                    },
+                    {
+                        name: "createpath",
+                        arg_type: PureToken,
+                        token: "CREATEPATH",
+                        flags: [Optional],
+                    },
+                    {
+                        name: "expiration",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "seconds",
+                                arg_type: Integer,
+                                token: "EX",
+                            },
+                            {
+                                name: "milliseconds",
+                                arg_type: Integer,
+                                token: "PX",
+                            },
+                            {
+                                name: "unix-time-seconds",
+                                arg_type: Integer,
+                                token: "EXAT",
+                            },
+                            {
+                                name: "unix-time-milliseconds",
+                                arg_type: Integer,
+                                token: "PXAT",
+                            },
+                            {
+                                name: "keepttl",
+                                arg_type: PureToken,
+                                token: "KEEPTTL",
+                            }
+                        ]
                     }
                 ]
             }
@@ -551,38 +925,60 @@ pub fn json_merge_command_impl<M: Manager>(
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    let mut args = args.into_iter().skip(1);
-
-    let key = args.next_arg()?;
-    let path = Path::new(args.next_str()?);
-    let value = args.next_str()?;
+    let [_, key, path, value, options @ ..] = args.as_slice() else {
+        return Err(RedisError::WrongArity);
+    };
+    let path = Path::new(path.try_as_str()?);
+    let value = value.try_as_str()?;
 
     let mut format = Format::JSON;
+    let mut create_path = false;
+    let mut expiration = None;
 
-    while let Some(s) = args.next() {
+    let mut option_args = options.iter().cloned();
+    while let Some(s) = option_args.next() {
         match s.try_as_str()? {
             arg if arg.eq_ignore_ascii_case("FORMAT") => {
-                format = Format::from_str(args.next_str()?)?;
+                format = Format::from_str(option_args.next_str()?)?;
+            }
+            arg if arg.eq_ignore_ascii_case("CREATEPATH") => create_path = true,
+            arg if is_key_expiration_option(arg) && expiration.is_none() => {
+                expiration = Some(parse_key_expiration(arg, &mut option_args, "json.merge")?);
             }
             _ => return Err(RedisError::Str("ERR syntax error")),
         };
     }
 
-    let mut redis_key = manager.open_key_write(ctx, key)?;
-    let current = redis_key.get_value()?;
-
+    let mut redis_key = manager.open_key_write(ctx, key.clone())?;
+    let key_expiration = ExpiringWrite {
+        key,
+        expiration,
+        args: &args,
+        options: 4,
+    };
     let val = manager.from_str(value, format, true, None)?;
+    let current = redis_key.get_value()?;
+    let missing = match current.as_deref() {
+        Some(doc) if create_path => missing_parents(doc, &path)?,
+        _ => None,
+    };
 
     match current {
         Some(doc) => {
             if path == JSON_ROOT_PATH {
                 redis_key.merge_value(Vec::new(), val)?;
                 redis_key.notify_keyspace_event(ctx, "json.merge")?;
-                manager.apply_changes(ctx);
+                apply_changes_with_expiration(&manager, ctx, &mut redis_key, key_expiration)?;
                 REDIS_OK
             } else {
-                let mut update_info =
-                    KeyValue::new(doc).find_paths(path.get_path(), SetOptions::MergeExisting)?;
+                let (mut update_info, val) = match missing {
+                    Some(missing) => nested_update(&manager, missing, val)?,
+                    None => (
+                        KeyValue::new(doc)
+                            .find_paths(path.get_path(), SetOptions::MergeExisting)?,
+                        val,
+                    ),
+                };
                 if !update_info.is_empty() {
                     let mut res = false;
                     if update_info.len() == 1 {
@@ -604,7 +1000,12 @@ pub fn json_merge_command_impl<M: Manager>(
                     }
                     if res {
                         redis_key.notify_keyspace_event(ctx, "json.merge")?;
-                        manager.apply_changes(ctx);
+                        apply_changes_with_expiration(
+                            &manager,
+                            ctx,
+                            &mut redis_key,
+                            key_expiration,
+                        )?;
                         REDIS_OK
                     } else {
                         Ok(RedisValue::Null)
@@ -619,7 +1020,7 @@ pub fn json_merge_command_impl<M: Manager>(
                 // Nothing to merge with it's a new doc
                 redis_key.set_value(Vec::new(), val)?;
                 redis_key.notify_keyspace_event(ctx, "json.merge")?;
-                manager.apply_changes(ctx);
+                apply_changes_with_expiration(&manager, ctx, &mut redis_key, key_expiration)?;
                 REDIS_OK
             } else {
                 Err(RedisError::Str(
@@ -630,8 +1031,50 @@ pub fn json_merge_command_impl<M: Manager>(
     }
 }
 
+/// Finds the parents missing along a static `path` for `CREATEPATH`: the path of its deepest
+/// existing ancestor, and the keys of the members missing below it, outermost first. Returns
+/// `None` when no parent is missing, for the last member to be set as usual.
+fn missing_parents<T: SelectValue>(
+    doc: &T,
+    path: &Path,
+) -> RedisResult<Option<(Vec<String>, Vec<String>)>> {
+    if *path == JSON_ROOT_PATH {
+        return Ok(None);
+    }
+    let query = compile(path.get_path())?;
+    if !query.is_static() {
+        return Err(RedisError::Str("ERR CREATEPATH requires a static path"));
+    }
+    if query.size() >= MAX_DEPTH {
+        return Err(err_recursion_limit_exceeded());
+    }
+    Ok(KeyValue::new(doc)
+        .find_add_path(path.get_path(), true)?
+        .filter(|(_, missing)| missing.len() > 1))
+}
+
+/// The update adding `val` below its missing parents, as found by [`missing_parents`]: `val` is
+/// nested in objects standing for them, and added as a single member of the deepest existing
+/// ancestor, so nothing is written unless all of it can be
+fn nested_update<M: Manager>(
+    manager: &M,
+    (path, mut missing): (Vec<String>, Vec<String>),
+    val: M::O,
+) -> RedisResult<(Vec<UpdateInfo>, M::O)> {
+    let key = missing.remove(0);
+    let val = manager.nest(&missing, val)?;
+    Ok((vec![UpdateInfo::AUI(AddUpdateInfo { path, key })], val))
+}
+
 ///
-/// JSON.MSET <key> <path> <json> [[<key> <path> <json>]...]
+/// JSON.MSET [NX | XX] [FORMAT <format>] [FPHA <type>] [<expiration>] <key> <path> <json>
+///           [[NX | XX] [FORMAT <format>] [FPHA <type>] [<expiration>] <key> <path> <json> ...]
+///
+/// where <expiration> is one of EX seconds | PX milliseconds | EXAT unix-time-seconds |
+///                              PXAT unix-time-milliseconds | KEEPTTL
+///
+/// A modifier applies to the triplets following it, up to one overriding it. The reply holds
+/// OK for each triplet written, and null for each one skipped.
 ///
 #[macro_export]
 macro_rules! json_mset_command {
@@ -639,7 +1082,7 @@ macro_rules! json_mset_command {
         #[::redis_module_macros::command(
             {
                 name: "json.mset",
-                flags: [Write, DenyOOM],
+                flags: [Write, DenyOOM, GetkeysApi],
                 acl_categories: [Write, Single("json")],
                 arity: -4,
                 complexity: "O(K*(M+N)) where k is the number of keys in the command, when path is evaluated to a single value where M is the size of the original value (if it exists) and N is the size of the new value, or O(K*(M+N)) when path is evaluated to multiple values where M is the size of the key and N is the size of the new value * the number of original values in the key",
@@ -647,7 +1090,9 @@ macro_rules! json_mset_command {
                 summary: "Set or update one or more JSON values according to the specified key-path-value triplets",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        // Modifiers shift the keys off the triplet stride, so the keys are
+                        // reported by the command itself
+                        flags: [ReadWrite, Incomplete],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: -1, steps: 3, limit: 0 }),
                     }
@@ -658,6 +1103,118 @@ macro_rules! json_mset_command {
                         arg_type: Block,
                         flags: [Multiple],
                         subargs: [
+                            {
+                                name: "condition",
+                                arg_type: OneOf,
+                                flags: [Optional],
+                                subargs: [
+                                    {
+                                        name: "nx",
+                                        arg_type: PureToken,
+                                        token: "NX",
+                                    },
+                                    {
+                                        name: "xx",
+                                        arg_type: PureToken,
+                                        token: "XX",
+                                    }
+                                ]
+                            },
+                            {
+                                name: "format",
+                                token: "FORMAT",
+                                arg_type: Block,
+                                flags: [Optional],
+                                subargs: [
+                                    {
+                                        name: "format-token",
+                                        arg_type: OneOf,
+                                        subargs: [
+                                            {
+                                                name: "STRING",
+                                                arg_type: PureToken,
+                                                token: "STRING",
+                                            },
+                                            {
+                                                name: "JSON",
+                                                arg_type: PureToken,
+                                                token: "JSON",
+                                            },
+                                            {
+                                                name: "BSON",
+                                                arg_type: PureToken,
+                                                token: "BSON",
+                                            }
+                                        ]
+                                    }
+                                ]
+                            },
+                            {
+                                name: "fpha",
+                                token: "FPHA",
+                                arg_type: Block,
+                                flags: [Optional],
+                                subargs: [
+                                    {
+                                        name: "fpha-type",
+                                        arg_type: OneOf,
+                                        subargs: [
+                                            {
+                                                name: "BF16",
+                                                arg_type: PureToken,
+                                                token: "BF16",
+                                            },
+                                            {
+                                                name: "FP16",
+                                                arg_type: PureToken,
+                                                token: "FP16",
+                                            },
+                                            {
+                                                name: "FP32",
+                                                arg_type: PureToken,
+                                                token: "FP32",
+                                            },
+                                            {
+                                                name: "FP64",
+                                                arg_type: PureToken,
+                                                token: "FP64",
+                                            }
+                                        ]
+                                    }
+                                ]
+                            },
+                            {
+                                name: "expiration",
+                                arg_type: OneOf,
+                                flags: [Optional],
+                                subargs: [
+                                    {
+                                        name: "seconds",
+                                        arg_type: Integer,
+                                        token: "EX",
+                                    },
+                                    {
+                                        name: "milliseconds",
+                                        arg_type: Integer,
+                                        token: "PX",
+                                    },
+                                    {
+                                        name: "unix-time-seconds",
+                                        arg_type: Integer,
+                                        token: "EXAT",
+                                    },
+                                    {
+                                        name: "unix-time-milliseconds",
+                                        arg_type: Integer,
+                                        token: "PXAT",
+                                    },
+                                    {
+                                        name: "keepttl",
+                                        arg_type: PureToken,
+                                        token: "KEEPTTL",
+                                    }
+                                ]
+                            },
                             {
                                 name: "key",
                                 arg_type: Key,
@@ -685,42 +1242,58 @@ pub fn json_mset_command_impl<M: Manager>(
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    let mut args = args.into_iter().skip(1);
-
-    if args.len() < 3 {
-        return Err(RedisError::WrongArity);
+    let (command, args) = args.split_first().ok_or(RedisError::WrongArity)?;
+    let keys = mset_key_indices(args);
+    if ctx.is_keys_position_request() {
+        // Modifiers move the keys off the triplet stride, which a key spec can't follow
+        for &key in keys.iter().flatten() {
+            ctx.key_at_pos(key as i32 + 1);
+        }
+        return Ok(RedisValue::NoReply);
     }
+    let keys = keys.ok_or(RedisError::WrongArity)?;
+
+    // Parse the arguments, validate the keys, the paths and the values. A modifier applies to
+    // the triplets following it, up to a modifier overriding it.
+    let mut modifiers = MSetModifiers::default();
+    let mut start = 0;
+    let mut parsed: Vec<(RedisString, Option<Vec<UpdateInfo>>, M::O, MSetModifiers)> = Vec::new();
+    for &at in &keys {
+        modifiers.parse(&args[start..at])?;
+        start = at + 3;
+        let (key, path_str, value_str) = (&args[at], &args[at + 1], &args[at + 2]);
+        let key = key.clone();
 
-    // Parse the arguments, validate the keys and the paths
-    let mut parsed: Vec<(RedisString, Option<Vec<UpdateInfo>>, String)> = Vec::new();
-    while let Ok(key) = args.next_arg() {
         let mut redis_key = manager.open_key_write(ctx, key.clone())?;
         let key_value = redis_key.get_value()?;
 
-        // Validate the path
-        let path_str = args.next_str()?.to_string();
-        let path = Path::new(&path_str);
-        let update_info = if path == JSON_ROOT_PATH {
-            None
-        } else if let Some(existing) = key_value {
-            Some(KeyValue::new(existing).find_paths(path.get_path(), SetOptions::None)?)
-        } else {
-            return Err(RedisError::Str(
-                "ERR new objects must be created at the root",
-            ));
-        };
+        // Validate the path. A triplet whose condition doesn't hold gets no updates, and is skipped
+        let path = Path::new(path_str.try_as_str()?);
+        let update_info = match (key_value, modifiers.set_option) {
+            (None, SetOptions::AlreadyExists) => Some(vec![]),
+            (Some(_), SetOptions::NotExists) if path == JSON_ROOT_PATH => Some(vec![]),
+            _ if path == JSON_ROOT_PATH => None,
+            (Some(existing), op) => Some(KeyValue::new(existing).find_paths(path.get_path(), op)?),
+            (None, _) => {
+                return Err(RedisError::Str(
+                    "ERR new objects must be created at the root",
+                ))
+            }
+        };
 
-        let value_str = args.next_str()?.to_string();
-        // Validate the value(We deliberately do not store the created value, and recreate it again later)
-        let _ = manager.from_str(&value_str, Format::JSON, true, None)?;
-        parsed.push((key, update_info, value_str));
+        let value = manager.from_str(
+            value_str.try_as_str()?,
+            modifiers.format,
+            true,
+            modifiers.fpha_type,
+        )?;
+        parsed.push((key, update_info, value, modifiers));
     }
 
-    let mut all_updated = true;
-    for (key, update_info, value_str) in parsed {
-        let mut redis_key = manager.open_key_write(ctx, key)?;
-
-        let value = manager.from_str(&value_str, Format::JSON, true, None)?;
+    let mut replies = Vec::with_capacity(parsed.len());
+    let mut expirations = Vec::new();
+    for (key, update_info, value, modifiers) in parsed {
+        let mut redis_key = manager.open_key_write(ctx, key.clone())?;
 
         let (any_updated, key_all_updated) = if let Some(update_info) = update_info {
             if update_info.is_empty() {
@@ -737,16 +1310,132 @@ pub fn json_mset_command_impl<M: Manager>(
 
         if any_updated {
             redis_key.notify_keyspace_event(ctx, "json.mset")?;
+            if let Some(KeyExpiration::At(at)) = modifiers.expiration {
+                set_key_expiration::<M>(ctx, &mut redis_key, &key, at)?;
+                expirations.push((key, at));
+            }
         }
-        all_updated &= key_all_updated;
+        replies.push(key_all_updated);
     }
 
-    manager.apply_changes(ctx);
-    if all_updated {
-        REDIS_OK
+    if expirations.is_empty() {
+        manager.apply_changes(ctx);
     } else {
-        Ok(RedisValue::Null)
+        let mut replicated = Vec::with_capacity(args.len());
+        let mut start = 0;
+        for &at in &keys {
+            strip_key_expiration(&args[start..at], &mut replicated);
+            replicated.extend(args[at..at + 3].iter().map(RedisString::as_slice));
+            start = at + 3;
+        }
+        manager.apply_changes_as(ctx, command.try_as_str()?, &replicated);
+        for (key, at) in &expirations {
+            replicate_key_expiration(ctx, key, *at);
+        }
+    }
+    // Report each triplet, so a caller can tell which writes were skipped
+    Ok(replies
+        .into_iter()
+        .map(|updated| {
+            if updated {
+                RedisValue::SimpleStringStatic("OK")
+            } else {
+                RedisValue::Null
+            }
+        })
+        .collect_vec()
+        .into())
+}
+
+/// The `NX | XX`, `FORMAT`, `FPHA` and key expiration modifiers of a JSON.MSET triplet
+#[derive(Clone, Copy)]
+struct MSetModifiers {
+    set_option: SetOptions,
+    format: Format,
+    fpha_type: Option<FloatType>,
+    expiration: Option<KeyExpiration>,
+}
+
+impl Default for MSetModifiers {
+    fn default() -> Self {
+        Self {
+            set_option: SetOptions::None,
+            format: Format::JSON,
+            fpha_type: None,
+            expiration: None,
+        }
+    }
+}
+
+impl MSetModifiers {
+    /// Parses the modifiers `args`, as split by [`mset_key_indices`], overriding the current
+    /// ones
+    fn parse(&mut self, args: &[RedisString]) -> RedisResult<()> {
+        let mut args = args.iter().cloned();
+        let mut condition = None;
+        let mut expiration = None;
+        while let Some(arg) = args.next() {
+            match arg.try_as_str()? {
+                arg if arg.eq_ignore_ascii_case("NX") && condition.is_none() => {
+                    condition = Some(SetOptions::NotExists);
+                }
+                arg if arg.eq_ignore_ascii_case("XX") && condition.is_none() => {
+                    condition = Some(SetOptions::AlreadyExists);
+                }
+                arg if arg.eq_ignore_ascii_case("FORMAT") => {
+                    self.format = Format::from_str(args.next_str()?)?;
+                }
+                arg if arg.eq_ignore_ascii_case("FPHA") => {
+                    self.fpha_type = Some(parse_fpha_type(args.next_str()?)?);
+                }
+                arg if is_key_expiration_option(arg) && expiration.is_none() => {
+                    expiration = Some(parse_key_expiration(arg, &mut args, "json.mset")?);
+                }
+                _ => return Err(RedisError::Str("ERR syntax error")),
+            }
+        }
+        if let Some(condition) = condition {
+            self.set_option = condition;
+        }
+        if expiration.is_some() {
+            self.expiration = expiration;
+        }
+        Ok(())
+    }
+}
+
+/// The number of arguments of the JSON.MSET modifier starting at `arg`, if it is one
+fn mset_modifier_len(arg: &RedisString) -> Option<usize> {
+    let arg = arg.try_as_str().ok()?;
+    let is_any = |modifiers: &[&str]| modifiers.iter().any(|m| arg.eq_ignore_ascii_case(m));
+    if is_any(&["NX", "XX", "KEEPTTL"]) {
+        Some(1)
+    } else if is_any(&["FORMAT", "FPHA", "EX", "PX", "EXAT", "PXAT"]) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Splits the arguments of JSON.MSET into modifiers and triplets, returning the index of each
+/// triplet's key. The modifiers of a triplet come before its key, so an argument named like a
+/// modifier is always read as one where a triplet starts, whatever follows it.
+fn mset_key_indices(args: &[RedisString]) -> Option<Vec<usize>> {
+    let mut keys = vec![];
+    let mut i = 0;
+    // The end of the last triplet, as modifiers can't end the arguments
+    let mut end = 0;
+    while i < args.len() {
+        match mset_modifier_len(&args[i]) {
+            Some(len) => i += len,
+            None => {
+                keys.push(i);
+                i += 3;
+                end = i;
+            }
+        }
     }
+    (!keys.is_empty() && end == args.len()).then_some(keys)
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -1086,8 +1775,23 @@ pub fn json_del_command_impl<M: Manager>(
     };
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
+    let deleted = delete_path_matches::<M>(&mut redis_key, &path)?;
+    if deleted > 0 {
+        redis_key.notify_keyspace_event(ctx, "json.del")?;
+        manager.apply_changes(ctx);
+    }
+
+    Ok(deleted.into())
+}
+
+/// Deletes the values matching `path`, and the key itself when it is left empty or `path` is
+/// the root. Returns the number of deleted values
+fn delete_path_matches<M: Manager>(
+    redis_key: &mut M::WriteHolder,
+    path: &Path,
+) -> RedisResult<i64> {
     let deleted = if let Some(doc) = redis_key.get_value()? {
-        if path != JSON_ROOT_PATH {
+        if *path != JSON_ROOT_PATH {
             let mut paths = find_paths(path.get_path(), doc, |_| true)?;
             prepare_paths_for_updating(&mut paths);
             paths
@@ -1105,14 +1809,263 @@ pub fn json_del_command_impl<M: Manager>(
             .get_value()?
             .and_then(|v| v.is_empty())
             .unwrap_or(false);
-        if is_empty || path == JSON_ROOT_PATH {
+        if is_empty || *path == JSON_ROOT_PATH {
             redis_key.delete()?;
         }
+    }
+    Ok(deleted)
+}
+
+/// Parses the optional `FORMAT` of JSON.GETDEL and JSON.GETSET, which shapes their reply like
+/// the one of JSON.GET
+fn parse_reply_format(
+    ctx: &Context,
+    mut args: impl Iterator<Item = RedisString>,
+) -> RedisResult<ReplyFormatOptions<'static>> {
+    let mut format_options = ReplyFormatOptions::new(is_resp3(ctx), ReplyFormat::STRING);
+    while let Some(arg) = args.next() {
+        if !arg.try_as_str()?.eq_ignore_ascii_case(CMD_ARG_FORMAT) {
+            return Err(RedisError::Str("ERR syntax error"));
+        }
+        if !format_options.resp3 {
+            return Err(RedisError::Str(
+                "ERR FORMAT argument is not supported on RESP2",
+            ));
+        }
+        let next = args.next_str()?;
+        if next.eq_ignore_ascii_case("STRINGS") {
+            return Err(RedisError::Str("ERR wrong reply format"));
+        }
+        format_options.format = ReplyFormat::from_str(next)?;
+    }
+    Ok(format_options)
+}
+
+///
+/// JSON.GETDEL <key> [path] [FORMAT {STRING|EXPAND1|EXPAND}]
+///
+#[macro_export]
+macro_rules! json_getdel_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.getdel",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: -2,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the deleted value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Get the JSON values at path in key and delete them",
+                key_spec: [
+                    {
+                        flags: [ReadWrite, Access, Delete],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "format",
+                        token: "FORMAT",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "format-token",
+                                arg_type: OneOf,
+                                subargs: [
+                                    {
+                                        name: "STRING",
+                                        arg_type: PureToken,
+                                        token: "STRING",
+                                    },
+                                    {
+                                        name: "EXPAND1",
+                                        arg_type: PureToken,
+                                        token: "EXPAND1",
+                                    },
+                                    {
+                                        name: "EXPAND",
+                                        arg_type: PureToken,
+                                        token: "EXPAND",
+                                    }
+                                ]
+                            }
+                        ]
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_getdel_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1).peekable();
+
+    let key = args.next_arg()?;
+    let path = match args.next_if(|arg| {
+        !arg.try_as_str()
+            .is_ok_and(|arg| arg.eq_ignore_ascii_case(CMD_ARG_FORMAT))
+    }) {
+        None => Path::default(),
+        Some(s) => Path::new(s.try_as_str()?),
+    };
+    let format_options = parse_reply_format(ctx, args)?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let Some(doc) = redis_key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    // The reply is built before anything is deleted
+    let value = KeyValue::new(doc).to_json(vec![path.clone()], &format_options)?;
+
+    if delete_path_matches::<M>(&mut redis_key, &path)? > 0 {
         redis_key.notify_keyspace_event(ctx, "json.del")?;
         manager.apply_changes(ctx);
     }
+    Ok(value)
+}
 
-    Ok(deleted.into())
+///
+/// JSON.GETSET <key> <path> <json> [FORMAT {STRING|EXPAND1|EXPAND}]
+///
+#[macro_export]
+macro_rules! json_getset_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.getset",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -4,
+                complexity: "O(M+N) where M is the size of the original value (if it exists) and N is the size of the new value",
+                since: "8.8.0",
+                summary: "Set the JSON value at path in key and return the values it replaces",
+                key_spec: [
+                    {
+                        flags: [ReadWrite, Access, Update],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "json",
+                        arg_type: String,
+                    },
+                    {
+                        name: "format",
+                        token: "FORMAT",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "format-token",
+                                arg_type: OneOf,
+                                subargs: [
+                                    {
+                                        name: "STRING",
+                                        arg_type: PureToken,
+                                        token: "STRING",
+                                    },
+                                    {
+                                        name: "EXPAND1",
+                                        arg_type: PureToken,
+                                        token: "EXPAND1",
+                                    },
+                                    {
+                                        name: "EXPAND",
+                                        arg_type: PureToken,
+                                        token: "EXPAND",
+                                    }
+                                ]
+                            }
+                        ]
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_getset_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let value = args.next_str()?;
+    let format_options = parse_reply_format(ctx, args)?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let val = manager.from_str(value, Format::JSON, true, None)?;
+
+    let Some(doc) = redis_key.get_value()? else {
+        // Like JSON.SET, a new key can only be created at the root, and there is nothing to return
+        if path != JSON_ROOT_PATH {
+            return Err(RedisError::Str(
+                "ERR new objects must be created at the root",
+            ));
+        }
+        redis_key.set_value(Vec::new(), val)?;
+        redis_key.notify_keyspace_event(ctx, "json.set")?;
+        manager.apply_changes(ctx);
+        return Ok(RedisValue::Null);
+    };
+
+    let mut key_value = KeyValue::new(doc);
+    let update_info = if path == JSON_ROOT_PATH {
+        vec![UpdateInfo::SUI(SetUpdateInfo { path: Vec::new() })]
+    } else {
+        key_value.find_paths(path.get_path(), SetOptions::None)?
+    };
+    if update_info.is_empty() {
+        return Ok(RedisValue::Null);
+    }
+    // A legacy path that doesn't exist yet has no previous value, and would fail JSON.GET
+    let previous = if path.is_legacy()
+        && !update_info
+            .iter()
+            .any(|ui| matches!(ui, UpdateInfo::SUI(_)))
+    {
+        RedisValue::Null
+    } else {
+        key_value.to_json(vec![path], &format_options)?
+    };
+
+    if apply_updates::<M>(&mut redis_key, val, update_info).any_updated() {
+        redis_key.notify_keyspace_event(ctx, "json.set")?;
+        manager.apply_changes(ctx);
+    }
+    Ok(previous)
 }
 
 ///
@@ -1265,201 +2218,24 @@ pub fn json_type_command_impl<M: Manager>(
     }
 }
 
-pub fn json_type_impl<M: Manager>(redis_key: &M::ReadHolder, path: &str) -> RedisResult {
-    let root = redis_key.get_value()?;
-    let value = match root {
-        Some(root) => KeyValue::new(root)
-            .get_values(path)?
-            .iter()
-            .map(|v| RedisValue::from(KeyValue::value_name(v.as_ref())))
-            .collect_vec()
-            .into(),
-        None => RedisValue::Null,
-    };
-    Ok(value)
-}
-
-fn json_type_legacy<M: Manager>(redis_key: &M::ReadHolder, path: &str) -> RedisResult {
-    // A legacy path that normalizes to a projection (e.g. `a + 1` -> `$.a + 1`) addresses no
-    // node; reject it explicitly instead of letting the lenient `map_or(Null)` swallow the
-    // projection error into a silent nil.
-    if compile(path).is_ok_and(|q| q.is_projection()) {
-        return Err(err_projection_readonly());
-    }
-    let value = redis_key.get_value()?.map_or(RedisValue::Null, |doc| {
-        KeyValue::new(doc)
-            .get_type(path)
-            .map_or(RedisValue::Null, |s| s.into())
-    });
-    Ok(value)
-}
-
-enum NumOp {
-    Incr,
-    Mult,
-    Pow,
-}
-
-fn json_num_op<M: Manager>(
-    manager: M,
-    ctx: &Context,
-    args: Vec<RedisString>,
-    cmd: &str,
-    op: NumOp,
-) -> RedisResult {
-    let mut args = args.into_iter().skip(1);
-
-    let key = args.next_arg()?;
-    let path = Path::new(args.next_str()?);
-    let number = args.next_str()?;
-
-    let mut redis_key = manager.open_key_write(ctx, key)?;
-
-    // check context flags to see if RESP3 is enabled
-    if is_resp3(ctx) {
-        let res = json_num_op_impl(
-            manager,
-            &mut redis_key,
-            ctx,
-            path.get_path(),
-            number,
-            op,
-            cmd,
-        )?
-        .into_iter()
-        .map(|v| {
-            v.map_or(RedisValue::Null, |v| {
-                if let Some(i) = v.as_i64() {
-                    RedisValue::Integer(i)
-                } else {
-                    RedisValue::Float(v.as_f64().unwrap_or_default())
-                }
-            })
-        })
-        .collect_vec()
-        .into();
-        Ok(res)
-    } else if path.is_legacy() {
-        json_num_op_legacy(
-            manager,
-            &mut redis_key,
-            ctx,
-            path.get_path(),
-            number,
-            op,
-            cmd,
-        )
-    } else {
-        let results = json_num_op_impl(
-            manager,
-            &mut redis_key,
-            ctx,
-            path.get_path(),
-            number,
-            op,
-            cmd,
-        )?;
-
-        // Convert to RESP2 format return as one JSON array
-        let values = to_json_value::<Number>(results, Value::Null);
-        Ok(KeyValue::<M::V>::serialize_object(&values, &ReplyFormatOptions::default())?.into())
-    }
-}
-
-fn json_num_op_impl<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    number: &str,
-    op: NumOp,
-    cmd: &str,
-) -> RedisResult<Vec<Option<Number>>> {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_all_paths(path, root, |v| {
-        matches!(
-            v.get_type(),
-            SelectValueType::Double | SelectValueType::Long
-        )
-    })?;
-
-    let mut need_notify = false;
-    let res = paths
-        .into_iter()
-        .map(|p| {
-            p.map(|p| {
-                need_notify = true;
-                match op {
-                    NumOp::Incr => redis_key.incr_by(p, number),
-                    NumOp::Mult => redis_key.mult_by(p, number),
-                    NumOp::Pow => redis_key.pow_by(p, number),
-                }
-            })
-            .transpose()
-        })
-        .try_collect()?;
-    if need_notify {
-        redis_key.notify_keyspace_event(ctx, cmd)?;
-        manager.apply_changes(ctx);
-    }
-    Ok(res)
-}
-
-fn json_num_op_legacy<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    number: &str,
-    op: NumOp,
-    cmd: &str,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_paths(path, root, |v| {
-        v.get_type() == SelectValueType::Double || v.get_type() == SelectValueType::Long
-    })?;
-    if !paths.is_empty() {
-        let res = paths
-            .into_iter()
-            .try_fold(None::<Number>, |_, p| -> RedisResult<Option<Number>> {
-                Ok(Some(match op {
-                    NumOp::Incr => redis_key.incr_by(p, number)?,
-                    NumOp::Mult => redis_key.mult_by(p, number)?,
-                    NumOp::Pow => redis_key.pow_by(p, number)?,
-                }))
-            })?
-            // SAFETY: res is modified to Some if there is at least one path
-            .unwrap();
-        redis_key.notify_keyspace_event(ctx, cmd)?;
-        manager.apply_changes(ctx);
-        Ok(res.to_string().into())
-    } else {
-        Err(err_invalid_path_or("does not contains a number"))
-    }
-}
-
 ///
-/// JSON.NUMINCRBY <key> <path> <number>
+/// JSON.EXISTS <key> <path> [path ...]
 ///
 #[macro_export]
-macro_rules! json_numincrby_command {
+macro_rules! json_exists_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.numincrby",
-                flags: [Write],
-                acl_categories: [Write, Single("json")],
-                arity: 4,
+                name: "json.exists",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -3,
                 complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Increment the number value stored at path by number",
+                since: "8.8.0",
+                summary: "Report whether paths exist in the JSON value at key",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        flags: [ReadOnly],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -1473,11 +2249,8 @@ macro_rules! json_numincrby_command {
                     {
                         name: "path",
                         arg_type: String,
-                    },
-                    {
-                        name: "number",
-                        arg_type: Double,
-                    },
+                        flags: [Multiple],
+                    }
                 ]
             }
         )]
@@ -1485,32 +2258,58 @@ macro_rules! json_numincrby_command {
     };
 }
 
-pub fn json_num_incrby_command_impl<M: Manager>(
+pub fn json_exists_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    json_num_op(manager, ctx, args, "json.numincrby", NumOp::Incr)
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let paths: Vec<_> = args
+        .map(|arg| arg.try_as_str().map(Path::new))
+        .try_collect()?;
+    if paths.is_empty() {
+        return Err(RedisError::WrongArity);
+    }
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let Some(doc) = key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+
+    // Only the matches are counted, no reply is built for their values
+    paths
+        .iter()
+        .map(|path| {
+            let matches = count_matches(path, doc)?;
+            Ok(if path.is_legacy() {
+                RedisValue::Bool(matches > 0)
+            } else {
+                RedisValue::Integer(matches as i64)
+            })
+        })
+        .try_collect::<_, Vec<_>, _>()
+        .map(RedisValue::Array)
 }
 
 ///
-/// JSON.NUMMULTBY <key> <path> <number>
+/// JSON.COUNT <key> <path>
 ///
 #[macro_export]
-macro_rules! json_nummultby_command {
+macro_rules! json_count_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.nummultby",
-                flags: [Write],
-                acl_categories: [Write, Single("json")],
-                arity: 4,
+                name: "json.count",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: 3,
                 complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Multiply the number value stored at path by number",
+                since: "8.8.0",
+                summary: "Count the JSON values matching path in key",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        flags: [ReadOnly],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -1524,11 +2323,7 @@ macro_rules! json_nummultby_command {
                     {
                         name: "path",
                         arg_type: String,
-                    },
-                    {
-                        name: "number",
-                        arg_type: Double,
-                    },
+                    }
                 ]
             }
         )]
@@ -1536,32 +2331,49 @@ macro_rules! json_nummultby_command {
     };
 }
 
-pub fn json_num_multby_command_impl<M: Manager>(
+pub fn json_count_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    json_num_op(manager, ctx, args, "json.nummultby", NumOp::Mult)
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+    key.get_value()?.map_or(Ok(RedisValue::Null), |doc| {
+        Ok(RedisValue::Integer(count_matches(&path, doc)? as i64))
+    })
+}
+
+/// Counts the values matching `path` without collecting them
+fn count_matches<T: SelectValue>(path: &Path, doc: &T) -> RedisResult<usize> {
+    let query = compile(path.get_path())?;
+    if query.is_projection() {
+        return Err(err_projection_readonly());
+    }
+    Ok(calc_once_count(query, doc))
 }
 
 ///
-/// JSON.NUMPOWBY <key> <path> <number>
+/// JSON.PATHS <key> [path] [LEAVES | ALL] [DEPTH depth] [LIMIT limit] [WITHTYPES]
 ///
 #[macro_export]
-macro_rules! json_numpowby_command {
+macro_rules! json_paths_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.numpowby",
-                flags: [Write],
-                acl_categories: [Write, Single("json")],
-                arity: 4,
-                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Raise the number value stored at path to the power of number",
+                name: "json.paths",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -2,
+                complexity: "O(N) where N is the number of values below the values matching path",
+                since: "8.8.0",
+                summary: "List the normalized paths of the values at and below path in key",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        flags: [ReadOnly],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -1575,11 +2387,43 @@ macro_rules! json_numpowby_command {
                     {
                         name: "path",
                         arg_type: String,
+                        flags: [Optional],
                     },
                     {
-                        name: "number",
-                        arg_type: Double,
+                        name: "which",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "leaves",
+                                arg_type: PureToken,
+                                token: "LEAVES",
+                            },
+                            {
+                                name: "all",
+                                arg_type: PureToken,
+                                token: "ALL",
+                            }
+                        ]
+                    },
+                    {
+                        name: "depth",
+                        arg_type: Integer,
+                        token: "DEPTH",
+                        flags: [Optional],
+                    },
+                    {
+                        name: "limit",
+                        arg_type: Integer,
+                        token: "LIMIT",
+                        flags: [Optional],
                     },
+                    {
+                        name: "withtypes",
+                        arg_type: PureToken,
+                        token: "WITHTYPES",
+                        flags: [Optional],
+                    }
                 ]
             }
         )]
@@ -1587,32 +2431,147 @@ macro_rules! json_numpowby_command {
     };
 }
 
-pub fn json_num_powby_command_impl<M: Manager>(
+/// The options of JSON.PATHS
+#[derive(Default)]
+struct PathsOptions {
+    /// List every value, not only the leaves
+    all: bool,
+    /// How far below the matched values to go, the values at this depth counting as leaves
+    depth: Option<usize>,
+    limit: Option<usize>,
+    with_types: bool,
+}
+
+impl PathsOptions {
+    fn is_full(&self, paths: &[RedisValue]) -> bool {
+        self.limit.is_some_and(|limit| paths.len() >= limit)
+    }
+}
+
+pub fn json_paths_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    json_num_op(manager, ctx, args, "json.numpowby", NumOp::Pow)
-}
-
-//
-/// JSON.TOGGLE <key> <path>
-///
+    let mut args = args.into_iter().skip(1).peekable();
+    let key = args.next_arg()?;
+    let path = args
+        .next_if(|arg| {
+            !arg.try_as_str().is_ok_and(|arg| {
+                ["LEAVES", "ALL", "DEPTH", "LIMIT", "WITHTYPES"]
+                    .iter()
+                    .any(|option| arg.eq_ignore_ascii_case(option))
+            })
+        })
+        .map_or(Ok(JSON_ROOT_PATH), |arg| arg.try_as_str().map(Path::new))?;
+
+    let mut options = PathsOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("LEAVES") => options.all = false,
+            arg if arg.eq_ignore_ascii_case("ALL") => options.all = true,
+            arg if arg.eq_ignore_ascii_case("DEPTH") => {
+                options.depth = Some(
+                    usize::try_from(args.next_i64()?)
+                        .map_err(|_| RedisError::Str("ERR DEPTH can't be negative"))?,
+                );
+            }
+            arg if arg.eq_ignore_ascii_case("LIMIT") => {
+                options.limit = Some(
+                    usize::try_from(args.next_i64()?)
+                        .map_err(|_| RedisError::Str("ERR LIMIT can't be negative"))?,
+                );
+            }
+            arg if arg.eq_ignore_ascii_case("WITHTYPES") => options.with_types = true,
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let Some(doc) = key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    let query = compile(path.get_path())?;
+    if query.is_projection() {
+        return Err(err_projection_readonly());
+    }
+
+    let mut paths = Vec::new();
+    for matched in calc_once_with_paths(query, doc) {
+        if options.is_full(&paths) {
+            break;
+        }
+        // SAFETY: `calc_once_with_paths` is guaranteed to return a path tracker
+        let mut tracker = matched.path_tracker.unwrap();
+        list_paths(matched.res.as_ref(), &mut tracker, 0, &options, &mut paths);
+    }
+    Ok(paths.into())
+}
+
+/// Lists, depth first, the normalized paths of `value` and of the values below it, `tracker`
+/// holding the path of `value`
+fn list_paths<T: SelectValue>(
+    value: &T,
+    tracker: &mut PTracker,
+    depth: usize,
+    options: &PathsOptions,
+    paths: &mut Vec<RedisValue>,
+) {
+    let expand = options.depth.is_none_or(|max| depth < max) && value.is_empty() == Some(false);
+    if options.all || !expand {
+        let path = RedisValue::BulkString(tracker.to_normalized_path());
+        paths.push(if options.with_types {
+            vec![path, KeyValue::value_name(value).into()].into()
+        } else {
+            path
+        });
+    }
+    if !expand {
+        return;
+    }
+    match value.get_type() {
+        SelectValueType::Object => {
+            for (key, child) in value.items().into_iter().flatten() {
+                if options.is_full(paths) {
+                    break;
+                }
+                tracker.add_str(key);
+                list_paths(child.as_ref(), tracker, depth + 1, options, paths);
+                tracker.elements.pop();
+            }
+        }
+        SelectValueType::Array => {
+            for (index, child) in value.values().into_iter().flatten().enumerate() {
+                if options.is_full(paths) {
+                    break;
+                }
+                tracker.add_index(index);
+                list_paths(child.as_ref(), tracker, depth + 1, options, paths);
+                tracker.elements.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+///
+/// JSON.FLATTEN <key> <path> [SEPARATOR separator]
+///
 #[macro_export]
-macro_rules! json_toggle_command {
+macro_rules! json_flatten_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.toggle",
-                flags: [Write],
-                acl_categories: [Write, Single("json")],
-                arity: 3,
-                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "2.0.0",
-                summary: "Toggle the boolean value stored at path",
+                name: "json.flatten",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -3,
+                complexity: "O(N) where N is the size of the values matching path",
+                since: "8.8.0",
+                summary: "Return a map of the flat keys of the leaves of the values at path in key to their values",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        flags: [ReadOnly, Access],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -1627,6 +2586,12 @@ macro_rules! json_toggle_command {
                         name: "path",
                         arg_type: String,
                     },
+                    {
+                        name: "separator",
+                        arg_type: String,
+                        token: "SEPARATOR",
+                        flags: [Optional],
+                    }
                 ]
             }
         )]
@@ -1634,7 +2599,23 @@ macro_rules! json_toggle_command {
     };
 }
 
-pub fn json_bool_toggle_command_impl<M: Manager>(
+/// Parses the optional `SEPARATOR separator` trailing the arguments of JSON.FLATTEN and
+/// JSON.UNFLATTEN
+fn parse_separator(mut args: impl Iterator<Item = RedisString>) -> RedisResult<String> {
+    let mut separator = flat::DEFAULT_SEPARATOR.to_string();
+    while let Some(arg) = args.next() {
+        match arg.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("SEPARATOR") => {
+                separator = args.next_string()?;
+                flat::check_separator(&separator)?;
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+    Ok(separator)
+}
+
+pub fn json_flatten_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
@@ -1642,84 +2623,61 @@ pub fn json_bool_toggle_command_impl<M: Manager>(
     let mut args = args.into_iter().skip(1);
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let mut redis_key = manager.open_key_write(ctx, key)?;
-
-    if path.is_legacy() {
-        json_bool_toggle_legacy(manager, &mut redis_key, ctx, path.get_path())
-    } else {
-        json_bool_toggle_impl(manager, &mut redis_key, ctx, path.get_path())
-    }
-}
+    let separator = parse_separator(args)?;
 
-fn json_bool_toggle_impl<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Bool)?;
-    let mut res = vec![];
-    let mut need_notify = false;
-    for p in paths {
-        res.push(match p {
-            Some(p) => {
-                need_notify = true;
-                RedisValue::Integer((redis_key.bool_toggle(p)?).into())
-            }
-            None => RedisValue::Null,
-        });
-    }
-    if need_notify {
-        redis_key.notify_keyspace_event(ctx, "json.toggle")?;
-        manager.apply_changes(ctx);
-    }
-    Ok(res.into())
-}
+    let key = manager.open_key_read(ctx, &key)?;
+    let Some(doc) = key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    let format = ReplyFormatOptions::new(is_resp3(ctx), ReplyFormat::EXPAND);
+    let is_container = |value: &M::V| {
+        matches!(
+            value.get_type(),
+            SelectValueType::Object | SelectValueType::Array
+        )
+    };
 
-fn json_bool_toggle_legacy<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_paths(path, root, |v| v.get_type() == SelectValueType::Bool)?;
-    if !paths.is_empty() {
-        let mut res = false;
-        for p in paths {
-            res = redis_key.bool_toggle(p)?;
+    let key_value = KeyValue::new(doc);
+    if path.is_legacy() {
+        let value = key_value.get_first(path.get_path())?;
+        if !is_container(value.as_ref()) {
+            return Err(err_json("object or array"));
         }
-        redis_key.notify_keyspace_event(ctx, "json.toggle")?;
-        manager.apply_changes(ctx);
-        Ok(res.to_string().into())
+        Ok(flat::flatten(value.as_ref(), &separator, &format))
     } else {
-        Err(err_invalid_path_or("not a bool"))
+        Ok(key_value
+            .get_values(path.get_path())?
+            .iter()
+            .map(|value| {
+                if is_container(value.as_ref()) {
+                    flat::flatten(value.as_ref(), &separator, &format)
+                } else {
+                    RedisValue::Null
+                }
+            })
+            .collect_vec()
+            .into())
     }
 }
 
 ///
-/// JSON.STRAPPEND <key> [path] <json-string>
+/// JSON.UNFLATTEN <key> <path> <flat-json> [SEPARATOR separator]
 ///
 #[macro_export]
-macro_rules! json_strappend_command {
+macro_rules! json_unflatten_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.strappend",
+                name: "json.unflatten",
                 flags: [Write, DenyOOM],
                 acl_categories: [Write, Single("json")],
-                arity: -3,
-                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Append the json-string values to the string at path",
+                arity: -4,
+                complexity: "O(M+N) where M is the size of the original value (if it exists) and N is the size of the new value",
+                since: "8.8.0",
+                summary: "Set the JSON value at path in key to the nested value described by a map of flat keys",
                 key_spec: [
                     {
-                        flags: [ReadWrite],
+                        flags: [ReadWrite, Update],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -1733,11 +2691,16 @@ macro_rules! json_strappend_command {
                     {
                         name: "path",
                         arg_type: String,
-                        flags: [Optional],
                     },
                     {
-                        name: "json-string",
+                        name: "flat-json",
+                        arg_type: String,
+                    },
+                    {
+                        name: "separator",
                         arg_type: String,
+                        token: "SEPARATOR",
+                        flags: [Optional],
                     }
                 ]
             }
@@ -1746,118 +2709,2348 @@ macro_rules! json_strappend_command {
     };
 }
 
-pub fn json_str_append_command_impl<M: Manager>(
+pub fn json_unflatten_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
     let mut args = args.into_iter().skip(1);
-
     let key = args.next_arg()?;
-    let path_or_json = args.next_str()?;
-
-    let path;
-    let json;
+    let path = Path::new(args.next_str()?);
+    let flat_json = args.next_str()?;
+    let separator = parse_separator(args)?;
 
-    // path is optional
-    if let Ok(val) = args.next_arg() {
-        path = Path::new(path_or_json);
-        json = val.try_as_str()?;
-    } else {
-        path = Path::default();
-        json = path_or_json;
-    }
+    let Ok(Value::Object(flat)) = serde_json::from_str(flat_json) else {
+        return Err(RedisError::Str("ERR flat-json must be a JSON object"));
+    };
+    let value = flat::unflatten(flat, &separator)?;
+    let val = manager.from_str(&serde_json::to_string(&value)?, Format::JSON, true, None)?;
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
+    let Some(doc) = redis_key.get_value()? else {
+        // Like JSON.SET, a new key can only be created at the root
+        if path != JSON_ROOT_PATH {
+            return Err(RedisError::Str(
+                "ERR new objects must be created at the root",
+            ));
+        }
+        redis_key.set_value(Vec::new(), val)?;
+        redis_key.notify_keyspace_event(ctx, "json.unflatten")?;
+        manager.apply_changes(ctx);
+        return REDIS_OK;
+    };
 
-    if path.is_legacy() {
-        json_str_append_legacy(manager, &mut redis_key, ctx, path.get_path(), json)
+    let update_info = if path == JSON_ROOT_PATH {
+        vec![UpdateInfo::SUI(SetUpdateInfo { path: Vec::new() })]
     } else {
-        json_str_append_impl(manager, &mut redis_key, ctx, path.get_path(), json)
-    }
-}
-
-fn json_str_append_impl<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    json: &str,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-
-    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::String)?;
-
-    let mut res = vec![];
-    let mut need_notify = false;
-    for p in paths {
-        res.push(match p {
-            Some(p) => {
-                need_notify = true;
-                (redis_key.str_append(p, json.to_string())?).into()
-            }
-            _ => RedisValue::Null,
-        });
+        KeyValue::new(doc).find_paths(path.get_path(), SetOptions::None)?
+    };
+    if update_info.is_empty() {
+        return Ok(RedisValue::Null);
     }
-    if need_notify {
-        redis_key.notify_keyspace_event(ctx, "json.strappend")?;
+    let result = apply_updates::<M>(&mut redis_key, val, update_info);
+    if result.any_updated() {
+        redis_key.notify_keyspace_event(ctx, "json.unflatten")?;
         manager.apply_changes(ctx);
     }
-    Ok(res.into())
-}
-
-fn json_str_append_legacy<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    json: &str,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-
-    let paths = find_paths(path, root, |v| v.get_type() == SelectValueType::String)?;
-    if !paths.is_empty() {
-        let mut res = None;
-        for p in paths {
-            res = Some(redis_key.str_append(p, json.to_string())?);
-        }
-        redis_key.notify_keyspace_event(ctx, "json.strappend")?;
-        manager.apply_changes(ctx);
-        // SAFETY: res is modified to Some if there is at least one path
-        Ok(res.unwrap().into())
+    if result.all_updated() {
+        REDIS_OK
     } else {
-        Err(err_invalid_path_or("not a string"))
+        Ok(RedisValue::Null)
     }
 }
 
 ///
-/// JSON.STRLEN <key> [path]
+/// JSON.FROMHASH <dstkey> <hashkey> [TYPES AUTO|STRING]
 ///
 #[macro_export]
-macro_rules! json_strlen_command {
+macro_rules! json_fromhash_command {
     ($item:item) => {
         #[::redis_module_macros::command(
-            {
-                name: "json.strlen",
-                flags: [ReadOnly],
-                acl_categories: [Read, Single("json")],
-                arity: -2,
-                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Report the length of the JSON String at path in key",
-                key_spec: [
                     {
-                        flags: [ReadOnly],
-                        begin_search: Index({ index: 1 }),
-                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                        name: "json.fromhash",
+                        flags: [Write, DenyOOM],
+                        acl_categories: [Write, Hash, Single("json")],
+                        arity: -3,
+                        complexity: "O(N) where N is the size of the hash",
+                        since: "8.8.0",
+                        summary: "Set a JSON key to an object built from the fields of a hash",
+                        key_spec: [
+                            {
+                                flags: [Overwrite, Update],
+                                begin_search: Index({ index: 1 }),
+                                find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                            },
+                            {
+                                flags: [ReadOnly, Access],
+                                begin_search: Index({ index: 2 }),
+                                find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                            }
+                        ],
+                        args: [
+                            {
+                                name: "dstkey",
+                                arg_type: Key,
+                                key_spec_index: 0,
+                            },
+                            {
+                                name: "hashkey",
+                                arg_type: Key,
+                                key_spec_index: 1,
+                            },
+                            {
+                                name: "types",
+                                token: "TYPES",
+                                arg_type: OneOf,
+                                flags: [Optional],
+                                subargs: [
+                                    {
+                                        name: "auto",
+                                        arg_type: PureToken,
+                                        token: "AUTO",
+                                    },
+                                    {
+                                        name: "string",
+                                        arg_type: PureToken,
+                                        token: "STRING",
+                                    }
+                                ]
+                            }
+                        ]
                     }
-                ],
-                args: [
-                    {
+                )]
+        $item
+    };
+}
+
+/// Reads a string out of the reply of a command run with `Context::call`
+fn call_reply_string(reply: RedisValue) -> RedisResult<String> {
+    match reply {
+        RedisValue::SimpleString(s) | RedisValue::BulkString(s) => Ok(s),
+        RedisValue::StringBuffer(b) => String::from_utf8(b)
+            .map_err(|_| RedisError::Str("ERR hash fields and values must be valid UTF-8")),
+        _ => Err(RedisError::Str("ERR unexpected reply")),
+    }
+}
+
+/// Infers the JSON value of a hash value, falling back to a string. A number is only inferred
+/// when it is written the way JSON.TOHASH writes it, so no digit or formatting is lost
+fn infer_hash_value(value: String) -> Value {
+    match value.as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match Number::from_str(&value) {
+            Ok(number) if number.to_string() == value => Value::Number(number),
+            _ => Value::String(value),
+        },
+    }
+}
+
+pub fn json_fromhash_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let hash_key = args.next_str()?;
+    let mut infer_types = false;
+    while let Some(arg) = args.next() {
+        match arg.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("TYPES") => {
+                infer_types = match args.next_str()? {
+                    types if types.eq_ignore_ascii_case("AUTO") => true,
+                    types if types.eq_ignore_ascii_case("STRING") => false,
+                    _ => return Err(RedisError::Str("ERR TYPES must be AUTO or STRING")),
+                };
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        }
+    }
+
+    // RESP2 replies with a flat `[field, value, ...]` array, RESP3 with a map
+    let fields = match ctx.call("HGETALL", &[hash_key])? {
+        RedisValue::Array(items) => items
+            .into_iter()
+            .map(call_reply_string)
+            .try_collect::<_, Vec<_>, _>()?
+            .into_iter()
+            .tuples::<(String, String)>()
+            .collect_vec(),
+        RedisValue::Map(map) => map
+            .into_iter()
+            .map(|(field, value)| match field {
+                RedisValueKey::String(field) => Ok((field, call_reply_string(value)?)),
+                _ => Err(RedisError::Str("ERR unexpected reply")),
+            })
+            .try_collect::<_, Vec<_>, RedisError>()?,
+        _ => return Err(RedisError::Str("ERR unexpected reply")),
+    };
+    // A missing hash replies with no fields, and there is nothing to convert
+    if fields.is_empty() {
+        return Ok(RedisValue::Null);
+    }
+
+    let len = fields.len();
+    let object: serde_json::Map<_, _> = fields
+        .into_iter()
+        .map(|(field, value)| {
+            let value = if infer_types {
+                infer_hash_value(value)
+            } else {
+                Value::String(value)
+            };
+            (field, value)
+        })
+        .collect();
+    let val = manager.from_str(&serde_json::to_string(&object)?, Format::JSON, true, None)?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    // Like JSON.SET, only replace a key that is either missing or a JSON document
+    redis_key.get_value()?;
+    redis_key.set_value(Vec::new(), val)?;
+    redis_key.notify_keyspace_event(ctx, "json.fromhash")?;
+    manager.apply_changes(ctx);
+    Ok(RedisValue::Integer(len as i64))
+}
+
+///
+/// JSON.TOHASH <dstkey> <jsonkey> <path>
+///
+#[macro_export]
+macro_rules! json_tohash_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.tohash",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Hash, Single("json")],
+                arity: 4,
+                complexity: "O(N) where N is the number of members of the object at path",
+                since: "8.8.0",
+                summary: "Set a hash to the scalar members of the JSON object at path in a key",
+                key_spec: [
+                    {
+                        flags: [Overwrite, Update],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    },
+                    {
+                        flags: [ReadOnly, Access],
+                        begin_search: Index({ index: 2 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "dstkey",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "jsonkey",
+                        arg_type: Key,
+                        key_spec_index: 1,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+/// Lists the members of `value` as hash fields and values, numbers and booleans being written
+/// as JSON
+fn hash_fields<T: SelectValue>(value: &T) -> RedisResult<Vec<(String, String)>> {
+    let items = value.items().ok_or_else(|| err_json("object"))?;
+    items
+        .map(|(field, value)| {
+            let value = match value.get_type() {
+                SelectValueType::String => value.get_str().unwrap_or_default(),
+                SelectValueType::Bool | SelectValueType::Long | SelectValueType::Double => {
+                    serde_json::to_string(value.as_ref())?
+                }
+                _ => {
+                    return Err(RedisError::Str(
+                        "ERR object members must be strings, numbers or booleans",
+                    ))
+                }
+            };
+            Ok((field.to_string(), value))
+        })
+        .try_collect()
+}
+
+pub fn json_tohash_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let hash_key = args.next_str()?;
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    args.done()?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let Some(doc) = key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    let fields = hash_fields(KeyValue::new(doc).get_first(path.get_path())?.as_ref())?;
+
+    // The hash is replaced rather than merged into, as JSON.FROMHASH replaces its JSON key
+    match call_reply_string(ctx.call("TYPE", &[hash_key])?)?.as_str() {
+        "none" | "hash" => {}
+        _ => {
+            return Err(RedisError::Str(
+                "WRONGTYPE Operation against a key holding the wrong kind of value",
+            ))
+        }
+    }
+    ctx.call("DEL", &[hash_key])?;
+    if !fields.is_empty() {
+        let hset_args = std::iter::once(hash_key)
+            .chain(
+                fields
+                    .iter()
+                    .flat_map(|(field, value)| [field.as_str(), value.as_str()]),
+            )
+            .collect_vec();
+        ctx.call("HSET", hset_args.as_slice())?;
+    }
+    manager.apply_changes(ctx);
+    Ok(RedisValue::Integer(fields.len() as i64))
+}
+
+///
+/// JSON.LOAD <key-template> <ndjson>
+///
+#[macro_export]
+macro_rules! json_load_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.load",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: 3,
+                complexity: "O(N) where N is the size of the payload",
+                since: "8.8.0",
+                summary: "Store each document of a newline-delimited JSON payload under a key built from a template",
+                key_spec: [
+                    {
+                        // The keys are built from the payload, so the command is routed by
+                        // the template, which shares their hash tag
+                        flags: [NotKey, Incomplete],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key-template",
+                        arg_type: String,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "ndjson",
+                        arg_type: String,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_load_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult
+where
+    M::O: SelectValue,
+{
+    let mut args = args.into_iter().skip(1);
+    let template_arg = args.next_arg()?;
+    let template = KeyTemplate::parse(template_arg.try_as_str()?)?;
+    let payload = args.next_str()?;
+    args.done()?;
+
+    // The keys aren't arguments, so their ACL permissions are checked here
+    let user = ctx.get_current_user();
+    let mut errors = Vec::new();
+    let mut documents = Vec::new();
+    for (line, json) in payload.lines().enumerate() {
+        if json.trim().is_empty() {
+            continue;
+        }
+        let document = manager
+            .from_str(json, Format::JSON, true, None)
+            .and_then(|value| {
+                let key = ctx.create_string(template.render(&value)?);
+                ctx.acl_check_key_permission(&user, &key, &AclPermissions::UPDATE)?;
+                Ok((key, value))
+            });
+        match document {
+            Ok((key, value)) => documents.push((line + 1, key, value)),
+            Err(e) => errors.push((line + 1, e)),
+        }
+    }
+
+    // Redis routes the command by the template, so the keys of a client's command must be in
+    // its slot, which they are when they share its hash tag. Whatever the master stored is
+    // stored again by its replicas and when loading the AOF.
+    let from_client = !ctx
+        .get_flags()
+        .intersects(ContextFlags::REPLICATED | ContextFlags::LOADING);
+    if from_client && ctx.get_flags().contains(ContextFlags::CLUSTER) {
+        let slot = load::key_slot(&template_arg)?;
+        for (_, key, _) in &documents {
+            if load::key_slot(key)? != slot {
+                return Err(RedisError::Str(
+                    "CROSSSLOT Keys in request don't hash to the same slot as the key template",
+                ));
+            }
+        }
+    }
+
+    let mut stored = 0;
+    for (line, key, value) in documents {
+        let stored_key = manager.open_key_write(ctx, key).and_then(|mut redis_key| {
+            // Like JSON.SET, only replace a key that is either missing or a JSON document
+            redis_key.get_value()?;
+            redis_key.set_value(Vec::new(), value)?;
+            redis_key.notify_keyspace_event(ctx, "json.load")
+        });
+        match stored_key {
+            Ok(()) => stored += 1,
+            Err(e) => errors.push((line, e)),
+        }
+    }
+    if stored > 0 {
+        manager.apply_changes(ctx);
+    }
+
+    errors.sort_by_key(|(line, _)| *line);
+    Ok(vec![
+        RedisValue::Integer(stored),
+        errors
+            .into_iter()
+            .map(|(line, e)| {
+                vec![
+                    RedisValue::Integer(line as i64),
+                    RedisValue::BulkString(e.to_string()),
+                ]
+                .into()
+            })
+            .collect_vec()
+            .into(),
+    ]
+    .into())
+}
+
+/// Returns the paths, as member names, of the object members matching `path`
+fn member_paths<T: SelectValue>(doc: &T, path: &Path) -> RedisResult<Vec<Vec<String>>> {
+    let query = compile(path.get_path())?;
+    if query.is_projection() {
+        return Err(err_projection_readonly());
+    }
+    calc_once_with_paths(query, doc)
+        .into_iter()
+        .map(|matched| {
+            // SAFETY: `calc_once_with_paths` is guaranteed to return a path tracker
+            let tracker = matched.path_tracker.unwrap();
+            let is_member = !tracker.elements.is_empty()
+                && tracker
+                    .elements
+                    .iter()
+                    .all(|element| matches!(element, PTrackerElement::Key(_)));
+            if is_member {
+                Ok(tracker.to_string_path())
+            } else {
+                Err(RedisError::Str("ERR path must match members of objects"))
+            }
+        })
+        .collect()
+}
+
+/// The normalized path of the member at `member`
+fn member_normalized_path(member: &[String]) -> String {
+    let mut tracker = PTracker {
+        elements: Vec::new(),
+    };
+    member.iter().for_each(|element| tracker.add_str(element));
+    tracker.to_normalized_path()
+}
+
+/// Replies with the result for the first match of a legacy path, and with the results of all
+/// matches otherwise
+fn reply_per_member(path: &Path, results: Vec<RedisValue>) -> RedisResult {
+    if path.is_legacy() {
+        results.into_iter().next().ok_or_else(err_invalid_path)
+    } else {
+        Ok(results.into())
+    }
+}
+
+///
+/// JSON.EXPIRE <key> <path> <seconds | PXAT unix-time-milliseconds>
+///
+#[macro_export]
+macro_rules! json_expire_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+                    {
+                        name: "json.expire",
+                        flags: [Write],
+                        acl_categories: [Write, Single("json")],
+                        arity: -4,
+                        complexity: "O(N) where N is the number of members matching the path",
+                        since: "8.8.0",
+                        summary: "Set the expiration time of the object members at path in key",
+                        key_spec: [
+                            {
+                                flags: [ReadWrite, Update],
+                                begin_search: Index({ index: 1 }),
+                                find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                            }
+                        ],
+                        args: [
+                            {
+                                name: "key",
+                                arg_type: Key,
+                                key_spec_index: 0,
+                            },
+                            {
+                                name: "path",
+                                arg_type: String,
+                            },
+                            {
+                                name: "expiration",
+                                arg_type: OneOf,
+                                subargs: [
+                                    {
+                                        name: "seconds",
+                                        arg_type: Integer,
+                                    },
+                                    {
+                                        name: "unix-time-milliseconds",
+                                        arg_type: Integer,
+                                        token: "PXAT",
+                                    }
+                                ]
+                            }
+                        ]
+                    }
+                )]
+        $item
+    };
+}
+
+pub fn json_expire_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let expiration = args.next_str()?;
+    let at = if expiration.eq_ignore_ascii_case("PXAT") {
+        args.next_i64()?
+    } else {
+        let seconds = expiration
+            .parse::<i64>()
+            .map_err(|_| RedisError::Str("ERR invalid expire time"))?;
+        expiry::now_ms().saturating_add(seconds.saturating_mul(1000))
+    };
+    args.done()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key.clone())?;
+    let Some(doc) = redis_key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    let mut members = member_paths(doc, &path)?;
+
+    // An expiration time in the past deletes the members right away, as HEXPIRE does
+    if at <= expiry::now_ms() {
+        let results = vec![RedisValue::Integer(2); members.len()];
+        prepare_paths_for_updating(&mut members);
+        for member in members {
+            redis_key.delete_path(member)?;
+        }
+        if !results.is_empty() {
+            if redis_key.get_value()?.and_then(|v| v.is_empty()) == Some(true) {
+                redis_key.delete()?;
+            }
+            redis_key.notify_keyspace_event(ctx, "json.expired")?;
+            // Replicas delete the members as well, the time being in the past for them too
+            manager.apply_changes(ctx);
+        }
+        return reply_per_member(&path, results);
+    }
+
+    let expiry = redis_key
+        .member_expiry()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let at_str = at.to_string();
+    for member in &members {
+        expiry.set(member.clone(), at);
+        let normalized = member_normalized_path(member);
+        ctx.replicate(
+            "JSON.EXPIRE",
+            &[
+                key.as_slice(),
+                normalized.as_bytes(),
+                b"PXAT".as_slice(),
+                at_str.as_bytes(),
+            ],
+        );
+    }
+    if !members.is_empty() {
+        expiry::track_key(ctx, &key);
+        redis_key.notify_keyspace_event(ctx, "json.expire")?;
+    }
+    reply_per_member(&path, vec![RedisValue::Integer(1); members.len()])
+}
+
+///
+/// JSON.TTL <key> <path>
+///
+#[macro_export]
+macro_rules! json_ttl_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.ttl",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: 3,
+                complexity: "O(N) where N is the number of members matching the path",
+                since: "8.8.0",
+                summary: "Return the remaining time to live, in seconds, of the object members at path in key",
+                key_spec: [
+                    {
+                        flags: [ReadOnly, Access],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_ttl_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    args.done()?;
+
+    let redis_key = manager.open_key_read(ctx, &key)?;
+    let (Some(doc), Some(expiry)) = (redis_key.get_value()?, redis_key.member_expiry()?) else {
+        return Ok(RedisValue::Null);
+    };
+    let now = expiry::now_ms();
+    let results = member_paths(doc, &path)?
+        .iter()
+        .map(|member| {
+            // Rounded like TTL does, a member expiring in 1.5 seconds having 2 seconds left
+            let ttl = expiry.get(member).map_or(-1, |at| (at - now + 500) / 1000);
+            RedisValue::Integer(ttl)
+        })
+        .collect();
+    reply_per_member(&path, results)
+}
+
+///
+/// JSON.PERSIST <key> <path>
+///
+#[macro_export]
+macro_rules! json_persist_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+                    {
+                        name: "json.persist",
+                        flags: [Write],
+                        acl_categories: [Write, Single("json")],
+                        arity: 3,
+                        complexity: "O(N) where N is the number of members matching the path",
+                        since: "8.8.0",
+                        summary: "Remove the expiration time of the object members at path in key",
+                        key_spec: [
+                            {
+                                flags: [ReadWrite, Update],
+                                begin_search: Index({ index: 1 }),
+                                find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                            }
+                        ],
+                        args: [
+                            {
+                                name: "key",
+                                arg_type: Key,
+                                key_spec_index: 0,
+                            },
+                            {
+                                name: "path",
+                                arg_type: String,
+                            }
+                        ]
+                    }
+                )]
+        $item
+    };
+}
+
+pub fn json_persist_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    args.done()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let Some(doc) = redis_key.get_value()? else {
+        return Ok(RedisValue::Null);
+    };
+    let members = member_paths(doc, &path)?;
+    let expiry = redis_key
+        .member_expiry()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let removed = members
+        .iter()
+        .map(|member| expiry.remove(member))
+        .collect_vec();
+    if removed.contains(&true) {
+        redis_key.notify_keyspace_event(ctx, "json.persist")?;
+        manager.apply_changes(ctx);
+    }
+    let results = removed
+        .into_iter()
+        .map(|removed| RedisValue::Integer(removed.into()))
+        .collect();
+    reply_per_member(&path, results)
+}
+
+pub fn json_type_impl<M: Manager>(redis_key: &M::ReadHolder, path: &str) -> RedisResult {
+    let root = redis_key.get_value()?;
+    let value = match root {
+        Some(root) => KeyValue::new(root)
+            .get_values(path)?
+            .iter()
+            .map(|v| RedisValue::from(KeyValue::value_name(v.as_ref())))
+            .collect_vec()
+            .into(),
+        None => RedisValue::Null,
+    };
+    Ok(value)
+}
+
+fn json_type_legacy<M: Manager>(redis_key: &M::ReadHolder, path: &str) -> RedisResult {
+    // A legacy path that normalizes to a projection (e.g. `a + 1` -> `$.a + 1`) addresses no
+    // node; reject it explicitly instead of letting the lenient `map_or(Null)` swallow the
+    // projection error into a silent nil.
+    if compile(path).is_ok_and(|q| q.is_projection()) {
+        return Err(err_projection_readonly());
+    }
+    let value = redis_key.get_value()?.map_or(RedisValue::Null, |doc| {
+        KeyValue::new(doc)
+            .get_type(path)
+            .map_or(RedisValue::Null, |s| s.into())
+    });
+    Ok(value)
+}
+
+enum NumOp {
+    Incr,
+    Mult,
+    Pow,
+}
+
+/// The `MIN`/`MAX` bounds of a numeric command, and whether a result past them is capped at the
+/// bound (`CLAMP`, the default) or leaves every path unwritten (`FAIL`). `CREATE` holds the
+/// initial value of a missing number, and `CREATEPATH` lets its missing parent objects be created
+/// too.
+#[derive(Default)]
+struct NumOptions {
+    min: Option<Number>,
+    max: Option<Number>,
+    fail: bool,
+    create: Option<Number>,
+    create_path: bool,
+}
+
+/// The bound a numeric result fell past
+#[derive(Clone, Copy)]
+enum BoundHit {
+    Min,
+    Max,
+}
+
+impl BoundHit {
+    fn reply(hit: Option<Self>) -> RedisValue {
+        match hit {
+            Some(Self::Min) => RedisValue::SimpleStringStatic("MIN"),
+            Some(Self::Max) => RedisValue::SimpleStringStatic("MAX"),
+            None => RedisValue::Null,
+        }
+    }
+
+    fn reply_all(hits: Vec<Option<Self>>) -> RedisValue {
+        hits.into_iter().map(Self::reply).collect_vec().into()
+    }
+}
+
+/// What a numeric command does to a single matched number
+enum NumUpdate {
+    /// Write the result of the operation
+    Apply,
+    /// Write the bound the result fell past instead (`CLAMP`)
+    Clamp(Number, BoundHit),
+    /// Keep the current value, as the result fell past a bound (`FAIL`), or the result of
+    /// another path did
+    Keep(Number, Option<BoundHit>),
+}
+
+/// A number compared with the bounds, exactly when it is an integer
+#[derive(Clone, Copy)]
+enum BoundedNum {
+    Int(i128),
+    Float(f64),
+}
+
+impl BoundedNum {
+    fn of(n: &Number) -> Option<Self> {
+        n.as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from))
+            .map(Self::Int)
+            .or_else(|| n.as_f64().map(Self::Float))
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+
+    /// Applies `op`, with the integer arithmetic of the numeric commands when both operands
+    /// are integers and the result does not overflow
+    fn apply(self, by: Self, op: &NumOp) -> Self {
+        let int = match (self, by) {
+            (Self::Int(num), Self::Int(by)) => match op {
+                NumOp::Incr => num.checked_add(by),
+                NumOp::Mult => num.checked_mul(by),
+                NumOp::Pow => u32::try_from(by).ok().and_then(|by| num.checked_pow(by)),
+            },
+            _ => None,
+        };
+        int.map_or_else(
+            || {
+                let (num, by) = (self.as_f64(), by.as_f64());
+                Self::Float(match op {
+                    NumOp::Incr => num + by,
+                    NumOp::Mult => num * by,
+                    NumOp::Pow => num.powf(by),
+                })
+            },
+            Self::Int,
+        )
+    }
+
+    fn cmp(self, other: Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(&b)),
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl NumOptions {
+    fn parse(args: impl Iterator<Item = RedisString>) -> RedisResult<Self> {
+        let mut args = args.peekable();
+        let parse_bound = |arg: &str| {
+            serde_json::from_str::<Number>(arg)
+                .map_err(|_| RedisError::Str("ERR bound is not a number"))
+        };
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let arg = arg.try_as_str()?;
+            if arg.eq_ignore_ascii_case("MIN") {
+                options.min = Some(parse_bound(args.next_str()?)?);
+            } else if arg.eq_ignore_ascii_case("MAX") {
+                options.max = Some(parse_bound(args.next_str()?)?);
+            } else if arg.eq_ignore_ascii_case("CLAMP") {
+                options.fail = false;
+            } else if arg.eq_ignore_ascii_case("FAIL") {
+                options.fail = true;
+            } else if arg.eq_ignore_ascii_case("CREATE") {
+                // The initial value is optional, and told apart from a following option by being
+                // a number
+                let initial = args
+                    .peek()
+                    .and_then(|arg| serde_json::from_str::<Number>(arg.try_as_str().ok()?).ok());
+                if initial.is_some() {
+                    args.next();
+                }
+                options.create = Some(initial.unwrap_or_else(|| 0.into()));
+            } else if arg.eq_ignore_ascii_case("CREATEPATH") {
+                options.create_path = true;
+            } else {
+                return Err(RedisError::Str("ERR syntax error"));
+            }
+        }
+        if options.create_path && options.create.is_none() {
+            options.create = Some(0.into());
+        }
+        if let (Some(min), Some(max)) = (&options.min, &options.max) {
+            let (min, max) = (BoundedNum::of(min), BoundedNum::of(max));
+            if min.zip(max).and_then(|(min, max)| min.cmp(max)) == Some(Ordering::Greater) {
+                return Err(RedisError::Str("ERR MIN is greater than MAX"));
+            }
+        }
+        Ok(options)
+    }
+
+    const fn is_bounded(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// Checks the result `op` would leave in `v` against the bounds, before anything is written,
+    /// so a rejected or clamped result never reaches the document (or promotes a typed array)
+    fn plan<T: SelectValue>(&self, v: &T, number: &str, op: &NumOp) -> RedisResult<NumUpdate> {
+        if !self.is_bounded() {
+            return Ok(NumUpdate::Apply);
+        }
+        let current = current_number(v)?;
+        let by = serde_json::from_str::<Number>(number).map_err(|_| err_not_a_number())?;
+        let (Some(num), Some(by)) = (BoundedNum::of(&current), BoundedNum::of(&by)) else {
+            return Err(err_not_a_number());
+        };
+        let result = num.apply(by, op);
+        let past = |bound: &Number, past: Ordering| {
+            BoundedNum::of(bound).and_then(|bound| result.cmp(bound)) == Some(past)
+        };
+        let (bound, hit) = match (&self.min, &self.max) {
+            (Some(min), _) if past(min, Ordering::Less) => (min, BoundHit::Min),
+            (_, Some(max)) if past(max, Ordering::Greater) => (max, BoundHit::Max),
+            _ => return Ok(NumUpdate::Apply),
+        };
+        if self.fail {
+            Ok(NumUpdate::Keep(current, Some(hit)))
+        } else {
+            Ok(NumUpdate::Clamp(bound.clone(), hit))
+        }
+    }
+
+    /// Plans the update of each number in `values`, in which `FAIL` keeps every number as it is
+    /// when any of them falls past a bound
+    fn plan_all<T: SelectValue>(
+        &self,
+        values: &[Option<&T>],
+        number: &str,
+        op: &NumOp,
+    ) -> RedisResult<Vec<Option<NumUpdate>>> {
+        let mut updates: Vec<_> = values
+            .iter()
+            .map(|v| v.map(|v| self.plan(v, number, op)).transpose())
+            .try_collect()?;
+        if updates
+            .iter()
+            .any(|u| matches!(u, Some(NumUpdate::Keep(..))))
+        {
+            for (u, v) in updates.iter_mut().zip(values) {
+                if let (Some(u @ NumUpdate::Apply), Some(v)) = (u, v) {
+                    *u = NumUpdate::Keep(current_number(*v)?, None);
+                }
+            }
+        }
+        Ok(updates)
+    }
+}
+
+fn current_number<T: SelectValue>(v: &T) -> RedisResult<Number> {
+    match v.get_type() {
+        SelectValueType::Long => v.get_long().map(Number::from),
+        _ => v.get_double().and_then(Number::from_f64),
+    }
+    .ok_or_else(err_not_a_number)
+}
+
+fn json_num_op<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+    cmd: &str,
+    op: NumOp,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let number = args.next_str()?;
+    let options = NumOptions::parse(args)?;
+    if options.create.is_some() && !matches!(op, NumOp::Incr) {
+        return Err(RedisError::Str("ERR syntax error"));
+    }
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    // With bounds, the reply is the usual one followed by the bound each path hit, if any
+    let with_hits = |reply: RedisValue, hits: RedisValue| -> RedisValue {
+        if options.is_bounded() {
+            vec![reply, hits].into()
+        } else {
+            reply
+        }
+    };
+
+    // check context flags to see if RESP3 is enabled
+    if is_resp3(ctx) {
+        let (res, hits): (Vec<_>, Vec<_>) = json_num_op_impl(
+            manager,
+            &mut redis_key,
+            ctx,
+            path.get_path(),
+            number,
+            &op,
+            &options,
+            cmd,
+        )?
+        .into_iter()
+        .map(|v| {
+            let hit = v.as_ref().and_then(|(_, hit)| *hit);
+            let v = v.map_or(RedisValue::Null, |(v, _)| {
+                if let Some(i) = v.as_i64() {
+                    RedisValue::Integer(i)
+                } else {
+                    RedisValue::Float(v.as_f64().unwrap_or_default())
+                }
+            });
+            (v, hit)
+        })
+        .unzip();
+        Ok(with_hits(res.into(), BoundHit::reply_all(hits)))
+    } else if path.is_legacy() {
+        let (res, hit) = json_num_op_legacy(
+            manager,
+            &mut redis_key,
+            ctx,
+            path.get_path(),
+            number,
+            &op,
+            &options,
+            cmd,
+        )?;
+        Ok(with_hits(res.to_string().into(), BoundHit::reply(hit)))
+    } else {
+        let (results, hits): (Vec<_>, Vec<_>) = json_num_op_impl(
+            manager,
+            &mut redis_key,
+            ctx,
+            path.get_path(),
+            number,
+            &op,
+            &options,
+            cmd,
+        )?
+        .into_iter()
+        .map(|v| v.map_or((None, None), |(v, hit)| (Some(v), hit)))
+        .unzip();
+
+        // Convert to RESP2 format return as one JSON array
+        let values = to_json_value::<Number>(results, Value::Null);
+        Ok(with_hits(
+            KeyValue::<M::V>::serialize_object(&values, &ReplyFormatOptions::default())?.into(),
+            BoundHit::reply_all(hits),
+        ))
+    }
+}
+
+/// Applies `op` to each number matched by `path`, replying with the new value of each matched
+/// path (`None` for a non-number), and the bound it hit, if any
+#[allow(clippy::too_many_arguments)]
+fn json_num_op_impl<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    number: &str,
+    op: &NumOp,
+    options: &NumOptions,
+    cmd: &str,
+) -> RedisResult<Vec<Option<(Number, Option<BoundHit>)>>> {
+    let mut need_notify = json_num_create(&manager, redis_key, path, options)?;
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let (values, paths): (Vec<_>, Vec<_>) = get_all_values_and_paths(path, root)?
+        .into_iter()
+        .map(|(v, p)| {
+            matches!(
+                v.get_type(),
+                SelectValueType::Double | SelectValueType::Long
+            )
+            .then_some((v, p))
+            .unzip()
+        })
+        .unzip();
+    let values = values.iter().map(|v| v.as_deref()).collect_vec();
+    let updates = options.plan_all(&values, number, op)?;
+
+    let res = paths
+        .into_iter()
+        .zip(updates)
+        .map(|u| match u {
+            (Some(p), Some(u)) => {
+                apply_num_update(&manager, redis_key, p, number, op, u, &mut need_notify).map(Some)
+            }
+            _ => Ok(None),
+        })
+        .try_collect()?;
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, cmd)?;
+        manager.apply_changes(ctx);
+    }
+    Ok(res)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn json_num_op_legacy<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    number: &str,
+    op: &NumOp,
+    options: &NumOptions,
+    cmd: &str,
+) -> RedisResult<(Number, Option<BoundHit>)> {
+    let mut need_notify = json_num_create(&manager, redis_key, path, options)?;
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let (values, paths): (Vec<_>, Vec<_>) = get_all_values_and_paths(path, root)?
+        .into_iter()
+        .filter(|(v, _)| {
+            v.get_type() == SelectValueType::Double || v.get_type() == SelectValueType::Long
+        })
+        .unzip();
+    let values = values.iter().map(|v| Some(&**v)).collect_vec();
+    let updates = options.plan_all(&values, number, op)?;
+    if !updates.is_empty() {
+        // The reply is the value left at the last path, and the bound hit by any of them
+        let res = paths
+            .into_iter()
+            .zip(updates.into_iter().flatten())
+            .try_fold(None, |res: Option<(Number, Option<BoundHit>)>, (p, u)| {
+                let (value, hit) =
+                    apply_num_update(&manager, redis_key, p, number, op, u, &mut need_notify)?;
+                Ok::<_, RedisError>(Some((value, hit.or(res.and_then(|(_, hit)| hit)))))
+            })?
+            // SAFETY: res is modified to Some if there is at least one path
+            .unwrap();
+        if need_notify {
+            redis_key.notify_keyspace_event(ctx, cmd)?;
+            manager.apply_changes(ctx);
+        }
+        Ok(res)
+    } else {
+        Err(err_invalid_path_or("does not contains a number"))
+    }
+}
+
+/// Adds the number missing at `path` with its `CREATE` initial value, nesting it in the missing
+/// parent objects with `CREATEPATH`. Returns whether it was added.
+fn json_num_create<M: Manager>(
+    manager: &M,
+    redis_key: &mut M::WriteHolder,
+    path: &str,
+    options: &NumOptions,
+) -> RedisResult<bool> {
+    let Some(initial) = &options.create else {
+        return Ok(false);
+    };
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    if !get_all_values_and_paths(path, root)?.is_empty() {
+        return Ok(false);
+    }
+    let Some((parent, missing)) = KeyValue::new(root).find_add_path(path, options.create_path)?
+    else {
+        return Ok(false);
+    };
+    let value = manager.from_str(&initial.to_string(), Format::JSON, true, None)?;
+    redis_key.dict_add(parent, &missing[0], manager.nest(&missing[1..], value)?)
+}
+
+/// Writes a single planned numeric update, returning the value left at `path`
+fn apply_num_update<M: Manager>(
+    manager: &M,
+    redis_key: &mut M::WriteHolder,
+    path: Vec<String>,
+    number: &str,
+    op: &NumOp,
+    update: NumUpdate,
+    need_notify: &mut bool,
+) -> RedisResult<(Number, Option<BoundHit>)> {
+    match update {
+        NumUpdate::Apply => {
+            *need_notify = true;
+            let res = match op {
+                NumOp::Incr => redis_key.incr_by(path, number)?,
+                NumOp::Mult => redis_key.mult_by(path, number)?,
+                NumOp::Pow => redis_key.pow_by(path, number)?,
+            };
+            Ok((res, None))
+        }
+        NumUpdate::Clamp(bound, hit) => {
+            *need_notify = true;
+            let value = manager.from_str(&bound.to_string(), Format::JSON, false, None)?;
+            redis_key.set_value(path, value)?;
+            Ok((bound, Some(hit)))
+        }
+        NumUpdate::Keep(current, hit) => Ok((current, hit)),
+    }
+}
+
+///
+/// JSON.NUMINCRBY <key> <path> <number> [MIN min] [MAX max] [CLAMP|FAIL] [CREATE [initial]]
+///                [CREATEPATH]
+///
+#[macro_export]
+macro_rules! json_numincrby_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.numincrby",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: -4,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Increment the number value stored at path by number",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "number",
+                        arg_type: Double,
+                    },
+                    {
+                        name: "min",
+                        token: "MIN",
+                        arg_type: Double,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "max",
+                        token: "MAX",
+                        arg_type: Double,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "policy",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "clamp",
+                                arg_type: PureToken,
+                                token: "CLAMP",
+                            },
+                            {
+                                name: "fail",
+                                arg_type: PureToken,
+                                token: "FAIL",
+                            }
+                        ]
+                    },
+                    {
+                        name: "create",
+                        token: "CREATE",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "initial",
+                                arg_type: Double,
+                                flags: [Optional],
+                            }
+                        ]
+                    },
+                    {
+                        name: "createpath",
+                        arg_type: PureToken,
+                        token: "CREATEPATH",
+                        flags: [Optional],
+                    },
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_num_incrby_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    json_num_op(manager, ctx, args, "json.numincrby", NumOp::Incr)
+}
+
+///
+/// JSON.NUMMULTBY <key> <path> <number> [MIN min] [MAX max] [CLAMP|FAIL]
+///
+#[macro_export]
+macro_rules! json_nummultby_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.nummultby",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: -4,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Multiply the number value stored at path by number",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "number",
+                        arg_type: Double,
+                    },
+                    {
+                        name: "min",
+                        token: "MIN",
+                        arg_type: Double,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "max",
+                        token: "MAX",
+                        arg_type: Double,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "policy",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "clamp",
+                                arg_type: PureToken,
+                                token: "CLAMP",
+                            },
+                            {
+                                name: "fail",
+                                arg_type: PureToken,
+                                token: "FAIL",
+                            }
+                        ]
+                    },
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_num_multby_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    json_num_op(manager, ctx, args, "json.nummultby", NumOp::Mult)
+}
+
+///
+/// JSON.NUMPOWBY <key> <path> <number>
+///
+#[macro_export]
+macro_rules! json_numpowby_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.numpowby",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: 4,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Raise the number value stored at path to the power of number",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "number",
+                        arg_type: Double,
+                    },
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_num_powby_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    json_num_op(manager, ctx, args, "json.numpowby", NumOp::Pow)
+}
+
+//
+/// JSON.TOGGLE <key> <path>
+///
+#[macro_export]
+macro_rules! json_toggle_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.toggle",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: 3,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "2.0.0",
+                summary: "Toggle the boolean value stored at path",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_bool_toggle_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    if path.is_legacy() {
+        json_bool_toggle_legacy(manager, &mut redis_key, ctx, path.get_path())
+    } else {
+        json_bool_toggle_impl(manager, &mut redis_key, ctx, path.get_path())
+    }
+}
+
+fn json_bool_toggle_impl<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Bool)?;
+    let mut res = vec![];
+    let mut need_notify = false;
+    for p in paths {
+        res.push(match p {
+            Some(p) => {
+                need_notify = true;
+                RedisValue::Integer((redis_key.bool_toggle(p)?).into())
+            }
+            None => RedisValue::Null,
+        });
+    }
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.toggle")?;
+        manager.apply_changes(ctx);
+    }
+    Ok(res.into())
+}
+
+fn json_bool_toggle_legacy<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let paths = find_paths(path, root, |v| v.get_type() == SelectValueType::Bool)?;
+    if !paths.is_empty() {
+        let mut res = false;
+        for p in paths {
+            res = redis_key.bool_toggle(p)?;
+        }
+        redis_key.notify_keyspace_event(ctx, "json.toggle")?;
+        manager.apply_changes(ctx);
+        Ok(res.to_string().into())
+    } else {
+        Err(err_invalid_path_or("not a bool"))
+    }
+}
+
+///
+/// JSON.STRAPPEND <key> [path] <json-string>
+///
+#[macro_export]
+macro_rules! json_strappend_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.strappend",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -3,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Append the json-string values to the string at path",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "json-string",
+                        arg_type: String,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_str_append_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    json_str_concat(manager, ctx, args, StrEnd::Back)
+}
+
+///
+/// JSON.STRPREPEND <key> [path] <json-string>
+///
+#[macro_export]
+macro_rules! json_strprepend_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.strprepend",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -3,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Prepend the json-string values to the string at path",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "json-string",
+                        arg_type: String,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_str_prepend_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    json_str_concat(manager, ctx, args, StrEnd::Front)
+}
+
+/// The end of the string JSON.STRAPPEND and JSON.STRPREPEND add to
+#[derive(Clone, Copy)]
+enum StrEnd {
+    Front,
+    Back,
+}
+
+impl StrEnd {
+    const fn event(self) -> &'static str {
+        match self {
+            Self::Front => "json.strprepend",
+            Self::Back => "json.strappend",
+        }
+    }
+
+    fn apply<M: Manager>(
+        self,
+        redis_key: &mut M::WriteHolder,
+        p: Vec<String>,
+        json: &str,
+    ) -> RedisResult<usize> {
+        match self {
+            Self::Front => redis_key.str_prepend(p, json.to_string()),
+            Self::Back => redis_key.str_append(p, json.to_string()),
+        }
+    }
+}
+
+fn json_str_concat<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+    end: StrEnd,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path_or_json = args.next_str()?;
+
+    let path;
+    let json;
+
+    // path is optional
+    if let Ok(val) = args.next_arg() {
+        path = Path::new(path_or_json);
+        json = val.try_as_str()?;
+    } else {
+        path = Path::default();
+        json = path_or_json;
+    }
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    if path.is_legacy() {
+        json_str_concat_legacy(manager, &mut redis_key, ctx, path.get_path(), json, end)
+    } else {
+        json_str_concat_impl(manager, &mut redis_key, ctx, path.get_path(), json, end)
+    }
+}
+
+fn json_str_concat_impl<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    json: &str,
+    end: StrEnd,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::String)?;
+
+    let mut res = vec![];
+    let mut need_notify = false;
+    for p in paths {
+        res.push(match p {
+            Some(p) => {
+                need_notify = true;
+                (end.apply::<M>(redis_key, p, json)?).into()
+            }
+            _ => RedisValue::Null,
+        });
+    }
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, end.event())?;
+        manager.apply_changes(ctx);
+    }
+    Ok(res.into())
+}
+
+fn json_str_concat_legacy<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    json: &str,
+    end: StrEnd,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let paths = find_paths(path, root, |v| v.get_type() == SelectValueType::String)?;
+    if !paths.is_empty() {
+        let mut res = None;
+        for p in paths {
+            res = Some(end.apply::<M>(redis_key, p, json)?);
+        }
+        redis_key.notify_keyspace_event(ctx, end.event())?;
+        manager.apply_changes(ctx);
+        // SAFETY: res is modified to Some if there is at least one path
+        Ok(res.unwrap().into())
+    } else {
+        Err(err_invalid_path_or("not a string"))
+    }
+}
+
+///
+/// JSON.STRSETRANGE <key> <path> <offset> <json-string> [UNIT BYTES|CODEPOINTS|UTF16]
+///
+#[macro_export]
+macro_rules! json_strsetrange_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.strsetrange",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -5,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Overwrite part of the string at path, starting at the specified offset, with the json-string value",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "offset",
+                        arg_type: Integer,
+                    },
+                    {
+                        name: "json-string",
+                        arg_type: String,
+                    },
+                    {
+                        name: "unit",
+                        token: "UNIT",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "bytes",
+                                arg_type: PureToken,
+                                token: "BYTES",
+                            },
+                            {
+                                name: "codepoints",
+                                arg_type: PureToken,
+                                token: "CODEPOINTS",
+                            },
+                            {
+                                name: "utf16",
+                                arg_type: PureToken,
+                                token: "UTF16",
+                            }
+                        ]
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+/// Parses the optional `UNIT <unit>` ending the string commands, in bytes by default
+fn parse_str_unit(options: &[RedisString]) -> RedisResult<StrUnit> {
+    match options {
+        [] => Ok(StrUnit::Bytes),
+        [token, unit] if token.try_as_str()?.eq_ignore_ascii_case("UNIT") => {
+            unit.try_as_str()?.parse()
+        }
+        _ => Err(RedisError::Str("ERR syntax error")),
+    }
+}
+
+pub fn json_str_set_range_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let offset = usize::try_from(args.next_i64()?)
+        .map_err(|_| RedisError::Str("ERR offset is out of range"))?;
+    let json = args.next_str()?;
+    let unit = parse_str_unit(&args.collect_vec())?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let paths = find_all_paths(path.get_path(), root, |v| {
+        v.get_type() == SelectValueType::String
+    })?;
+    if path.is_legacy() && paths.iter().all(Option::is_none) {
+        return Err(err_invalid_path_or("not a string"));
+    }
+
+    let mut res = vec![];
+    let mut need_notify = false;
+    for p in paths {
+        res.push(match p {
+            Some(p) => {
+                need_notify = true;
+                Some(redis_key.str_set_range(p, offset, json.to_string(), unit)? as i64)
+            }
+            _ => None,
+        });
+    }
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.strsetrange")?;
+        manager.apply_changes(ctx);
+    }
+
+    let reply = |len: Option<i64>| len.map_or(RedisValue::Null, RedisValue::Integer);
+    if path.is_legacy() {
+        Ok(reply(res.into_iter().flatten().last()))
+    } else {
+        Ok(res.into_iter().map(reply).collect_vec().into())
+    }
+}
+
+///
+/// JSON.STRGETRANGE <key> <path> <start> <end> [UNIT BYTES|CODEPOINTS|UTF16]
+///
+#[macro_export]
+macro_rules! json_strgetrange_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.strgetrange",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -5,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Return the part of the string at path between the start and end offsets (both inclusive)",
+                key_spec: [
+                    {
+                        flags: [ReadOnly],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "start",
+                        arg_type: Integer,
+                    },
+                    {
+                        name: "end",
+                        arg_type: Integer,
+                    },
+                    {
+                        name: "unit",
+                        token: "UNIT",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "bytes",
+                                arg_type: PureToken,
+                                token: "BYTES",
+                            },
+                            {
+                                name: "codepoints",
+                                arg_type: PureToken,
+                                token: "CODEPOINTS",
+                            },
+                            {
+                                name: "utf16",
+                                arg_type: PureToken,
+                                token: "UTF16",
+                            }
+                        ]
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+/// Replies with the substrings serialized as JSON strings, like JSON.GET would
+pub fn json_str_get_range_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let start = args.next_i64()?;
+    let end = args.next_i64()?;
+    let unit = parse_str_unit(&args.collect_vec())?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+    let root = key.get_value()?.ok_or_else(RedisError::nonexistent_key)?;
+
+    let values = find_all_values(path.get_path(), root, |v| {
+        v.get_type() == SelectValueType::String
+    })?;
+    let res = values
+        .into_iter()
+        .map(|v| match v.as_deref().and_then(SelectValue::as_str) {
+            Some(s) => Ok(Some(serde_json::to_string(unit.substring(s, start, end)?)?)),
+            None => Ok(None),
+        })
+        .collect::<RedisResult<Vec<Option<String>>>>()?;
+
+    if path.is_legacy() {
+        res.into_iter()
+            .flatten()
+            .next()
+            .map(RedisValue::BulkString)
+            .ok_or_else(|| err_invalid_path_or("not a string"))
+    } else {
+        Ok(res
+            .into_iter()
+            .map(|s| s.map_or(RedisValue::Null, RedisValue::BulkString))
+            .collect_vec()
+            .into())
+    }
+}
+
+///
+/// JSON.STRLEN <key> [path] [UNIT BYTES|CODEPOINTS|UTF16]
+///
+#[macro_export]
+macro_rules! json_strlen_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.strlen",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -2,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Report the length of the JSON String at path in key",
+                key_spec: [
+                    {
+                        flags: [ReadOnly],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                        flags: [Optional],
+                    },
+                    {
+                        name: "unit",
+                        token: "UNIT",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "bytes",
+                                arg_type: PureToken,
+                                token: "BYTES",
+                            },
+                            {
+                                name: "codepoints",
+                                arg_type: PureToken,
+                                token: "CODEPOINTS",
+                            },
+                            {
+                                name: "utf16",
+                                arg_type: PureToken,
+                                token: "UTF16",
+                            }
+                        ]
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_str_len_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let rest = args.collect_vec();
+
+    // The path is optional, so it is present only when an odd number of arguments follow the
+    // key (`UNIT <unit>` comes in pairs)
+    let (path, options) = match rest.split_first() {
+        Some((path, options)) if rest.len() % 2 == 1 => (Path::new(path.try_as_str()?), options),
+        _ => (Path::default(), rest.as_slice()),
+    };
+    let unit = parse_str_unit(options)?;
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    if path.is_legacy() {
+        json_str_len_legacy::<M>(&key, path.get_path(), unit)
+    } else {
+        json_str_len_impl::<M>(&key, ctx, path.get_path(), unit)
+    }
+}
+
+fn json_str_len_impl<M: Manager>(
+    redis_key: &M::ReadHolder,
+    ctx: &Context,
+    path: &str,
+    unit: StrUnit,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let values = find_all_values(path, root, |v| v.get_type() == SelectValueType::String)?;
+    let mut res = vec![];
+    for v in values {
+        res.push(v.map_or(RedisValue::Null, |v| {
+            v.as_str()
+                .map(|s| (unit.len(s) as i64).into())
+                .unwrap_or_else(|| {
+                    ctx.log_warning("String type returned None from as_str()");
+                    RedisValue::Null
+                })
+        }));
+    }
+    Ok(res.into())
+}
+
+fn json_str_len_legacy<M: Manager>(
+    redis_key: &M::ReadHolder,
+    path: &str,
+    unit: StrUnit,
+) -> RedisResult {
+    match redis_key.get_value()? {
+        Some(doc) => Ok(RedisValue::Integer(
+            KeyValue::new(doc).str_len(path, unit)? as i64
+        )),
+        None => Ok(RedisValue::Null),
+    }
+}
+
+///
+/// JSON.ARRAPPEND <key> <path> <json> [json ...]
+///
+#[macro_export]
+macro_rules! json_arrappend_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.arrappend",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -3,
+                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Append the JSON values into the array at path after the last element in it",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "json",
+                        arg_type: String,
+                        flags: [Multiple],
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_arr_append_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1).peekable();
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+
+    // We require at least one JSON item to append
+    args.peek().ok_or(RedisError::WrongArity)?;
+
+    let args =
+        args.try_fold::<_, _, RedisResult<_>>(Vec::with_capacity(args.len()), |mut acc, arg| {
+            let json = arg.try_as_str()?;
+            acc.push(manager.from_str(json, Format::JSON, true, None)?);
+            Ok(acc)
+        })?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+
+    if path.is_legacy() {
+        json_arr_append_legacy(manager, &mut redis_key, ctx, &path, args)
+    } else {
+        json_arr_append_impl(manager, &mut redis_key, ctx, path.get_path(), args)
+    }
+}
+
+fn json_arr_append_legacy<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &Path,
+    args: Vec<M::O>,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let mut paths = find_paths(path.get_path(), root, |v| {
+        v.get_type() == SelectValueType::Array
+    })?;
+    if paths.is_empty() {
+        Err(err_invalid_path_or("not an array"))
+    } else if paths.len() == 1 {
+        let res = redis_key.arr_append(paths.pop().unwrap(), args)?;
+        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
+        manager.apply_changes(ctx);
+        Ok(res.into())
+    } else {
+        let mut res = 0;
+        for p in paths {
+            res = redis_key.arr_append(p, args.clone())?;
+        }
+        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
+        manager.apply_changes(ctx);
+        Ok(res.into())
+    }
+}
+
+fn json_arr_append_impl<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    args: Vec<M::O>,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
+
+    let mut res = vec![];
+    let mut need_notify = false;
+    for p in paths {
+        res.push(match p {
+            Some(p) => {
+                need_notify = true;
+                (redis_key.arr_append(p, args.clone())? as i64).into()
+            }
+            _ => RedisValue::Null,
+        });
+    }
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
+        manager.apply_changes(ctx);
+    }
+    Ok(res.into())
+}
+
+pub enum FoundIndex {
+    Index(i64),
+    NotFound,
+    NotArray,
+}
+
+impl From<FoundIndex> for RedisValue {
+    fn from(e: FoundIndex) -> Self {
+        match e {
+            FoundIndex::NotFound => Self::Integer(-1),
+            FoundIndex::NotArray => Self::Null,
+            FoundIndex::Index(i) => Self::Integer(i),
+        }
+    }
+}
+
+pub enum ObjectLen {
+    Len(usize),
+    NoneExisting,
+    NotObject,
+}
+
+///
+/// JSON.ARRINDEX <key> <path> <json-value | filter> [start [stop]] [FIRST | LAST | ALL] [COUNT n]
+///
+#[macro_export]
+macro_rules! json_arrindex_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.arrindex",
+                flags: [ReadOnly],
+                acl_categories: [Read, Single("json")],
+                arity: -4,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Search for the first occurrence of a JSON value in an array",
+                key_spec: [
+                    {
+                        flags: [ReadOnly],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
                         name: "key",
                         arg_type: Key,
                         key_spec_index: 0,
@@ -1865,6 +5058,53 @@ macro_rules! json_strlen_command {
                     {
                         name: "path",
                         arg_type: String,
+                    },
+                    {
+                        name: "json-value",
+                        arg_type: String,
+                    },
+                    {
+                        name: "range",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "start",
+                                arg_type: Integer,
+                            },
+                            {
+                                name: "stop",
+                                arg_type: Integer,
+                                flags: [Optional],
+                            }
+                        ]
+                    },
+                    {
+                        name: "mode",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "first",
+                                arg_type: PureToken,
+                                token: "FIRST",
+                            },
+                            {
+                                name: "last",
+                                arg_type: PureToken,
+                                token: "LAST",
+                            },
+                            {
+                                name: "all",
+                                arg_type: PureToken,
+                                token: "ALL",
+                            }
+                        ]
+                    },
+                    {
+                        name: "count",
+                        token: "COUNT",
+                        arg_type: Integer,
                         flags: [Optional],
                     }
                 ]
@@ -1874,69 +5114,236 @@ macro_rules! json_strlen_command {
     };
 }
 
-pub fn json_str_len_command_impl<M: Manager>(
+pub fn json_arr_index_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    let mut args = args.into_iter().skip(1);
+    let mut args = args.into_iter().skip(1).peekable();
+
     let key = args.next_arg()?;
-    let path = args.next_str().map(Path::new).unwrap_or_default();
+    let path = Path::new(args.next_str()?);
+    let value = args.next_str()?;
+
+    // The optional range is positional, so it is taken as long as the arguments are integers
+    let mut range = [0; 2];
+    for bound in &mut range {
+        match args.peek().map(RedisString::parse_integer) {
+            Some(Ok(i)) => *bound = i,
+            _ => break,
+        }
+        args.next();
+    }
+    let [start, end] = range;
+
+    let mut from_end = false;
+    let mut count = None;
+    while let Some(s) = args.next() {
+        match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("FIRST") => from_end = false,
+            arg if arg.eq_ignore_ascii_case("LAST") => from_end = true,
+            arg if arg.eq_ignore_ascii_case("ALL") => count = Some(count.unwrap_or(0)),
+            arg if arg.eq_ignore_ascii_case("COUNT") => {
+                let n = args.next_i64()?;
+                count = Some(
+                    usize::try_from(n)
+                        .map_err(|_| RedisError::Str("ERR COUNT can't be negative"))?,
+                );
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        };
+    }
+
+    // A JSON value never starts with `?`, which a filter expression always does
+    let filter = value
+        .trim_start()
+        .starts_with('?')
+        .then(|| format!("$[{value}]"));
+    let matcher = match &filter {
+        // Compiled once for all the arrays, and held to a single filter so the argument
+        // can't close the brackets to select anything else
+        Some(filter) => {
+            let query = compile(filter)?;
+            if !query.is_single_filter() {
+                return Err(RedisError::Str("ERR invalid filter expression"));
+            }
+            ArrIndexMatcher::Filter(query)
+        }
+        None => ArrIndexMatcher::Value(serde_json::from_str(value)?),
+    };
+    let query = ArrIndexQuery {
+        matcher,
+        start,
+        end,
+        from_end,
+        count,
+    };
+
+    let key = manager.open_key_read(ctx, &key)?;
+
+    key.get_value()?.map_or_else(
+        || Err(err_invalid_path()),
+        |doc| {
+            if path.is_legacy() {
+                KeyValue::new(doc).arr_index_legacy(path.get_path(), &query)
+            } else {
+                KeyValue::new(doc).arr_index(path.get_path(), &query)
+            }
+        },
+    )
+}
+
+///
+/// JSON.ARRINSERT <key> <path> <index> <json> [json ...]
+///
+#[macro_export]
+macro_rules! json_arrinsert_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.arrinsert",
+                flags: [Write, DenyOOM],
+                acl_categories: [Write, Single("json")],
+                arity: -5,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "1.0.0",
+                summary: "Insert the json values into the array at path before the index (shifts to the right)",
+                key_spec: [
+                    {
+                        flags: [ReadWrite],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "index",
+                        arg_type: Integer,
+                    },
+                    {
+                        name: "json",
+                        arg_type: String,
+                        flags: [Multiple],
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+pub fn json_arr_insert_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> RedisResult {
+    let mut args = args.into_iter().skip(1).peekable();
+
+    let key = args.next_arg()?;
+    let path = Path::new(args.next_str()?);
+    let index = args.next_i64()?;
+
+    // We require at least one JSON item to insert
+    args.peek().ok_or(RedisError::WrongArity)?;
+    let args =
+        args.try_fold::<_, _, RedisResult<_>>(Vec::with_capacity(args.len()), |mut acc, arg| {
+            let json = arg.try_as_str()?;
+            acc.push(manager.from_str(json, Format::JSON, true, None)?);
+            Ok(acc)
+        })?;
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    if path.is_legacy() {
+        json_arr_insert_legacy(manager, &mut redis_key, ctx, path.get_path(), index, args)
+    } else {
+        json_arr_insert_impl(manager, &mut redis_key, ctx, path.get_path(), index, args)
+    }
+}
+
+fn json_arr_insert_impl<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
+    ctx: &Context,
+    path: &str,
+    index: i64,
+    args: Vec<M::O>,
+) -> RedisResult {
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
+
+    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
+
+    let mut res = vec![RedisValue::Null; paths.len()];
+    let mut need_notify = false;
+
+    let mut indexed: Vec<(usize, Vec<String>)> = paths
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.map(|path| (i, path)))
+        .collect();
+    sort_paths_for_mutation(&mut indexed);
 
-    let key = manager.open_key_read(ctx, &key)?;
+    for (orig_idx, p) in indexed {
+        need_notify = true;
+        res[orig_idx] = (redis_key.arr_insert(p, &args, index)? as i64).into();
+    }
 
-    if path.is_legacy() {
-        json_str_len_legacy::<M>(&key, path.get_path())
-    } else {
-        json_str_len_impl::<M>(&key, ctx, path.get_path())
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.arrinsert")?;
+        manager.apply_changes(ctx);
     }
+    Ok(res.into())
 }
 
-fn json_str_len_impl<M: Manager>(
-    redis_key: &M::ReadHolder,
+fn json_arr_insert_legacy<M: Manager>(
+    manager: M,
+    redis_key: &mut M::WriteHolder,
     ctx: &Context,
     path: &str,
+    index: i64,
+    args: Vec<M::O>,
 ) -> RedisResult {
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
-    let values = find_all_values(path, root, |v| v.get_type() == SelectValueType::String)?;
-    let mut res = vec![];
-    for v in values {
-        res.push(v.map_or(RedisValue::Null, |v| {
-            v.get_str()
-                .map(|s| (s.len() as i64).into())
-                .unwrap_or_else(|| {
-                    ctx.log_warning("String type returned None from get_str()");
-                    RedisValue::Null
-                })
-        }));
-    }
-    Ok(res.into())
-}
 
-fn json_str_len_legacy<M: Manager>(redis_key: &M::ReadHolder, path: &str) -> RedisResult {
-    match redis_key.get_value()? {
-        Some(doc) => Ok(RedisValue::Integer(KeyValue::new(doc).str_len(path)? as i64)),
-        None => Ok(RedisValue::Null),
+    let mut paths = find_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
+    if paths.is_empty() {
+        return Err(err_invalid_path_or("not an array"));
     }
+    paths.sort_by(|v1, v2| compare_paths_for_mutation(v1, v2));
+    let res = paths
+        .into_iter()
+        .try_fold(0, |_, p| redis_key.arr_insert(p, &args, index))?;
+    redis_key.notify_keyspace_event(ctx, "json.arrinsert")?;
+    manager.apply_changes(ctx);
+    Ok(res.into())
 }
 
 ///
-/// JSON.ARRAPPEND <key> <path> <json> [json ...]
+/// JSON.ARRINSORT <key> <path> <json> [BY relpath] [DESC] [UNIQUE]
 ///
 #[macro_export]
-macro_rules! json_arrappend_command {
+macro_rules! json_arrinsort_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.arrappend",
+                name: "json.arrinsort",
                 flags: [Write, DenyOOM],
                 acl_categories: [Write, Single("json")],
-                arity: -3,
-                complexity: "O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Append the JSON values into the array at path after the last element in it",
+                arity: -4,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Insert the json value into the sorted array at path, keeping it sorted",
                 key_spec: [
                     {
                         flags: [ReadWrite],
@@ -1957,7 +5364,30 @@ macro_rules! json_arrappend_command {
                     {
                         name: "json",
                         arg_type: String,
-                        flags: [Multiple],
+                    },
+                    {
+                        name: "by",
+                        token: "BY",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "relpath",
+                                arg_type: String,
+                            }
+                        ]
+                    },
+                    {
+                        name: "desc",
+                        arg_type: PureToken,
+                        token: "DESC",
+                        flags: [Optional],
+                    },
+                    {
+                        name: "unique",
+                        arg_type: PureToken,
+                        token: "UNIQUE",
+                        flags: [Optional],
                     }
                 ]
             }
@@ -1966,136 +5396,189 @@ macro_rules! json_arrappend_command {
     };
 }
 
-pub fn json_arr_append_command_impl<M: Manager>(
+/// Parses the path given to `BY`, which is evaluated relative to each array element
+fn parse_relative_path(relpath: &str) -> RedisResult<Path<'_>> {
+    let by = Path::new(relpath);
+    if compile(by.get_path())?.is_projection() {
+        return Err(err_projection_readonly());
+    }
+    Ok(by)
+}
+
+/// Ordering options of JSON.ARRINSORT
+struct SortedInsert<'a> {
+    by: Option<Query<'a>>,
+    desc: bool,
+    unique: bool,
+}
+
+impl SortedInsert<'_> {
+    fn index<V: SelectValue>(&self, arr: &V, v: &Value) -> Option<usize> {
+        KeyValue::arr_insort_index(arr, v, self.by.as_ref(), self.desc, self.unique)
+    }
+}
+
+pub fn json_arr_insort_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    let mut args = args.into_iter().skip(1).peekable();
+    let mut args = args.into_iter().skip(1);
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
+    let value = args.next_str()?;
 
-    // We require at least one JSON item to append
-    args.peek().ok_or(RedisError::WrongArity)?;
+    let mut by = None;
+    let (mut desc, mut unique) = (false, false);
+    while let Some(s) = args.next() {
+        match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("BY") && by.is_none() => {
+                by = Some(parse_relative_path(args.next_str()?)?);
+            }
+            arg if arg.eq_ignore_ascii_case("DESC") => desc = true,
+            arg if arg.eq_ignore_ascii_case("UNIQUE") => unique = true,
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        };
+    }
+    // Compiled once, as it is evaluated on every element the search compares
+    let opts = SortedInsert {
+        by: by.as_ref().map(|by| compile(by.get_path())).transpose()?,
+        desc,
+        unique,
+    };
 
-    let args =
-        args.try_fold::<_, _, RedisResult<_>>(Vec::with_capacity(args.len()), |mut acc, arg| {
-            let json = arg.try_as_str()?;
-            acc.push(manager.from_str(json, Format::JSON, true, None)?);
-            Ok(acc)
-        })?;
+    let json_value: Value = serde_json::from_str(value)?;
+    let val = manager.from_str(value, Format::JSON, true, None)?;
 
     let mut redis_key = manager.open_key_write(ctx, key)?;
-
     if path.is_legacy() {
-        json_arr_append_legacy(manager, &mut redis_key, ctx, &path, args)
+        json_arr_insort_legacy(
+            manager,
+            &mut redis_key,
+            ctx,
+            path.get_path(),
+            &json_value,
+            val,
+            &opts,
+        )
     } else {
-        json_arr_append_impl(manager, &mut redis_key, ctx, path.get_path(), args)
+        json_arr_insort_impl(
+            manager,
+            &mut redis_key,
+            ctx,
+            path.get_path(),
+            &json_value,
+            val,
+            &opts,
+        )
     }
 }
 
-fn json_arr_append_legacy<M: Manager>(
+/// Replies with the index the value was inserted at for each matched array,
+/// or -1 where `UNIQUE` found an equal element already there
+fn json_arr_insort_impl<M: Manager>(
     manager: M,
     redis_key: &mut M::WriteHolder,
     ctx: &Context,
-    path: &Path,
-    args: Vec<M::O>,
+    path: &str,
+    json_value: &Value,
+    val: M::O,
+    opts: &SortedInsert,
 ) -> RedisResult {
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
-    let mut paths = find_paths(path.get_path(), root, |v| {
-        v.get_type() == SelectValueType::Array
-    })?;
-    if paths.is_empty() {
-        Err(err_invalid_path_or("not an array"))
-    } else if paths.len() == 1 {
-        let res = redis_key.arr_append(paths.pop().unwrap(), args)?;
-        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
-        manager.apply_changes(ctx);
-        Ok(res.into())
-    } else {
-        let mut res = 0;
-        for p in paths {
-            res = redis_key.arr_append(p, args.clone())?;
+
+    let values_and_paths = get_all_values_and_paths(path, root)?;
+    let mut res = vec![RedisValue::Null; values_and_paths.len()];
+    let mut indexed = Vec::with_capacity(values_and_paths.len());
+    let mut positions = vec![None; values_and_paths.len()];
+    for (i, (v, p)) in values_and_paths.into_iter().enumerate() {
+        if v.get_type() != SelectValueType::Array {
+            continue;
         }
-        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
+        match opts.index(v.as_ref(), json_value) {
+            Some(pos) => {
+                positions[i] = Some(pos);
+                indexed.push((i, p));
+            }
+            None => res[i] = RedisValue::Integer(-1),
+        }
+    }
+    sort_paths_for_mutation(&mut indexed);
+
+    let need_notify = !indexed.is_empty();
+    for (orig_idx, p) in indexed {
+        let pos = positions[orig_idx].unwrap_or_default();
+        redis_key.arr_insert(p, std::slice::from_ref(&val), pos as i64)?;
+        res[orig_idx] = (pos as i64).into();
+    }
+
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.arrinsort")?;
         manager.apply_changes(ctx);
-        Ok(res.into())
     }
+    Ok(res.into())
 }
 
-fn json_arr_append_impl<M: Manager>(
+fn json_arr_insort_legacy<M: Manager>(
     manager: M,
     redis_key: &mut M::WriteHolder,
     ctx: &Context,
     path: &str,
-    args: Vec<M::O>,
+    json_value: &Value,
+    val: M::O,
+    opts: &SortedInsert,
 ) -> RedisResult {
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
-    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
 
-    let mut res = vec![];
+    let mut updates = get_all_values_and_paths(path, root)?
+        .into_iter()
+        .filter(|(v, _)| v.get_type() == SelectValueType::Array)
+        .map(|(v, p)| (opts.index(v.as_ref(), json_value), p))
+        .collect_vec();
+    if updates.is_empty() {
+        return Err(err_invalid_path_or("not an array"));
+    }
+    updates.sort_by(|(_, v1), (_, v2)| compare_paths_for_mutation(v1, v2));
+
+    let mut res = -1;
     let mut need_notify = false;
-    for p in paths {
-        res.push(match p {
-            Some(p) => {
-                need_notify = true;
-                (redis_key.arr_append(p, args.clone())? as i64).into()
-            }
-            _ => RedisValue::Null,
-        });
+    for (pos, p) in updates {
+        res = pos.map_or(-1, |pos| pos as i64);
+        if let Some(pos) = pos {
+            redis_key.arr_insert(p, std::slice::from_ref(&val), pos as i64)?;
+            need_notify = true;
+        }
     }
     if need_notify {
-        redis_key.notify_keyspace_event(ctx, "json.arrappend")?;
+        redis_key.notify_keyspace_event(ctx, "json.arrinsort")?;
         manager.apply_changes(ctx);
     }
     Ok(res.into())
 }
 
-pub enum FoundIndex {
-    Index(i64),
-    NotFound,
-    NotArray,
-}
-
-impl From<FoundIndex> for RedisValue {
-    fn from(e: FoundIndex) -> Self {
-        match e {
-            FoundIndex::NotFound => Self::Integer(-1),
-            FoundIndex::NotArray => Self::Null,
-            FoundIndex::Index(i) => Self::Integer(i),
-        }
-    }
-}
-
-pub enum ObjectLen {
-    Len(usize),
-    NoneExisting,
-    NotObject,
-}
-
 ///
-/// JSON.ARRINDEX <key> <path> <json-value> [start [stop]]
+/// JSON.ARRUNIQUE <key> <path> [BY relpath] [KEEP FIRST|LAST]
 ///
 #[macro_export]
-macro_rules! json_arrindex_command {
+macro_rules! json_arrunique_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.arrindex",
-                flags: [ReadOnly],
-                acl_categories: [Read, Single("json")],
-                arity: -4,
+                name: "json.arrunique",
+                flags: [Write],
+                acl_categories: [Write, Single("json")],
+                arity: -3,
                 complexity: "O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Search for the first occurrence of a JSON value in an array",
+                since: "8.8.0",
+                summary: "Remove duplicate elements from the array at path, keeping the order of the remaining elements",
                 key_spec: [
                     {
-                        flags: [ReadOnly],
+                        flags: [ReadWrite],
                         begin_search: Index({ index: 1 }),
                         find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
                     }
@@ -2111,22 +5594,38 @@ macro_rules! json_arrindex_command {
                         arg_type: String,
                     },
                     {
-                        name: "json-value",
-                        arg_type: String,
+                        name: "by",
+                        token: "BY",
+                        arg_type: Block,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "relpath",
+                                arg_type: String,
+                            }
+                        ]
                     },
                     {
-                        name: "range",
+                        name: "keep",
+                        token: "KEEP",
                         arg_type: Block,
                         flags: [Optional],
                         subargs: [
                             {
-                                name: "start",
-                                arg_type: Integer,
-                            },
-                            {
-                                name: "stop",
-                                arg_type: Integer,
-                                flags: [Optional],
+                                name: "which",
+                                arg_type: OneOf,
+                                subargs: [
+                                    {
+                                        name: "first",
+                                        arg_type: PureToken,
+                                        token: "FIRST",
+                                    },
+                                    {
+                                        name: "last",
+                                        arg_type: PureToken,
+                                        token: "LAST",
+                                    }
+                                ]
                             }
                         ]
                     }
@@ -2137,7 +5636,7 @@ macro_rules! json_arrindex_command {
     };
 }
 
-pub fn json_arr_index_command_impl<M: Manager>(
+pub fn json_arr_unique_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
@@ -2146,43 +5645,85 @@ pub fn json_arr_index_command_impl<M: Manager>(
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let value = args.next_str()?;
-    let start: i64 = args.next().map_or(Ok(0), |v| v.parse_integer())?;
-    let end: i64 = args.next().map_or(Ok(0), |v| v.parse_integer())?;
 
-    args.done()?; // TODO: Add to other functions as well to terminate args list
+    let mut by = None;
+    let mut keep_last = false;
+    while let Some(s) = args.next() {
+        match s.try_as_str()? {
+            arg if arg.eq_ignore_ascii_case("BY") && by.is_none() => {
+                by = Some(parse_relative_path(args.next_str()?)?);
+            }
+            arg if arg.eq_ignore_ascii_case("KEEP") => {
+                keep_last = match args.next_str()?.to_uppercase().as_str() {
+                    "FIRST" => false,
+                    "LAST" => true,
+                    _ => return Err(RedisError::Str("ERR syntax error")),
+                };
+            }
+            _ => return Err(RedisError::Str("ERR syntax error")),
+        };
+    }
+    let by = by.as_ref().map(|by| compile(by.get_path())).transpose()?;
+
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    let root = redis_key
+        .get_value()?
+        .ok_or_else(RedisError::nonexistent_key)?;
 
-    let key = manager.open_key_read(ctx, &key)?;
+    let values_and_paths = get_all_values_and_paths(path.get_path(), root)?;
+    let mut lens = vec![None; values_and_paths.len()];
+    let mut masks = vec![None; values_and_paths.len()];
+    let mut indexed = Vec::new();
+    for (i, (v, p)) in values_and_paths.into_iter().enumerate() {
+        if v.get_type() != SelectValueType::Array {
+            continue;
+        }
+        let keep = KeyValue::arr_unique_mask(v.as_ref(), by.as_ref(), keep_last);
+        lens[i] = Some(keep.iter().filter(|&&k| k).count() as i64);
+        if keep.contains(&false) {
+            masks[i] = Some(keep);
+            indexed.push((i, p));
+        }
+    }
+    if path.is_legacy() && lens.iter().all(Option::is_none) {
+        return Err(err_invalid_path_or("not an array"));
+    }
 
-    let json_value: Value = serde_json::from_str(value)?;
+    sort_paths_for_mutation(&mut indexed);
+    let need_notify = !indexed.is_empty();
+    for (orig_idx, p) in indexed {
+        if let Some(keep) = masks[orig_idx].take() {
+            redis_key.arr_retain(p, &keep)?;
+        }
+    }
 
-    key.get_value()?.map_or_else(
-        || Err(err_invalid_path()),
-        |doc| {
-            if path.is_legacy() {
-                KeyValue::new(doc).arr_index_legacy(path.get_path(), json_value, start, end)
-            } else {
-                KeyValue::new(doc).arr_index(path.get_path(), json_value, start, end)
-            }
-        },
-    )
+    if need_notify {
+        redis_key.notify_keyspace_event(ctx, "json.arrunique")?;
+        manager.apply_changes(ctx);
+    }
+    let reply = |len: Option<i64>| len.map_or(RedisValue::Null, RedisValue::Integer);
+    if path.is_legacy() {
+        Ok(reply(lens.into_iter().flatten().last()))
+    } else {
+        Ok(lens.into_iter().map(reply).collect_vec().into())
+    }
 }
 
 ///
-/// JSON.ARRINSERT <key> <path> <index> <json> [json ...]
+/// JSON.ARRUPSERT <key> <path> <idfield> <json>
 ///
 #[macro_export]
-macro_rules! json_arrinsert_command {
+macro_rules! json_arrupsert_command {
     ($item:item) => {
         #[::redis_module_macros::command(
             {
-                name: "json.arrinsert",
+                name: "json.arrupsert",
                 flags: [Write, DenyOOM],
                 acl_categories: [Write, Single("json")],
-                arity: -5,
+                arity: 5,
                 complexity: "O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
-                since: "1.0.0",
-                summary: "Insert the json values into the array at path before the index (shifts to the right)",
+                since: "8.8.0",
+                summary: "Merge the json value into the element of the array at path with the same id field, or append it if there is none",
                 key_spec: [
                     {
                         flags: [ReadWrite],
@@ -2201,13 +5742,12 @@ macro_rules! json_arrinsert_command {
                         arg_type: String,
                     },
                     {
-                        name: "index",
-                        arg_type: Integer,
+                        name: "idfield",
+                        arg_type: String,
                     },
                     {
                         name: "json",
                         arg_type: String,
-                        flags: [Multiple],
                     }
                 ]
             }
@@ -2216,92 +5756,79 @@ macro_rules! json_arrinsert_command {
     };
 }
 
-pub fn json_arr_insert_command_impl<M: Manager>(
+/// Replies with `updated` or `inserted` for each matched array, depending on whether an element
+/// with the same id was merged into or the value was appended
+pub fn json_arr_upsert_command_impl<M: Manager>(
     manager: M,
     ctx: &Context,
     args: Vec<RedisString>,
 ) -> RedisResult {
-    let mut args = args.into_iter().skip(1).peekable();
+    let mut args = args.into_iter().skip(1);
 
     let key = args.next_arg()?;
     let path = Path::new(args.next_str()?);
-    let index = args.next_i64()?;
+    let idfield = parse_relative_path(args.next_str()?)?;
+    let value = args.next_str()?;
+    args.done()?;
 
-    // We require at least one JSON item to insert
-    args.peek().ok_or(RedisError::WrongArity)?;
-    let args =
-        args.try_fold::<_, _, RedisResult<_>>(Vec::with_capacity(args.len()), |mut acc, arg| {
-            let json = arg.try_as_str()?;
-            acc.push(manager.from_str(json, Format::JSON, true, None)?);
-            Ok(acc)
-        })?;
-    let mut redis_key = manager.open_key_write(ctx, key)?;
-    if path.is_legacy() {
-        json_arr_insert_legacy(manager, &mut redis_key, ctx, path.get_path(), index, args)
-    } else {
-        json_arr_insert_impl(manager, &mut redis_key, ctx, path.get_path(), index, args)
-    }
-}
+    let json_value: Value = serde_json::from_str(value)?;
+    let idfield = compile(idfield.get_path())?;
+    let id = create(&idfield)
+        .calc(&json_value)
+        .into_iter()
+        .next()
+        .map(|id| id.inner_cloned())
+        .ok_or(RedisError::Str("ERR value does not contain the id field"))?;
+    let val = manager.from_str(value, Format::JSON, true, None)?;
+    let value_depth = json_value.calculate_value_depth();
 
-fn json_arr_insert_impl<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    index: i64,
-    args: Vec<M::O>,
-) -> RedisResult {
+    let mut redis_key = manager.open_key_write(ctx, key)?;
     let root = redis_key
         .get_value()?
         .ok_or_else(RedisError::nonexistent_key)?;
 
-    let paths = find_all_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
-
-    let mut res = vec![RedisValue::Null; paths.len()];
-    let mut need_notify = false;
-
-    let mut indexed: Vec<(usize, Vec<String>)> = paths
-        .into_iter()
-        .enumerate()
-        .filter_map(|(i, p)| p.map(|path| (i, path)))
-        .collect();
+    let values_and_paths = get_all_values_and_paths(path.get_path(), root)?;
+    let mut res = vec![None; values_and_paths.len()];
+    let mut positions = vec![None; values_and_paths.len()];
+    let mut indexed = Vec::new();
+    for (i, (v, p)) in values_and_paths.into_iter().enumerate() {
+        if v.get_type() == SelectValueType::Array {
+            // Nothing is written unless the value fits in every array, merged or appended
+            if p.len() + 1 + value_depth >= MAX_DEPTH {
+                return Err(err_recursion_limit_exceeded());
+            }
+            positions[i] = KeyValue::arr_position_by(v.as_ref(), &idfield, &id);
+            indexed.push((i, p));
+        }
+    }
+    if path.is_legacy() && indexed.is_empty() {
+        return Err(err_invalid_path_or("not an array"));
+    }
     sort_paths_for_mutation(&mut indexed);
 
-    for (orig_idx, p) in indexed {
-        need_notify = true;
-        res[orig_idx] = (redis_key.arr_insert(p, &args, index)? as i64).into();
+    let need_notify = !indexed.is_empty();
+    for (orig_idx, mut p) in indexed {
+        res[orig_idx] = if let Some(pos) = positions[orig_idx] {
+            p.push(pos.to_string());
+            redis_key.merge_value(p, val.clone())?;
+            Some("updated")
+        } else {
+            redis_key.arr_append(p, vec![val.clone()])?;
+            Some("inserted")
+        };
     }
 
     if need_notify {
-        redis_key.notify_keyspace_event(ctx, "json.arrinsert")?;
+        redis_key.notify_keyspace_event(ctx, "json.arrupsert")?;
         manager.apply_changes(ctx);
     }
-    Ok(res.into())
-}
-
-fn json_arr_insert_legacy<M: Manager>(
-    manager: M,
-    redis_key: &mut M::WriteHolder,
-    ctx: &Context,
-    path: &str,
-    index: i64,
-    args: Vec<M::O>,
-) -> RedisResult {
-    let root = redis_key
-        .get_value()?
-        .ok_or_else(RedisError::nonexistent_key)?;
-
-    let mut paths = find_paths(path, root, |v| v.get_type() == SelectValueType::Array)?;
-    if paths.is_empty() {
-        return Err(err_invalid_path_or("not an array"));
+    let reply =
+        |r: Option<&str>| r.map_or(RedisValue::Null, |s| RedisValue::SimpleString(s.into()));
+    if path.is_legacy() {
+        Ok(reply(res.into_iter().flatten().last()))
+    } else {
+        Ok(res.into_iter().map(reply).collect_vec().into())
     }
-    paths.sort_by(|v1, v2| compare_paths_for_mutation(v1, v2));
-    let res = paths
-        .into_iter()
-        .try_fold(0, |_, p| redis_key.arr_insert(p, &args, index))?;
-    redis_key.notify_keyspace_event(ctx, "json.arrinsert")?;
-    manager.apply_changes(ctx);
-    Ok(res.into())
 }
 
 ///
@@ -2606,20 +6133,410 @@ fn json_arr_pop_legacy<M: Manager>(
     if !paths.is_empty() {
         paths.sort_by(|v1, v2| compare_paths_for_mutation(v1, v2));
         let mut res = Ok(().into());
+        let mut need_notify = false;
         for p in paths {
             res = Ok(redis_key.arr_pop(p, index, |v| match v {
-                Some(r) => Ok(serde_json::to_string(&r)?.into()),
+                Some(r) => {
+                    need_notify = true;
+                    Ok(serde_json::to_string(&r)?.into())
+                }
                 None => Ok(().into()),
             })?);
         }
-        redis_key.notify_keyspace_event(ctx, "json.arrpop")?;
-        manager.apply_changes(ctx);
+        if need_notify {
+            redis_key.notify_keyspace_event(ctx, "json.arrpop")?;
+            manager.apply_changes(ctx);
+        }
         res
     } else {
         Err(err_invalid_path_or("not an array"))
     }
 }
 
+///
+/// JSON.ARRBPOP <key> [key ...] <path> [LEFT | RIGHT] <timeout>
+///
+#[macro_export]
+macro_rules! json_arrbpop_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.arrbpop",
+                flags: [Write, Blocking, GetkeysApi],
+                acl_categories: [Write, Single("json")],
+                arity: -4,
+                complexity: "O(N) where N is the number of provided keys, plus the complexity of JSON.ARRPOP on the array an element is popped from",
+                since: "8.8.0",
+                summary: "Remove and return an element from the first non-empty array at path in the given keys, or block until one is available",
+                key_spec: [
+                    {
+                        flags: [ReadWrite, Incomplete],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: -3, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                        flags: [Multiple],
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "where",
+                        arg_type: OneOf,
+                        flags: [Optional],
+                        subargs: [
+                            {
+                                name: "left",
+                                arg_type: PureToken,
+                                token: "LEFT",
+                            },
+                            {
+                                name: "right",
+                                arg_type: PureToken,
+                                token: "RIGHT",
+                            }
+                        ]
+                    },
+                    {
+                        name: "timeout",
+                        arg_type: Double,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+/// Parses `<key> [key ...] <path> [LEFT | RIGHT] <timeout>` into the keys, the path,
+/// the index to pop at and the timeout in milliseconds (0 blocks forever).
+///
+/// `LEFT`/`RIGHT` is only taken as the side when at least a key and a path precede it,
+/// so a single key named `LEFT` still parses as `<key> <path> <timeout>`.
+fn parse_arr_bpop_args(args: &[RedisString]) -> RedisResult<(&[RedisString], Path<'_>, i64, i64)> {
+    let args = args.get(1..).unwrap_or_default();
+    let (timeout, args) = args.split_last().ok_or(RedisError::WrongArity)?;
+    let (index, args) = match args.split_last() {
+        Some((side, rest)) if rest.len() >= 2 => match side.try_as_str()?.to_uppercase().as_str() {
+            "LEFT" => (0, rest),
+            "RIGHT" => (-1, rest),
+            _ => (-1, args),
+        },
+        _ => (-1, args),
+    };
+    let (path, keys) = args.split_last().ok_or(RedisError::WrongArity)?;
+    if keys.is_empty() {
+        return Err(RedisError::WrongArity);
+    }
+
+    Ok((
+        keys,
+        Path::new(path.try_as_str()?),
+        index,
+        parse_timeout(timeout)?,
+    ))
+}
+
+/// Parses a timeout in seconds into milliseconds, 0 blocking forever
+fn parse_timeout(timeout: &RedisString) -> RedisResult<i64> {
+    let timeout = timeout
+        .try_as_str()?
+        .parse::<f64>()
+        .ok()
+        .filter(|t| t.is_finite())
+        .ok_or(RedisError::Str(
+            "ERR timeout is not a float or out of range",
+        ))?;
+    if timeout < 0.0 {
+        return Err(RedisError::Str("ERR timeout is negative"));
+    }
+    // Round up so a sub-millisecond timeout does not turn into 0 (block forever)
+    Ok((timeout * 1000.0).ceil() as i64)
+}
+
+/// Pops from the first non-empty array matched by `path`, trying the keys in order.
+/// Returns `None` when none of the keys holds such an array.
+fn json_arr_bpop_try<M: Manager>(
+    manager: &M,
+    ctx: &Context,
+    keys: &[RedisString],
+    path: &Path,
+    index: i64,
+) -> RedisResult<Option<RedisValue>> {
+    for key in keys {
+        let mut redis_key = manager.open_key_write(ctx, key.clone())?;
+        let Some(root) = redis_key.get_value()? else {
+            continue;
+        };
+        let Some(p) = find_paths(path.get_path(), root, |v| {
+            v.get_type() == SelectValueType::Array && v.len().is_some_and(|n| n > 0)
+        })?
+        .into_iter()
+        .next() else {
+            continue;
+        };
+        let normalized = normalized_path(Some(&*root), &p);
+
+        let popped = redis_key.arr_pop(p, index, |v| match v {
+            Some(r) => Ok(serde_json::to_string(&r)?.into()),
+            None => Ok(RedisValue::Null),
+        })?;
+        redis_key.notify_keyspace_event(ctx, "json.arrpop")?;
+        // Same as BLPOP is propagated as LPOP, replicas pop from the array that was popped
+        manager.apply_changes_as(
+            ctx,
+            "JSON.ARRPOP",
+            &[
+                key.as_slice(),
+                normalized.as_bytes(),
+                index.to_string().as_bytes(),
+            ],
+        );
+        return Ok(Some(
+            vec![RedisValue::BulkString(key.to_string_lossy()), popped].into(),
+        ));
+    }
+    Ok(None)
+}
+
+/// Timeout callback of a client blocked by JSON.ARRBPOP or JSON.WAIT, replies with a null
+/// like `BLPOP`
+extern "C" fn blocked_client_timeout(
+    ctx: *mut raw::RedisModuleCtx,
+    _argv: *mut *mut raw::RedisModuleString,
+    _argc: c_int,
+) -> c_int {
+    Context::new(ctx).reply(Ok(RedisValue::Null)) as c_int
+}
+
+/// `on_keys` is invoked with the original arguments whenever one of the keys is
+/// signaled as ready (see `WriteHolder::notify_keyspace_event`), and is expected to call
+/// [`json_arr_bpop_on_keys_impl`] with the manager the command ran with.
+pub fn json_arr_bpop_command_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+    on_keys: raw::RedisModuleCmdFunc,
+) -> RedisResult {
+    if ctx.is_keys_position_request() {
+        // The optional side moves the path, which a key spec can't tell from a key
+        if let Ok((keys, ..)) = parse_arr_bpop_args(&args) {
+            (1..=keys.len()).for_each(|pos| ctx.key_at_pos(pos as i32));
+        }
+        return Ok(RedisValue::NoReply);
+    }
+    let (keys, path, index, timeout_ms) = parse_arr_bpop_args(&args)?;
+
+    if let Some(res) = json_arr_bpop_try(&manager, ctx, keys, &path, index)? {
+        return Ok(res);
+    }
+
+    // Same as BLPOP, a client that is not allowed to block gets a null reply right away
+    if ctx
+        .get_flags()
+        .intersects(ContextFlags::MULTI | ContextFlags::LUA | ContextFlags::DENY_BLOCKING)
+    {
+        return Ok(RedisValue::Null);
+    }
+
+    let mut raw_keys = keys.iter().map(|k| k.inner).collect_vec();
+    unsafe {
+        raw::RedisModule_BlockClientOnKeys.unwrap()(
+            ctx.ctx,
+            on_keys,
+            Some(blocked_client_timeout),
+            None,
+            timeout_ms,
+            raw_keys.as_mut_ptr(),
+            raw_keys.len() as c_int,
+            null_mut(),
+        );
+    }
+    Ok(RedisValue::NoReply)
+}
+
+/// Serves a client blocked by JSON.ARRBPOP once one of its keys was signaled as ready.
+/// Returns `None` if there is still nothing to pop, in which case the client stays blocked.
+pub fn json_arr_bpop_on_keys_impl<M: Manager>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> Option<RedisResult> {
+    let (keys, path, index, _) = match parse_arr_bpop_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(Err(e)),
+    };
+    json_arr_bpop_try(&manager, ctx, keys, &path, index).transpose()
+}
+
+///
+/// JSON.WAIT <key> <path> [IFNE json] <timeout>
+///
+#[macro_export]
+macro_rules! json_wait_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+            {
+                name: "json.wait",
+                flags: [ReadOnly, Blocking],
+                acl_categories: [Read, Single("json")],
+                arity: -4,
+                complexity: "O(N) when path is evaluated to a single value where N is the size of the value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+                since: "8.8.0",
+                summary: "Block until a write touches the value at path, or until it differs from the given JSON, and return the new value",
+                key_spec: [
+                    {
+                        flags: [ReadOnly, Access],
+                        begin_search: Index({ index: 1 }),
+                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                    }
+                ],
+                args: [
+                    {
+                        name: "key",
+                        arg_type: Key,
+                        key_spec_index: 0,
+                    },
+                    {
+                        name: "path",
+                        arg_type: String,
+                    },
+                    {
+                        name: "json",
+                        arg_type: String,
+                        token: "IFNE",
+                        flags: [Optional],
+                    },
+                    {
+                        name: "timeout",
+                        arg_type: Double,
+                    }
+                ]
+            }
+        )]
+        $item
+    };
+}
+
+/// Parses `<key> <path> [IFNE json] <timeout>` into the key, the path, the JSON to compare
+/// the value with and the timeout in milliseconds (0 blocks forever).
+fn parse_wait_args(
+    args: &[RedisString],
+) -> RedisResult<(&RedisString, Path<'_>, Option<IValue>, i64)> {
+    let (key, path, ifne, timeout) = match args {
+        [_, key, path, timeout] => (key, path, None, timeout),
+        [_, key, path, token, json, timeout]
+            if token.try_as_str()?.eq_ignore_ascii_case("IFNE") =>
+        {
+            (
+                key,
+                path,
+                Some(serde_json::from_str(json.try_as_str()?)?),
+                timeout,
+            )
+        }
+        [_, _, _, _, _, _] => return Err(RedisError::Str("ERR syntax error")),
+        _ => return Err(RedisError::WrongArity),
+    };
+    Ok((
+        key,
+        Path::new(path.try_as_str()?),
+        ifne,
+        parse_timeout(timeout)?,
+    ))
+}
+
+/// The reply of JSON.WAIT, the value at `path` as JSON.GET has it
+fn json_wait_reply<V: SelectValue>(ctx: &Context, doc: Option<&V>, path: Path) -> RedisResult {
+    let format_options = ReplyFormatOptions::new(is_resp3(ctx), ReplyFormat::STRING);
+    match doc {
+        Some(doc) => KeyValue::new(doc).to_json(vec![path], &format_options),
+        None => Ok(RedisValue::Null),
+    }
+}
+
+/// Replies right away when the value at `path` already differs from the `IFNE` JSON, and
+/// blocks otherwise until a write touches it. `on_keys` is invoked with the original
+/// arguments whenever the key is signaled as ready (see `wait::on_write`), and is expected
+/// to call [`json_wait_on_keys_impl`] with the manager the command ran with.
+pub fn json_wait_command_impl<M: Manager<V = IValue>>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+    on_keys: raw::RedisModuleCmdFunc,
+) -> RedisResult {
+    let (key, path, ifne, timeout_ms) = parse_wait_args(&args)?;
+
+    let redis_key = manager.open_key_read(ctx, key)?;
+    let doc = redis_key.get_value()?;
+    let values = wait::values_at(doc, path.get_path())?;
+    if ifne.is_some_and(|json| wait::differ(&values, &json)) {
+        return json_wait_reply(ctx, doc, path);
+    }
+
+    // Same as BLPOP, a client that is not allowed to block gets a null reply right away
+    if ctx
+        .get_flags()
+        .intersects(ContextFlags::MULTI | ContextFlags::LUA | ContextFlags::DENY_BLOCKING)
+    {
+        return Ok(RedisValue::Null);
+    }
+
+    let privdata = wait::register(ctx, key, path.get_path(), doc)?;
+    let mut raw_key = key.inner;
+    unsafe {
+        // Deleting the key is a write to the path as well
+        raw::RedisModule_BlockClientOnKeysWithFlags.unwrap()(
+            ctx.ctx,
+            on_keys,
+            Some(blocked_client_timeout),
+            Some(wait::free_waiter),
+            timeout_ms,
+            &mut raw_key,
+            1,
+            privdata,
+            raw::REDISMODULE_BLOCK_UNBLOCK_DELETED as c_int,
+        );
+    }
+    Ok(RedisValue::NoReply)
+}
+
+/// Serves a client blocked by JSON.WAIT once its key was signaled as ready. Returns `None`
+/// if no write touched the value at its path, in which case the client stays blocked.
+pub fn json_wait_on_keys_impl<M: Manager<V = IValue>>(
+    manager: M,
+    ctx: &Context,
+    args: Vec<RedisString>,
+) -> Option<RedisResult> {
+    let (key, path, ifne, _) = match parse_wait_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(Err(e)),
+    };
+    let redis_key = match manager.open_key_read(ctx, key) {
+        Ok(redis_key) => redis_key,
+        Err(e) => return Some(Err(e)),
+    };
+    let doc = match redis_key.get_value() {
+        Ok(doc) => doc,
+        Err(e) => return Some(Err(e)),
+    };
+    // The key may also be signaled by writes to other paths, or for JSON.ARRBPOP. Once it is
+    // deleted, the values are gone, which is a difference as well.
+    let served = match ifne {
+        Some(json) => {
+            wait::values_at(doc, path.get_path()).is_ok_and(|values| wait::differ(&values, &json))
+        }
+        None => doc.is_none() || wait::is_woken(ctx),
+    };
+    served.then(|| json_wait_reply(ctx, doc, path))
+}
+
 ///
 /// JSON.ARRTRIM <key> <path> <start> <stop>
 ///
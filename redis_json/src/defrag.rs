@@ -78,7 +78,7 @@ pub unsafe extern "C" fn defrag(
     }
     std::ptr::write(
         &mut (**value).data as *mut ijson::IValue,
-        std::ptr::read(*value).data.defrag(&mut defrag_allocator),
+        std::ptr::read(&(**value).data).defrag(&mut defrag_allocator),
     );
     0
 }
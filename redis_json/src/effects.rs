@@ -0,0 +1,372 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Effect replication of JSON writes.
+//!
+//! With the `REPLICATE_EFFECTS yes` module argument, a write is replicated to replicas and the
+//! AOF as the operations it made at concrete paths rather than as the command that made it, so
+//! they apply it without evaluating its paths again:
+//!
+//! `_JSON.PATCH <key> <event> <SET | REPLACE | DEL | INSERT | APPEND | PERSIST> <path> [value] ...`
+//!
+//! `path` is the JSON array of the member names and array indices leading to the value, and
+//! `value` the value written there, in the binary encoding of RDB. `SET` forgets the expiry of
+//! the members it replaces, as JSON.SET does, where `REPLACE` keeps it, as JSON.NUMINCRBY does.
+//! `PERSIST` forgets the expiry of the members at and below `path` that a `REPLACE` replaced
+//! anyway, e.g. the members JSON.CLEAR emptied.
+//! The writes of a few elements of an array or of the end of a string don't ship the whole of
+//! it: `DEL` of an element removes it, `INSERT` inserts the elements of the array `value` from
+//! the index ending `path`, and `APPEND` appends the string `value` to the string at `path`.
+//! The replica notifies `event`, the event of the command. `_JSON.PATCH` is rejected from any
+//! other client than the master or the AOF, and a command one of whose writes couldn't be
+//! recorded is replicated as is.
+
+use crate::ivalue_manager::{with_value_at, IValueKeyHolderWrite, RedisIValueJsonKeyManager};
+use crate::manager::{Manager, WriteHolder};
+use ijson::IValue;
+use itertools::Itertools;
+use json_path::select_value::SelectValue;
+use redis_module::{
+    Context, ContextFlags, NextArg, RedisError, RedisResult, RedisString, RedisValue, REDIS_OK,
+};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const PATCH_COMMAND: &str = "_JSON.PATCH";
+
+static REPLICATE_EFFECTS: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the replication of the effects of the writes
+pub fn set_replicate_effects(enabled: bool) {
+    REPLICATE_EFFECTS.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// The value was written, forgetting the expiry of the members it replaced
+    Set,
+    /// The value was written in place, keeping the expiry of its members
+    Replace,
+    /// The value was deleted
+    Del,
+    /// The array had `count` elements inserted from `index` on
+    Insert { index: usize, count: usize },
+    /// The array had its element at `index` removed
+    Remove { index: usize },
+    /// The string had `len` bytes appended
+    Append { len: usize },
+}
+
+impl Op {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Set => "SET",
+            Self::Replace => "REPLACE",
+            Self::Del | Self::Remove { .. } => "DEL",
+            Self::Insert { .. } => "INSERT",
+            Self::Append { .. } => "APPEND",
+        }
+    }
+}
+
+/// The arguments of the patches a command wrote, until they are replicated along with it
+#[derive(Clone, Default)]
+pub struct Patches(Rc<RefCell<PendingPatches>>);
+
+#[derive(Default)]
+struct PendingPatches {
+    args: Vec<Vec<Vec<u8>>>,
+    /// Whether a write couldn't be recorded, so the command is to be replicated as is
+    verbatim: bool,
+}
+
+impl Patches {
+    /// Replicates the patches written so far, returning whether there were any. There are none
+    /// when a write couldn't be recorded, as the command is then replicated as is
+    pub fn replicate(&self, ctx: &Context) -> bool {
+        let pending = std::mem::take(&mut *self.0.borrow_mut());
+        if pending.verbatim {
+            return false;
+        }
+        for args in &pending.args {
+            ctx.replicate(
+                PATCH_COMMAND,
+                args.iter().map(Vec::as_slice).collect_vec().as_slice(),
+            );
+        }
+        !pending.args.is_empty()
+    }
+}
+
+/// The effects of the writes to one key, until they are notified
+pub struct Effects {
+    ops: Vec<Vec<u8>>,
+    patches: Patches,
+}
+
+/// Starts recording the effects of the writes into `patches`, if they are to be replicated
+pub fn capture(ctx: &Context, patches: &Patches) -> Option<Effects> {
+    // A replica applies the effects of its master without replicating them
+    if !REPLICATE_EFFECTS.load(Ordering::Relaxed)
+        || ctx
+            .get_flags()
+            .intersects(ContextFlags::REPLICATED | ContextFlags::LOADING)
+    {
+        return None;
+    }
+    Some(Effects {
+        ops: Vec::new(),
+        patches: patches.clone(),
+    })
+}
+
+impl Effects {
+    /// Records that `op` wrote `path` of what is now `doc`
+    pub fn record(&mut self, doc: Option<&IValue>, path: &[String], op: Op) {
+        let encode_at = |f: &dyn Fn(&IValue) -> Option<IValue>| {
+            doc.and_then(|doc| with_value_at(doc, path, |v| f(v).map(|v| ijson::encode(&v))))
+                .flatten()
+        };
+        // The elements are the index ending the path, and the other values are whole
+        let (index, value) = match op {
+            Op::Del => (None, None),
+            Op::Remove { index } => (Some(index), None),
+            Op::Set | Op::Replace => {
+                let value = doc.and_then(|doc| with_value_at(doc, path, ijson::encode));
+                (None, Some(value))
+            }
+            Op::Insert { index, count } => {
+                let elements = encode_at(&|v| {
+                    let elements = (index..index + count)
+                        .map(|i| v.get_index(i).map(|e| e.inner_cloned()))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(elements.into())
+                });
+                (Some(index), Some(elements))
+            }
+            Op::Append { len } => {
+                let appended = encode_at(&|v| {
+                    let s = v.as_str()?;
+                    Some(s.get(s.len().checked_sub(len)?..)?.into())
+                });
+                (None, Some(appended))
+            }
+        };
+        let path = path.iter().cloned().chain(index.map(|i| i.to_string()));
+        let path_arg = serde_json::to_vec(&path.collect_vec()).unwrap_or_default();
+        match value {
+            None => self.ops.extend([op.name().as_bytes().to_vec(), path_arg]),
+            Some(Some(value)) => {
+                self.ops
+                    .extend([op.name().as_bytes().to_vec(), path_arg, value]);
+            }
+            // The value written can't be found, e.g. as it is gone along with the key
+            Some(None) => self.patches.0.borrow_mut().verbatim = true,
+        }
+    }
+
+    /// Records that the write forgot the expiry of the members at and below `path`
+    pub fn record_persist(&mut self, path: &[String]) {
+        let path_arg = serde_json::to_vec(path).unwrap_or_default();
+        self.ops.extend([b"PERSIST".to_vec(), path_arg]);
+    }
+
+    /// Makes the effects recorded so far the patch of `key` notified as `event`, replicated
+    /// along with the command
+    pub fn flush(&mut self, event: &str, key: &RedisString) {
+        if self.ops.is_empty() {
+            return;
+        }
+        let args = [key.as_slice().to_vec(), event.as_bytes().to_vec()]
+            .into_iter()
+            .chain(self.ops.drain(..))
+            .collect_vec();
+        self.patches.0.borrow_mut().args.push(args);
+    }
+}
+
+///
+/// _JSON.PATCH <key> <event> <SET | REPLACE | DEL | INSERT | APPEND | PERSIST> <path> [value] [...]
+///
+#[macro_export]
+macro_rules! json_patch_command {
+    ($item:item) => {
+        #[::redis_module_macros::command(
+                            {
+                                name: "_json.patch",
+                                flags: [Write, DenyOOM],
+                                acl_categories: [Admin, Dangerous],
+                                arity: -5,
+                                complexity: "O(N) where N is the size of the values written",
+                                since: "8.8.0",
+                                summary: "Apply the effects of a write replicated by the master",
+                                key_spec: [
+                                    {
+                                        flags: [ReadWrite],
+                                        begin_search: Index({ index: 1 }),
+                                        find_keys: Range({ last_key: 0, steps: 1, limit: 0 }),
+                                    }
+                                ],
+                                args: [
+                                    {
+                                        name: "key",
+                                        arg_type: Key,
+                                        key_spec_index: 0,
+                                    },
+                                    {
+                                        name: "event",
+                                        arg_type: String,
+                                    },
+                                    {
+                                        name: "effect",
+                                        arg_type: Block,
+                                        flags: [Multiple],
+                                        subargs: [
+                                            {
+                                                name: "op",
+                                                arg_type: OneOf,
+                                                subargs: [
+                                                    {
+                                                        name: "set",
+                                                        arg_type: PureToken,
+                                                        token: "SET",
+                                                    },
+                                                    {
+                                                        name: "replace",
+                                                        arg_type: PureToken,
+                                                        token: "REPLACE",
+                                                    },
+                                                    {
+                                                        name: "del",
+                                                        arg_type: PureToken,
+                                                        token: "DEL",
+                                                    },
+                                                    {
+                                                        name: "insert",
+                                                        arg_type: PureToken,
+                                                        token: "INSERT",
+                                                    },
+                                                    {
+                                                        name: "append",
+                                                        arg_type: PureToken,
+                                                        token: "APPEND",
+                                                    },
+                                                    {
+                                                        name: "persist",
+                                                        arg_type: PureToken,
+                                                        token: "PERSIST",
+                                                    }
+                                                ]
+                                            },
+                                            {
+                                                name: "path",
+                                                arg_type: String,
+                                            },
+                                            {
+                                                name: "value",
+                                                arg_type: String,
+                                                flags: [Optional],
+                                            }
+                                        ]
+                                    }
+                                ]
+                            }
+                        )]
+        $item
+    };
+}
+
+pub fn json_patch_command_impl(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+    // Only the replication stream of the master and the AOF carry patches
+    if !ctx
+        .get_flags()
+        .intersects(ContextFlags::REPLICATED | ContextFlags::LOADING)
+    {
+        return Err(RedisError::Str(
+            "ERR _JSON.PATCH is only applied from the replication stream or the AOF",
+        ));
+    }
+    let mut args = args.into_iter().skip(1);
+    let key = args.next_arg()?;
+    let event = args.next_str()?.to_string();
+
+    let manager = RedisIValueJsonKeyManager {
+        phantom: PhantomData,
+        patches: Patches::default(),
+    };
+    let mut redis_key = manager.open_key_write(ctx, key)?;
+    while let Some(op) = args.next() {
+        let path = serde_json::from_slice::<Vec<String>>(args.next_arg()?.as_slice())
+            .map_err(|_| RedisError::Str("ERR invalid patch path"))?;
+        match op.try_as_str()?.to_ascii_uppercase().as_str() {
+            "SET" => set(&mut redis_key, path, decode(&args.next_arg()?)?)?,
+            "REPLACE" => {
+                redis_key.replace_value(path, decode(&args.next_arg()?)?)?;
+            }
+            "DEL" if path.is_empty() => redis_key.delete()?,
+            "DEL" => {
+                redis_key.delete_path(path)?;
+            }
+            "INSERT" => insert(&mut redis_key, path, decode(&args.next_arg()?)?)?,
+            "APPEND" => {
+                let s = decode(&args.next_arg()?)?;
+                redis_key.str_append(path, serde_json::to_string(&s)?)?;
+            }
+            "PERSIST" => redis_key.clear_member_expiry(&path)?,
+            _ => return Err(RedisError::Str("ERR invalid patch operation")),
+        }
+    }
+    redis_key.notify_keyspace_event(ctx, &event)?;
+    REDIS_OK
+}
+
+fn decode(value: &RedisString) -> RedisResult<IValue> {
+    ijson::decode(value.as_slice()).map_err(|e| RedisError::String(format!("ERR {e}")))
+}
+
+/// Inserts the elements of the array `elements` from the index ending `path`
+fn insert(
+    redis_key: &mut IValueKeyHolderWrite,
+    mut path: Vec<String>,
+    elements: IValue,
+) -> RedisResult<()> {
+    let index = path
+        .pop()
+        .and_then(|index| index.parse().ok())
+        .ok_or(RedisError::Str("ERR invalid patch path"))?;
+    let elements = elements
+        .values()
+        .ok_or(RedisError::Str("ERR invalid patch value"))?
+        .map(|e| e.inner_cloned())
+        .collect_vec();
+    redis_key.arr_insert(path, &elements, index)?;
+    Ok(())
+}
+
+/// Writes `v` at `path` as JSON.SET does, adding it to its object when it is missing
+fn set(redis_key: &mut IValueKeyHolderWrite, mut path: Vec<String>, v: IValue) -> RedisResult<()> {
+    let exists = redis_key
+        .get_value()?
+        .is_some_and(|doc| with_value_at(doc, &path, |_| ()).is_some());
+    match path.pop() {
+        Some(member) if !exists => {
+            redis_key.dict_add(path, &member, v)?;
+        }
+        Some(member) => {
+            path.push(member);
+            redis_key.set_value(path, v)?;
+        }
+        None => {
+            redis_key.set_value(path, v)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,335 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Expiry of object members, set by JSON.EXPIRE.
+//!
+//! The expiration times are stored alongside the document in [`RedisJSON`], keyed by the path
+//! of the member. Expired members are hidden from the reads, and deleted when their key is opened
+//! for writing, or by a cycle actively going over the keys known to have member expiries. The
+//! deletions are a write of their own, notified as a `json.expired` keyspace event and
+//! replicated as JSON.DEL of the members, or as their effects.
+//!
+//! The expiration times are saved in the RDB as auxiliary data following the keyspace, so the
+//! documents keep encoding version 4.
+
+use crate::c_api::get_llapi_ctx;
+use crate::ivalue_manager::RedisIValueJsonKeyManager;
+use crate::manager::{Manager, WriteHolder};
+use crate::redisjson::RedisJSON;
+use crate::REDIS_JSON_TYPE;
+use ijson::IValue;
+use redis_module::raw::{self, RedisModuleIO};
+use redis_module::{Context, NotifyEvent, RedisResult, RedisString};
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+use std::ops::Bound;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the active expiry cycle runs
+const ACTIVE_CYCLE_PERIOD: Duration = Duration::from_millis(100);
+/// How many keys the active expiry cycle looks at on each run
+const ACTIVE_CYCLE_KEYS: usize = 20;
+
+/// The expiration times, in milliseconds since the Unix epoch, of the object members of a
+/// document, by member path
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemberExpiry {
+    members: BTreeMap<Vec<String>, i64>,
+}
+
+impl MemberExpiry {
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, path: &[String]) -> Option<i64> {
+        self.members.get(path).copied()
+    }
+
+    pub fn set(&mut self, path: Vec<String>, at: i64) {
+        self.members.insert(path, at);
+    }
+
+    /// The earliest expiration time
+    pub fn next_expiry(&self) -> Option<i64> {
+        self.members.values().min().copied()
+    }
+
+    pub fn remove(&mut self, path: &[String]) -> bool {
+        self.members.remove(path).is_some()
+    }
+
+    /// Forgets the expiry of the member at `path` and of the members below it, as they are
+    /// replaced or deleted, returning whether there were any
+    pub fn clear_below(&mut self, path: &[String]) -> bool {
+        if path.is_empty() {
+            let cleared = !self.members.is_empty();
+            self.members.clear();
+            return cleared;
+        }
+        let below = self
+            .members
+            .range(path.to_vec()..)
+            .map(|(member, _)| member)
+            .take_while(|member| member.starts_with(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for member in &below {
+            self.members.remove(member);
+        }
+        !below.is_empty()
+    }
+
+    /// Removes and returns the paths of the members expired at `now`
+    pub fn take_expired(&mut self, now: i64) -> Vec<Vec<String>> {
+        let expired = self
+            .members
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(member, _)| member.clone())
+            .collect::<Vec<_>>();
+        for member in &expired {
+            self.members.remove(member);
+        }
+        expired
+    }
+
+    pub fn rdb_save(&self, rdb: *mut RedisModuleIO) {
+        raw::save_unsigned(rdb, self.members.len() as u64);
+        for (member, at) in &self.members {
+            raw::save_unsigned(rdb, member.len() as u64);
+            for element in member {
+                raw::save_slice(rdb, element.as_bytes());
+            }
+            raw::save_signed(rdb, *at);
+        }
+    }
+
+    pub fn rdb_load(rdb: *mut RedisModuleIO) -> RedisResult<Self> {
+        let mut members = BTreeMap::new();
+        for _ in 0..raw::load_unsigned(rdb)? {
+            let member = (0..raw::load_unsigned(rdb)?)
+                .map(|_| Ok(raw::load_string_buffer(rdb)?.to_string()?))
+                .collect::<RedisResult<Vec<_>>>()?;
+            members.insert(member, raw::load_signed(rdb)?);
+        }
+        Ok(Self { members })
+    }
+}
+
+/// The current time, in milliseconds since the Unix epoch
+pub fn now_ms() -> i64 {
+    unsafe { raw::RedisModule_Milliseconds.unwrap()() }
+}
+
+/// The keys, with their database, known to have member expiries
+static EXPIRING_KEYS: Mutex<BTreeSet<(c_int, Vec<u8>)>> = Mutex::new(BTreeSet::new());
+
+/// Lets the active expiry cycle know that `key`, in the selected database, has member expiries
+pub fn track_key(ctx: &Context, key: &RedisString) {
+    let db = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+    track(db, key.as_slice());
+}
+
+fn track(db: c_int, key: &[u8]) {
+    EXPIRING_KEYS.lock().unwrap().insert((db, key.to_vec()));
+}
+
+/// `REDISMODULE_AUX_AFTER_RDB`: the member expiries are saved after the keyspace, so they are
+/// loaded onto the documents already there
+pub const AUX_SAVE_TRIGGERS: c_int = 1 << 1;
+
+/// Saves the member expiries of the tracked keys apart from their documents, so that the
+/// documents keep the encoding of a module without member expiry, and an RDB without any
+/// member expiry loads into one. DUMP and MIGRATE, carrying no auxiliary data, leave them out.
+pub extern "C" fn aux_save(rdb: *mut RedisModuleIO, _when: c_int) {
+    let ctx = get_llapi_ctx();
+    let tracked = EXPIRING_KEYS.lock().unwrap().clone();
+    let selected = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+    let mut keys = Vec::new();
+    for (db, key) in tracked {
+        unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, db) };
+        let expiry = ctx
+            .open_key(&ctx.create_string(key.as_slice()))
+            .get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)
+            .ok()
+            .flatten()
+            .filter(|json| !json.expiry.is_empty())
+            .map(|json| json.expiry.clone());
+        if let Some(expiry) = expiry {
+            keys.push((db, key, expiry));
+        }
+    }
+    unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, selected) };
+
+    // Saving nothing leaves the auxiliary data out of the RDB
+    if keys.is_empty() {
+        return;
+    }
+    raw::save_unsigned(rdb, keys.len() as u64);
+    for (db, key, expiry) in &keys {
+        raw::save_unsigned(rdb, *db as u64);
+        raw::save_slice(rdb, key);
+        expiry.rdb_save(rdb);
+    }
+}
+
+/// Loads the member expiries saved by [`aux_save`] onto their documents
+pub extern "C" fn aux_load(rdb: *mut RedisModuleIO, _encver: c_int, _when: c_int) -> c_int {
+    let ctx = get_llapi_ctx();
+    let selected = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+    let loaded = load_expiries(&ctx, rdb);
+    unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, selected) };
+    match loaded {
+        Ok(()) => raw::Status::Ok as c_int,
+        Err(_) => raw::Status::Err as c_int,
+    }
+}
+
+fn load_expiries(ctx: &Context, rdb: *mut RedisModuleIO) -> RedisResult<()> {
+    for _ in 0..raw::load_unsigned(rdb)? {
+        let db = raw::load_unsigned(rdb)? as c_int;
+        let key = raw::load_string_buffer(rdb)?;
+        let expiry = MemberExpiry::rdb_load(rdb)?;
+        unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, db) };
+        let key_name = ctx.create_string(key.as_ref());
+        // The document may have been left out of the load, e.g. by a slot it isn't served in
+        if let Ok(Some(json)) = ctx
+            .open_key_writable(&key_name)
+            .get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)
+        {
+            json.expiry = expiry;
+            track(db, key.as_ref());
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the expired members of the document at `key_name` as the writes do, returning
+/// whether the key is still known to have member expiries
+fn expire_members(ctx: &Context, key_name: RedisString) -> bool {
+    let manager = RedisIValueJsonKeyManager {
+        phantom: PhantomData,
+        patches: Default::default(),
+    };
+    // Opening the key for writing deletes them
+    manager
+        .open_key_write(ctx, key_name)
+        .is_ok_and(|mut redis_key| {
+            redis_key
+                .member_expiry()
+                .is_ok_and(|expiry| expiry.is_some_and(|expiry| !expiry.is_empty()))
+        })
+}
+
+/// Starts the cycle actively deleting expired members
+pub fn start_active_cycle(ctx: &Context) {
+    ctx.create_timer(ACTIVE_CYCLE_PERIOD, active_cycle, None);
+}
+
+/// Goes over the next tracked keys, from the one following `last`
+fn active_cycle(ctx: &Context, last: Option<(c_int, Vec<u8>)>) {
+    let keys = {
+        let tracked = EXPIRING_KEYS.lock().unwrap();
+        let start = last.as_ref().map_or(Bound::Unbounded, Bound::Excluded);
+        // Wrap around once the end is reached
+        tracked
+            .range((start, Bound::Unbounded))
+            .chain(tracked.iter())
+            .take(ACTIVE_CYCLE_KEYS.min(tracked.len()))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let selected = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+    for (db, key) in &keys {
+        unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, *db) };
+        if !expire_members(ctx, ctx.create_string(key.as_slice())) {
+            EXPIRING_KEYS.lock().unwrap().remove(&(*db, key.clone()));
+        }
+    }
+    unsafe { raw::RedisModule_SelectDb.unwrap()(ctx.ctx, selected) };
+
+    ctx.create_timer(ACTIVE_CYCLE_PERIOD, active_cycle, keys.into_iter().last());
+}
+
+/// Keeps tracking keys with member expiries that are renamed, moved or copied
+pub fn subscribe_to_key_moves(ctx: &Context) {
+    unsafe {
+        raw::RedisModule_SubscribeToKeyspaceEvents.unwrap()(
+            ctx.ctx,
+            NotifyEvent::GENERIC.bits(),
+            Some(on_generic_event),
+        );
+    }
+}
+
+extern "C" fn on_generic_event(
+    ctx: *mut raw::RedisModuleCtx,
+    _type: c_int,
+    event: *const c_char,
+    key: *mut raw::RedisModuleString,
+) -> c_int {
+    let event = unsafe { std::ffi::CStr::from_ptr(event) };
+    if matches!(event.to_bytes(), b"rename_to" | b"move_to" | b"copy_to") {
+        let ctx = Context::new(ctx);
+        let key = RedisString::new(std::ptr::NonNull::new(ctx.ctx), key);
+        let has_expiry = ctx
+            .open_key(&key)
+            .get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)
+            .is_ok_and(|json| json.is_some_and(|json| !json.expiry.is_empty()));
+        if has_expiry {
+            track_key(&ctx, &key);
+        }
+    }
+    raw::Status::Ok as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(elements: &[&str]) -> Vec<String> {
+        elements.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_member_expiry() {
+        let mut expiry = MemberExpiry::default();
+        expiry.set(path(&["a", "b"]), 10);
+        expiry.set(path(&["a", "bc"]), 20);
+        expiry.set(path(&["a"]), 30);
+        expiry.set(path(&["c"]), 5);
+        assert_eq!(expiry.next_expiry(), Some(5));
+        assert_eq!(expiry.get(&path(&["a", "b"])), Some(10));
+
+        assert_eq!(
+            expiry.take_expired(10),
+            vec![path(&["a", "b"]), path(&["c"])]
+        );
+        assert_eq!(expiry.next_expiry(), Some(20));
+
+        expiry.set(path(&["a", "b"]), 10);
+        assert!(expiry.clear_below(&path(&["a", "b"])));
+        assert_eq!(expiry.get(&path(&["a", "b"])), None);
+        assert_eq!(expiry.get(&path(&["a", "bc"])), Some(20));
+        assert!(!expiry.clear_below(&path(&["a", "b"])));
+        assert!(expiry.clear_below(&path(&["a"])));
+        assert!(expiry.is_empty());
+
+        expiry.set(path(&["a"]), 30);
+        assert!(expiry.remove(&path(&["a"])));
+        assert!(!expiry.remove(&path(&["a"])));
+        expiry.set(path(&["a"]), 30);
+        expiry.clear_below(&[]);
+        assert!(expiry.is_empty());
+    }
+}
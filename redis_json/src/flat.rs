@@ -0,0 +1,328 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Conversion between nested JSON values and flat maps of leaf keys, such as `a.b[0].c`, to
+//! their values.
+//!
+//! A flat key joins the member names on the way to a leaf with a separator, and appends `[i]`
+//! for array elements. Within a member name, `\`, `[`, `]` and the characters of the separator
+//! are escaped with a `\`, so any member name survives a round trip. A key may start with a
+//! separator, which is how a first member with an empty name is told apart from an index. The
+//! leaves are the scalars and the empty arrays and objects.
+
+use crate::formatter::ReplyFormatOptions;
+use crate::key_value::KeyValue;
+use crate::manager::err_recursion_limit_exceeded;
+use json_path::select_value::{SelectValue, SelectValueType, MAX_DEPTH};
+use redis_module::redisvalue::RedisValueKey;
+use redis_module::{RedisError, RedisResult, RedisValue};
+use serde_json::{Map, Value};
+
+pub const DEFAULT_SEPARATOR: &str = ".";
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum FlatElement {
+    Key(String),
+    Index(usize),
+}
+
+fn err_invalid_flat_key() -> RedisError {
+    RedisError::Str("ERR invalid flat key")
+}
+
+pub fn check_separator(separator: &str) -> RedisResult<()> {
+    if separator.is_empty() || separator.contains(['\\', '[', ']']) {
+        Err(RedisError::Str("ERR invalid separator"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Flattens `value` into a map of its leaves, with values shaped by `format`
+pub fn flatten<T: SelectValue>(
+    value: &T,
+    separator: &str,
+    format: &ReplyFormatOptions,
+) -> RedisValue {
+    let mut leaves = Vec::new();
+    flatten_into(
+        value,
+        &mut String::new(),
+        true,
+        separator,
+        format,
+        &mut leaves,
+    );
+    RedisValue::OrderedMap(
+        leaves
+            .into_iter()
+            .map(|(key, value)| (RedisValueKey::String(key), value))
+            .collect(),
+    )
+}
+
+fn flatten_into<T: SelectValue>(
+    value: &T,
+    key: &mut String,
+    top: bool,
+    separator: &str,
+    format: &ReplyFormatOptions,
+    leaves: &mut Vec<(String, RedisValue)>,
+) {
+    let len = key.len();
+    match value.get_type() {
+        SelectValueType::Object if value.is_empty() == Some(false) => {
+            for (name, child) in value.items().into_iter().flatten() {
+                // The members of the flattened value itself have no leading separator, unless
+                // their name is empty
+                if !top || name.is_empty() {
+                    key.push_str(separator);
+                }
+                escape_member(name, separator, key);
+                flatten_into(child.as_ref(), key, false, separator, format, leaves);
+                key.truncate(len);
+            }
+        }
+        SelectValueType::Array if value.is_empty() == Some(false) => {
+            for (index, child) in value.values().into_iter().flatten().enumerate() {
+                key.push_str(&format!("[{index}]"));
+                flatten_into(child.as_ref(), key, false, separator, format, leaves);
+                key.truncate(len);
+            }
+        }
+        _ => leaves.push((key.clone(), KeyValue::value_to_resp3(value, format))),
+    }
+}
+
+fn escape_member(name: &str, separator: &str, key: &mut String) {
+    for c in name.chars() {
+        if matches!(c, '\\' | '[' | ']') || separator.contains(c) {
+            key.push('\\');
+        }
+        key.push(c);
+    }
+}
+
+fn parse_flat_key(key: &str, separator: &str) -> RedisResult<Vec<FlatElement>> {
+    let mut elements = Vec::new();
+    // A key starts with a member name, possibly empty, unless it starts with an index
+    let (mut name, mut rest) = match key.strip_prefix(separator) {
+        Some(rest) => (Some(String::new()), rest),
+        None => ((!key.starts_with('[')).then(String::new), key),
+    };
+    while let Some(c) = rest.chars().next() {
+        if let Some(tail) = rest.strip_prefix(separator) {
+            elements.extend(name.replace(String::new()).map(FlatElement::Key));
+            rest = tail;
+        } else if c == '[' {
+            elements.extend(name.take().map(FlatElement::Key));
+            let (index, tail) = rest[1..].split_once(']').ok_or_else(err_invalid_flat_key)?;
+            let index = index.parse().map_err(|_| err_invalid_flat_key())?;
+            elements.push(FlatElement::Index(index));
+            rest = tail;
+        } else {
+            // A member name can't directly follow an index
+            let name = name.as_mut().ok_or_else(err_invalid_flat_key)?;
+            let (c, consumed) = match c {
+                '\\' => {
+                    let escaped = rest[1..].chars().next().ok_or_else(err_invalid_flat_key)?;
+                    (escaped, 1 + escaped.len_utf8())
+                }
+                ']' => return Err(err_invalid_flat_key()),
+                c => (c, c.len_utf8()),
+            };
+            name.push(c);
+            rest = &rest[consumed..];
+        }
+    }
+    elements.extend(name.map(FlatElement::Key));
+    Ok(elements)
+}
+
+/// Builds the nested value described by the flat map `flat`, an empty object when it is empty.
+/// Array elements missing from it are null
+pub fn unflatten(flat: Map<String, Value>, separator: &str) -> RedisResult<Value> {
+    let err_conflict = || RedisError::Str("ERR conflicting flat keys");
+    if flat.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    // Every element of an array holds at least one leaf, so no index can reach the number of
+    // keys
+    let max_len = flat.len();
+    let flat = flat
+        .into_iter()
+        .map(|(key, value)| {
+            let elements = parse_flat_key(&key, separator)?;
+            if elements.len() >= MAX_DEPTH {
+                return Err(err_recursion_limit_exceeded());
+            }
+            Ok((elements, value))
+        })
+        .collect::<RedisResult<Vec<_>>>()?;
+    // A leaf can't be above another one, whatever their order. Once sorted, a key is followed
+    // by the keys it is a prefix of
+    let mut sorted: Vec<_> = flat.iter().map(|(elements, _)| elements).collect();
+    sorted.sort_unstable();
+    if sorted.windows(2).any(|pair| pair[1].starts_with(pair[0])) {
+        return Err(err_conflict());
+    }
+
+    // Null stands for a value no key has reached yet, whose type the next element decides
+    let mut root = Value::Null;
+    for (elements, value) in flat {
+        let mut node = &mut root;
+        for element in elements {
+            node = match element {
+                FlatElement::Key(name) => {
+                    if node.is_null() {
+                        *node = Value::Object(Map::new());
+                    }
+                    node.as_object_mut()
+                        .ok_or_else(err_conflict)?
+                        .entry(name)
+                        .or_insert(Value::Null)
+                }
+                FlatElement::Index(index) => {
+                    if index >= max_len {
+                        return Err(RedisError::Str("ERR flat key index out of range"));
+                    }
+                    if node.is_null() {
+                        *node = Value::Array(Vec::new());
+                    }
+                    let array = node.as_array_mut().ok_or_else(err_conflict)?;
+                    if array.len() <= index {
+                        array.resize(index + 1, Value::Null);
+                    }
+                    &mut array[index]
+                }
+            };
+        }
+        if !node.is_null() {
+            return Err(err_conflict());
+        }
+        *node = value;
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redisjson::ReplyFormat;
+    use serde_json::json;
+
+    fn flat_keys(value: &Value, separator: &str) -> Vec<String> {
+        let format = ReplyFormatOptions::new(true, ReplyFormat::EXPAND);
+        match flatten(value, separator, &format) {
+            RedisValue::OrderedMap(map) => map
+                .into_keys()
+                .map(|key| match key {
+                    RedisValueKey::String(key) => key,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_flat_keys() {
+        let value = json!({"a": {"b": [1, {"c": true}], "d": {}}, "e.f": [], "": {"g[0]": null}});
+        assert_eq!(
+            flat_keys(&value, DEFAULT_SEPARATOR),
+            vec!["..g\\[0\\]", "a.b[0]", "a.b[1].c", "a.d", "e\\.f"]
+        );
+        assert_eq!(
+            flat_keys(&json!([[1], {"a": 2}]), "::"),
+            vec!["[0][0]", "[1]::a"]
+        );
+        assert_eq!(flat_keys(&json!({"a:b": {"c": 1}}), "::"), vec!["a\\:b::c"]);
+    }
+
+    #[test]
+    fn test_parse_flat_key() {
+        assert_eq!(
+            parse_flat_key("a.b[0][12].\\.c\\\\", ".").unwrap(),
+            vec![
+                FlatElement::Key("a".to_string()),
+                FlatElement::Key("b".to_string()),
+                FlatElement::Index(0),
+                FlatElement::Index(12),
+                FlatElement::Key(".c\\".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_flat_key(".", ".").unwrap(),
+            vec![FlatElement::Key(String::new())]
+        );
+        assert_eq!(
+            parse_flat_key("..[0]", ".").unwrap(),
+            vec![
+                FlatElement::Key(String::new()),
+                FlatElement::Key(String::new()),
+                FlatElement::Index(0)
+            ]
+        );
+        assert!(parse_flat_key("a[0]b", ".").is_err());
+        assert!(parse_flat_key("a[x]", ".").is_err());
+        assert!(parse_flat_key("a]", ".").is_err());
+        assert!(parse_flat_key("a\\", ".").is_err());
+    }
+
+    #[test]
+    fn test_unflatten() {
+        let value = json!({"a": {"b": [1, {"c.d": true}], "e": {}}, "": [[], null]});
+        for separator in [".", "::", "b"] {
+            let format = ReplyFormatOptions::new(true, ReplyFormat::EXPAND);
+            let RedisValue::OrderedMap(map) = flatten(&value, separator, &format) else {
+                unreachable!()
+            };
+            let flat = map
+                .into_iter()
+                .map(|(key, value)| {
+                    let RedisValueKey::String(key) = key else {
+                        unreachable!()
+                    };
+                    let value = match value {
+                        RedisValue::Integer(i) => json!(i),
+                        RedisValue::Bool(b) => json!(b),
+                        RedisValue::Null => Value::Null,
+                        RedisValue::Array(_) => json!([]),
+                        RedisValue::Map(_) => json!({}),
+                        _ => unreachable!(),
+                    };
+                    (key, value)
+                })
+                .collect();
+            assert_eq!(unflatten(flat, separator).unwrap(), value);
+        }
+
+        let unflat = |flat: Value| unflatten(flat.as_object().unwrap().clone(), ".");
+        assert_eq!(
+            unflat(json!({"[1]": 1, "[0].a": 2})).unwrap(),
+            json!([{"a": 2}, 1])
+        );
+        assert_eq!(
+            unflat(json!({"a[2]": 1, "a[0]": 2, "b": 3})).unwrap(),
+            json!({"a": [2, null, 1], "b": 3})
+        );
+        assert_eq!(unflat(json!({})).unwrap(), json!({}));
+        assert!(unflat(json!({"a": 1, "a.b": 2})).is_err());
+        // The keys are met sorted, which puts an escaped key before the leaf it goes below
+        for below in ["a.b", "\\a.b", "a[0]", "\\a[0]"] {
+            for leaf in [json!({}), json!([]), Value::Null] {
+                let flat = Map::from_iter([("a".to_string(), leaf), (below.to_string(), json!(1))]);
+                assert!(unflatten(flat, ".").is_err());
+            }
+        }
+        assert!(unflat(json!({"a": 1, "\\a": 2})).is_err());
+        assert!(unflat(json!({"a[0]": 1, "a.b": 2})).is_err());
+        assert!(unflat(json!({"a[5]": 1})).is_err());
+    }
+}
@@ -61,6 +61,12 @@ typedef const void* JSONResultsIterator;
 typedef const void* JSONPath;
 typedef const void* JSONKeyValuesIterator;
 
+// Called after each write to a JSON key with the keyspace event of the write (e.g. "json.set"),
+// the key, and the normalized paths the write touched (e.g. "$['a'][0]")
+typedef void (*JSONPathEventCallback)(RedisModuleCtx *ctx, const char *event,
+                                      RedisModuleString *key, const char *const *paths,
+                                      size_t len);
+
 typedef struct RedisJSONAPI {
 
   ////////////////
@@ -184,9 +190,17 @@ typedef struct RedisJSONAPI {
   // Like `get`, but takes a compiled path handle (from `pathParse`) instead of a path string.
   JSONResultsIterator (*getWithPath)(RedisJSON json, JSONPath path);
 
+  /////////////////
+  // V10 entries //
+  /////////////////
+  // Registers `callback` to be called after each write to a JSON key, whether or not the
+  // module notifies the touched paths as keyspace events. Returns REDISMODULE_ERR for a NULL
+  // callback.
+  int (*subscribeToPathEvents)(JSONPathEventCallback callback);
+
 } RedisJSONAPI;
 
-#define RedisJSONAPI_LATEST_API_VER 9
+#define RedisJSONAPI_LATEST_API_VER 10
 #ifdef __cplusplus
 }
 #endif
@@ -10,7 +10,7 @@
 use crate::manager::{
     err_invalid_path, err_json, err_recursion_limit_exceeded, Manager, ReadHolder, WriteHolder,
 };
-use crate::redisjson::normalize_arr_start_index;
+use crate::redisjson::{normalize_arr_start_index, StrUnit};
 use crate::Format;
 use crate::REDIS_JSON_TYPE;
 use bson::{from_document, Document};
@@ -22,7 +22,7 @@ use json_path::select_value::{SelectValue, SelectValueType, MAX_DEPTH};
 use redis_module::key::{verify_type, KeyFlags, RedisKey, RedisKeyWritable};
 use redis_module::raw::{self as rawmod, RedisModuleKey, Status};
 use redis_module::RedisError;
-use redis_module::{Context, NotifyEvent, RedisResult, RedisString};
+use redis_module::{Context, ContextFlags, NotifyEvent, RedisResult, RedisString};
 use serde::de::DeserializeSeed;
 use serde::Serialize;
 use serde_json::Number;
@@ -30,8 +30,14 @@ use std::io::Cursor;
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::ptr::null;
+use std::time::Duration;
 
+use crate::cdc;
+use crate::effects::{self, Op};
+use crate::expiry::{self, MemberExpiry};
+use crate::notify;
 use crate::redisjson::RedisJSON;
+use crate::wait;
 
 use crate::array_index::ArrayIndex;
 
@@ -39,6 +45,14 @@ pub struct IValueKeyHolderWrite<'a> {
     key: RedisKeyWritable,
     key_name: RedisString,
     val: Option<&'a mut RedisJSON<IValue>>,
+    /// The paths written since the last notification
+    touched: Vec<Vec<String>>,
+    /// Whether an array was given elements since the last notification
+    adds_elements: bool,
+    /// The capture of the writes, when the key is captured
+    cdc: Option<cdc::Capture>,
+    /// The effects of the writes, when they are replicated rather than the command
+    effects: Option<effects::Effects>,
 }
 
 #[derive(Debug)]
@@ -133,10 +147,37 @@ where
     )
 }
 
+/// Calls `f` with the value at `path` in `doc`, whose elements are indices where `doc` has arrays
+pub(crate) fn with_value_at<R>(
+    doc: &IValue,
+    path: &[String],
+    f: impl FnOnce(&IValue) -> R,
+) -> Option<R> {
+    let Some((element, rest)) = path.split_first() else {
+        return Some(f(doc));
+    };
+    let child = if doc.get_type() == SelectValueType::Array {
+        doc.get_index(element.parse().ok()?)
+    } else {
+        doc.get_key(element)
+    }?;
+    with_value_at(&child, rest, f)
+}
+
+/// Whether `v` is an array with elements, or holds one at any depth
+fn holds_elements(v: &IValue) -> bool {
+    if let Some(arr) = v.as_array() {
+        !arr.is_empty()
+    } else {
+        v.as_object()
+            .is_some_and(|obj| obj.values().any(holds_elements))
+    }
+}
+
 ///
 /// Removes a value at a given `path`, starting from `root`
 ///
-fn remove(mut path: Vec<String>, root: &mut IValue) -> bool {
+pub(crate) fn remove(mut path: Vec<String>, root: &mut IValue) -> bool {
     let Some(token) = path.pop() else {
         return false;
     };
@@ -166,8 +207,39 @@ impl<'a> IValueKeyHolderWrite<'a> {
     where
         F: FnOnce(PathValue<'_, '_>, usize) -> RedisResult<T>,
     {
+        self.do_op_as(paths, |v, depth| Ok((op_fun(v, depth)?, Op::Replace)))
+    }
+
+    /// Same as [`Self::do_op`], with `op_fun` returning the effect of its write as well
+    fn do_op_as<F, T>(&mut self, paths: Vec<String>, op_fun: F) -> RedisResult<T>
+    where
+        F: FnOnce(PathValue<'_, '_>, usize) -> RedisResult<(T, Op)>,
+    {
+        self.before_write(&paths)?;
         let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
-        update(paths, root, op_fun)
+        let (res, op) = update(paths.clone(), root, op_fun)?;
+        self.touch(paths, op)?;
+        Ok(res)
+    }
+
+    /// Replaces the string at `paths` by `op_fun` of it and of the JSON string `json`, along
+    /// with the effect of the write, returning the length in bytes of the new string
+    fn do_str_op<F>(&mut self, paths: Vec<String>, json: &str, op_fun: F) -> RedisResult<usize>
+    where
+        F: FnOnce(&str, &str) -> RedisResult<(String, Op)>,
+    {
+        let serde_json::Value::String(s) = serde_json::from_str(json)? else {
+            return Err(err_json("string"));
+        };
+        self.do_op_as(paths, |v, _depth| {
+            let PathValue::IValue(v) = v else {
+                return Err(crate::manager::err_bad_object());
+            };
+            let v_str = v.as_string_mut().ok_or_else(|| err_json("string"))?;
+            let (new_str, op) = op_fun(v_str.as_str(), &s)?;
+            *v_str = IString::intern(&new_str);
+            Ok((new_str.len(), op))
+        })
     }
 
     fn do_num_op<F1, F2>(
@@ -355,41 +427,66 @@ impl<'a> IValueKeyHolderWrite<'a> {
         Ok(())
     }
 
-    fn set_root(&mut self, data: IValue) -> RedisResult<bool> {
-        self.get_json_holder()?;
-        if let Some(val) = &mut self.val {
-            val.data = data
-        } else {
-            self.key.set_value(&REDIS_JSON_TYPE, RedisJSON { data })?
+    /// Keeps the value at `path` for the change data capture, as it is about to be written
+    fn before_write(&mut self, path: &[String]) -> RedisResult<()> {
+        if self.cdc.is_some() {
+            self.get_json_holder()?;
+            if let Some(cdc) = &mut self.cdc {
+                cdc.before_write(self.val.as_deref().map(|v| &v.data), path);
+            }
         }
-        Ok(true)
+        Ok(())
     }
-}
 
-impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
-    fn notify_keyspace_event(&mut self, ctx: &Context, command: &str) -> RedisResult<()> {
-        if ctx.notify_keyspace_event(NotifyEvent::MODULE, command, &self.key_name) != Status::Ok {
-            Err(RedisError::Str("failed notify key space event"))
-        } else {
-            Ok(())
+    /// Records that the value at `path` was written by `op`, for the next notification
+    fn touch(&mut self, path: Vec<String>, op: Op) -> RedisResult<()> {
+        if self.effects.is_some() {
+            self.get_json_holder()?;
+            if let Some(effects) = &mut self.effects {
+                effects.record(self.val.as_deref().map(|v| &v.data), &path, op);
+            }
         }
+        self.adds_elements |= match op {
+            Op::Insert { count, .. } => count > 0,
+            Op::Set => {
+                self.get_json_holder()?;
+                self.val
+                    .as_deref()
+                    .and_then(|v| with_value_at(&v.data, &path, holds_elements))
+                    .unwrap_or(false)
+            }
+            _ => false,
+        };
+        self.touched.push(path);
+        Ok(())
     }
 
-    fn delete(&mut self) -> RedisResult<()> {
-        self.key.delete()?;
+    /// Forgets the expiry of the member at `path` and of the members below it
+    pub(crate) fn clear_member_expiry(&mut self, path: &[String]) -> RedisResult<()> {
+        self.get_json_holder()?;
+        if let Some(val) = &mut self.val {
+            val.expiry.clear_below(path);
+        }
         Ok(())
     }
 
-    fn get_value(&mut self) -> RedisResult<Option<&mut IValue>> {
+    /// Forgets the expiry of the members at and below `path`, which a write otherwise keeping
+    /// the expiries replaced or emptied
+    fn clear_replaced_expiry(&mut self, path: &[String]) -> RedisResult<()> {
         self.get_json_holder()?;
-
-        match &mut self.val {
-            Some(v) => Ok(Some(&mut v.data)),
-            None => Ok(None),
+        let cleared = self
+            .val
+            .as_mut()
+            .is_some_and(|val| val.expiry.clear_below(path));
+        // The replicas keep the expiries on the write itself
+        if let (true, Some(effects)) = (cleared, &mut self.effects) {
+            effects.record_persist(path);
         }
+        Ok(())
     }
 
-    fn set_value(&mut self, path: Vec<String>, mut v: IValue) -> RedisResult<bool> {
+    /// Replaces the value at `path`, which isn't the root, returning whether it was there
+    fn replace_at(&mut self, path: Vec<String>, mut v: IValue) -> RedisResult<bool> {
         // Macro to generate repetitive match arms for array types
         macro_rules! handle_array_types {
             ($val:expr, $v:expr, $depth:expr, $($variant:ident),+ $(,)?) => {
@@ -413,28 +510,182 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
             };
         }
 
+        let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
+        Ok(update(path, root, |val, depth| {
+            handle_array_types!(
+                val, v, depth, I8, U8, I16, U16, F16, BF16, I32, U32, F32, I64, U64, F64
+            )
+        })
+        .is_ok())
+    }
+
+    /// Writes `v` at `path` as the writes keeping the expiry of the members they replace do,
+    /// returning whether the value was there
+    pub(crate) fn replace_value(&mut self, path: Vec<String>, v: IValue) -> RedisResult<bool> {
+        self.before_write(&path)?;
+        let adds_elements = holds_elements(&v);
+        let replaced = if path.is_empty() {
+            self.get_json_holder()?;
+            match &mut self.val {
+                Some(val) => {
+                    val.data = v;
+                    true
+                }
+                None => false,
+            }
+        } else {
+            self.replace_at(path.clone(), v)?
+        };
+        if replaced {
+            self.adds_elements |= adds_elements;
+            self.touch(path, Op::Replace)?;
+        }
+        Ok(replaced)
+    }
+
+    /// Deletes the members expired by now, and the key along with its last member, as a write of
+    /// its own notified as `json.expired` and replicated ahead of the command's
+    fn expire_members(&mut self, ctx: &Context) -> RedisResult<()> {
+        // A replica waits for the deletions of its master
+        if ctx
+            .get_flags()
+            .intersects(ContextFlags::REPLICATED | ContextFlags::SLAVE | ContextFlags::LOADING)
+        {
+            return Ok(());
+        }
+        // A key of another type is left for the command to reject
+        if self.get_json_holder().is_err() {
+            return Ok(());
+        }
+        let now = expiry::now_ms();
+        let expired = match &mut self.val {
+            Some(val) if val.expiry.next_expiry().is_some_and(|at| at <= now) => {
+                val.expiry.take_expired(now)
+            }
+            _ => return Ok(()),
+        };
+
+        let patches = effects::Patches::default();
+        let effects = std::mem::replace(&mut self.effects, effects::capture(ctx, &patches));
+        let mut deleted = Vec::new();
+        for member in expired {
+            if self.delete_path(member.clone())? {
+                deleted.push(member);
+            }
+        }
+        if !deleted.is_empty() {
+            // Like JSON.DEL, deleting the last member deletes the key
+            if self.get_value()?.and_then(|v| SelectValue::is_empty(&*v)) == Some(true) {
+                self.delete()?;
+            }
+            self.notify_keyspace_event(ctx, "json.expired")?;
+            if !patches.replicate(ctx) {
+                for member in &deleted {
+                    let path = notify::normalized_path::<IValue>(None, member);
+                    ctx.replicate("JSON.DEL", &[self.key_name.as_slice(), path.as_bytes()]);
+                }
+            }
+        }
+        self.effects = effects;
+        Ok(())
+    }
+
+    fn set_root(&mut self, data: IValue) -> RedisResult<bool> {
+        self.get_json_holder()?;
+        if let Some(val) = &mut self.val {
+            val.data = data;
+            val.expiry = MemberExpiry::default();
+        } else {
+            self.key.set_value(&REDIS_JSON_TYPE, RedisJSON::new(data))?
+        }
+        Ok(true)
+    }
+}
+
+impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
+    fn notify_keyspace_event(&mut self, ctx: &Context, command: &str) -> RedisResult<()> {
+        if ctx.notify_keyspace_event(NotifyEvent::MODULE, command, &self.key_name) != Status::Ok {
+            return Err(RedisError::Str("failed notify key space event"));
+        }
+        let touched = std::mem::take(&mut self.touched);
+        self.get_json_holder()?;
+        let doc = self.val.as_deref().map(|v| &v.data);
+        // Writes that gave an array elements wake up clients blocked by JSON.ARRBPOP
+        let adds_elements = std::mem::take(&mut self.adds_elements);
+        // So do the writes touching a path clients wait on with JSON.WAIT
+        if wait::on_write(ctx, &self.key_name, doc, &touched) || adds_elements {
+            unsafe {
+                rawmod::RedisModule_SignalKeyAsReady.unwrap()(ctx.ctx, self.key_name.inner);
+            }
+        }
+        notify::notify_paths(ctx, command, &self.key_name, doc, &touched);
+        if let Some(cdc) = &mut self.cdc {
+            cdc.append(ctx, command, &self.key_name, doc, &touched);
+        }
+        if let Some(effects) = &mut self.effects {
+            effects.flush(command, &self.key_name);
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self) -> RedisResult<()> {
+        self.before_write(&[])?;
+        self.key.delete()?;
+        // The document is freed along with the key
+        self.val = None;
+        self.touch(Vec::new(), Op::Del)?;
+        Ok(())
+    }
+
+    fn set_expire_at(&mut self, at: i64) -> RedisResult<()> {
+        // A time in the past expires the key right away
+        let ttl = at.saturating_sub(expiry::now_ms()).max(0);
+        self.key.set_expire(Duration::from_millis(ttl as u64))?;
+        Ok(())
+    }
+
+    fn get_value(&mut self) -> RedisResult<Option<&mut IValue>> {
+        self.get_json_holder()?;
+
+        match &mut self.val {
+            Some(v) => Ok(Some(&mut v.data)),
+            None => Ok(None),
+        }
+    }
+
+    fn member_expiry(&mut self) -> RedisResult<Option<&mut MemberExpiry>> {
+        self.get_json_holder()?;
+        Ok(self.val.as_mut().map(|v| &mut v.expiry))
+    }
+
+    fn set_value(&mut self, path: Vec<String>, v: IValue) -> RedisResult<bool> {
+        self.before_write(&path)?;
         if path.is_empty() {
             // update the root
-            self.set_root(v)
+            self.set_root(v)?;
+            self.touch(Vec::new(), Op::Set)?;
+            Ok(true)
         } else {
-            let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
-            Ok(update(path, root, |val, depth| {
-                handle_array_types!(
-                    val, v, depth, I8, U8, I16, U16, F16, BF16, I32, U32, F32, I64, U64, F64
-                )
-            })
-            .is_ok())
+            let updated = self.replace_at(path.clone(), v)?;
+            if updated {
+                self.clear_member_expiry(&path)?;
+                self.touch(path, Op::Set)?;
+            }
+            Ok(updated)
         }
     }
 
     fn merge_value(&mut self, path: Vec<String>, mut v: IValue) -> RedisResult<bool> {
+        self.before_write(&path)?;
+        let adds_elements = holds_elements(&v);
         let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
-        update(path, root, |current, depth| {
+        let mut replaced = Vec::new();
+        let merged = update(path.clone(), root, |current, depth| {
             let PathValue::IValue(current) = current else {
                 return Err(crate::manager::err_bad_object());
             };
             if can_merge(current, &v, depth) {
-                merge(current, v.take());
+                merge(current, v.take(), &mut path.clone(), &mut replaced);
                 Ok(true)
             } else {
                 Ok(false)
@@ -450,11 +701,23 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
             } else {
                 Err(e)
             }
-        })
+        })?;
+        if merged {
+            self.adds_elements |= adds_elements;
+            self.touch(path, Op::Replace)?;
+            for member in &replaced {
+                self.clear_replaced_expiry(member)?;
+            }
+        }
+        Ok(merged)
     }
 
     fn dict_add(&mut self, path: Vec<String>, key: &str, mut v: IValue) -> RedisResult<bool> {
-        self.do_op(path, |val: PathValue<'_, '_>, depth| {
+        let mut member = path.clone();
+        member.push(key.to_string());
+        self.before_write(&member)?;
+        let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
+        let added = update(path, root, |val: PathValue<'_, '_>, depth| {
             let PathValue::IValue(val) = val else {
                 return Err(crate::manager::err_bad_object());
             };
@@ -470,12 +733,23 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
                 }
                 Ok(res)
             })
-        })
+        })?;
+        if added {
+            self.clear_member_expiry(&member)?;
+            self.touch(member, Op::Set)?;
+        }
+        Ok(added)
     }
 
     fn delete_path(&mut self, path: Vec<String>) -> RedisResult<bool> {
+        self.before_write(&path)?;
         let root = self.get_value()?.ok_or(RedisError::nonexistent_key())?;
-        Ok(remove(path, root))
+        let deleted = remove(path.clone(), root);
+        if deleted {
+            self.clear_member_expiry(&path)?;
+            self.touch(path, Op::Del)?;
+        }
+        Ok(deleted)
     }
 
     fn incr_by(&mut self, path: Vec<String>, num: &str) -> RedisResult<Number> {
@@ -512,25 +786,31 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
     }
 
     fn str_append(&mut self, path: Vec<String>, val: String) -> RedisResult<usize> {
-        match serde_json::from_str(&val)? {
-            serde_json::Value::String(s) => self.do_op(path, |v, _depth| {
-                let PathValue::IValue(v) = v else {
-                    return Err(crate::manager::err_bad_object());
-                };
-                v.as_string_mut()
-                    .map(|v_str| {
-                        let new_str = [v_str.as_str(), s.as_str()].concat();
-                        *v_str = IString::intern(&new_str);
-                        Ok(new_str.len())
-                    })
-                    .unwrap_or_else(|| Err(err_json("string")))
-            }),
-            _ => Err(err_json("string")),
-        }
+        self.do_str_op(path, &val, |v_str, s| {
+            Ok(([v_str, s].concat(), Op::Append { len: s.len() }))
+        })
+    }
+
+    fn str_prepend(&mut self, path: Vec<String>, val: String) -> RedisResult<usize> {
+        self.do_str_op(path, &val, |v_str, s| {
+            Ok(([s, v_str].concat(), Op::Replace))
+        })
+    }
+
+    fn str_set_range(
+        &mut self,
+        path: Vec<String>,
+        offset: usize,
+        val: String,
+        unit: StrUnit,
+    ) -> RedisResult<usize> {
+        self.do_str_op(path, &val, |v_str, s| {
+            Ok((unit.set_range(v_str, offset, s)?, Op::Replace))
+        })
     }
 
     fn arr_append(&mut self, path: Vec<String>, args: Vec<IValue>) -> RedisResult<usize> {
-        self.do_op(path, |v, depth| {
+        self.do_op_as(path, |v, depth| {
             let PathValue::IValue(v) = v else {
                 return Err(crate::manager::err_bad_object());
             };
@@ -543,16 +823,18 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
                         return Err(err_recursion_limit_exceeded());
                     }
 
+                    let index = arr.len() as usize;
+                    let count = args.len();
                     arr.try_extend(args)
                         .map_err(|e| RedisError::String(e.to_string()))?;
-                    Ok(arr.len() as usize)
+                    Ok((arr.len() as usize, Op::Insert { index, count }))
                 })
                 .unwrap_or_else(|| Err(err_json("array")))
         })
     }
 
     fn arr_insert(&mut self, paths: Vec<String>, args: &[IValue], idx: i64) -> RedisResult<usize> {
-        self.do_op(paths, |v, depth| {
+        self.do_op_as(paths, |v, depth| {
             let PathValue::IValue(v) = v else {
                 return Err(crate::manager::err_bad_object());
             };
@@ -589,7 +871,11 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
                         U64(slice) => slice[idx as _..].rotate_right(args.len()),
                         F64(slice) => slice[idx as _..].rotate_right(args.len()),
                     };
-                    Ok(arr.len() as usize)
+                    let op = Op::Insert {
+                        index: idx as _,
+                        count: args.len(),
+                    };
+                    Ok((arr.len() as usize, op))
                 })
                 .unwrap_or_else(|| Err(err_json("array")))
         })
@@ -599,21 +885,22 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
     where
         C: FnOnce(Option<&IValue>) -> RedisResult,
     {
-        let res = self.do_op(path, |v, _depth| {
+        // Popping from an empty array writes nothing
+        let empty = self.get_value()?.and_then(|root| {
+            with_value_at(root, &path, |v| v.as_array().is_some_and(|a| a.is_empty()))
+        });
+        if empty == Some(true) {
+            return serialize_callback(None);
+        }
+        let res = self.do_op_as(path, |v, _depth| {
             let PathValue::IValue(v) = v else {
                 return Err(crate::manager::err_bad_object());
             };
-            v.as_array_mut()
-                .map(|array| {
-                    if array.is_empty() {
-                        return None;
-                    }
-                    // Verify legal index in bounds
-                    let len = array.len() as i64;
-                    let index = normalize_arr_start_index(index, len) as usize;
-                    array.remove(index)
-                })
-                .ok_or_else(|| err_json("array"))
+            let array = v.as_array_mut().ok_or_else(|| err_json("array"))?;
+            // Verify legal index in bounds
+            let len = array.len() as i64;
+            let index = normalize_arr_start_index(index, len) as usize;
+            Ok((array.remove(index), Op::Remove { index }))
         })?;
         serialize_callback(res.as_ref())
     }
@@ -661,39 +948,120 @@ impl<'a> WriteHolder<IValue, IValue> for IValueKeyHolderWrite<'a> {
         })
     }
 
-    fn clear(&mut self, path: Vec<String>) -> RedisResult<usize> {
+    fn arr_retain(&mut self, path: Vec<String>, keep: &[bool]) -> RedisResult<usize> {
+        // Moves the kept elements to the front, preserving their order, and returns their count
+        fn compact<T>(slice: &mut [T], keep: &[bool]) -> usize {
+            let mut len = 0;
+            for i in 0..slice.len() {
+                if keep.get(i).copied().unwrap_or(true) {
+                    slice.swap(len, i);
+                    len += 1;
+                }
+            }
+            len
+        }
+
         self.do_op(path, |v, _depth| {
+            let PathValue::IValue(v) = v else {
+                return Err(crate::manager::err_bad_object());
+            };
+            v.as_array_mut()
+                .map(|array| {
+                    use ijson::array::ArraySliceMut::*;
+                    let len = match array.as_mut_slice() {
+                        Heterogeneous(slice) => compact(slice, keep),
+                        I8(slice) => compact(slice, keep),
+                        U8(slice) => compact(slice, keep),
+                        I16(slice) => compact(slice, keep),
+                        U16(slice) => compact(slice, keep),
+                        F16(slice) => compact(slice, keep),
+                        BF16(slice) => compact(slice, keep),
+                        I32(slice) => compact(slice, keep),
+                        U32(slice) => compact(slice, keep),
+                        F32(slice) => compact(slice, keep),
+                        I64(slice) => compact(slice, keep),
+                        U64(slice) => compact(slice, keep),
+                        F64(slice) => compact(slice, keep),
+                    };
+                    array.truncate(len);
+                    len
+                })
+                .ok_or_else(|| err_json("array"))
+        })
+    }
+
+    fn clear(&mut self, path: Vec<String>) -> RedisResult<usize> {
+        let (cleared, emptied) = self.do_op(path.clone(), |v, _depth| {
             let PathValue::IValue(v) = v else {
                 return Err(crate::manager::err_bad_object());
             };
             match v.destructure_mut() {
                 DestructuredMut::Object(obj) => {
                     obj.clear();
-                    Ok(1)
+                    Ok((1, true))
                 }
                 DestructuredMut::Array(arr) => {
                     arr.clear();
-                    Ok(1)
+                    Ok((1, true))
                 }
                 DestructuredMut::Number(n) => {
                     *n = INumber::from(0);
-                    Ok(1)
+                    Ok((1, false))
                 }
-                _ => Ok(0),
+                _ => Ok((0, false)),
             }
-        })
+        })?;
+        if emptied {
+            self.clear_replaced_expiry(&path)?;
+        }
+        Ok(cleared)
     }
 }
 
 pub struct IValueKeyHolderRead {
     key: RedisKey,
+    /// The document without its members expired but not deleted yet, when it has any, and
+    /// `None` when they are all of its members, as the key goes along with them
+    unexpired: Option<Option<IValue>>,
+}
+
+impl IValueKeyHolderRead {
+    fn new(key: RedisKey) -> Self {
+        let unexpired = Self::without_expired(&key);
+        Self { key, unexpired }
+    }
+
+    /// Hides the members expired at `key`, which only the writes and the active expiry cycle
+    /// delete, so that a read neither writes nor replicates
+    fn without_expired(key: &RedisKey) -> Option<Option<IValue>> {
+        let json = key
+            .get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)
+            .ok()??;
+        let now = expiry::now_ms();
+        json.expiry.next_expiry().filter(|at| *at <= now)?;
+        let mut data = json.data.clone();
+        let mut removed = false;
+        for member in json.expiry.clone().take_expired(now) {
+            removed |= remove(member, &mut data);
+        }
+        let is_empty = SelectValue::is_empty(&data) == Some(true);
+        Some((!removed || !is_empty).then_some(data))
+    }
 }
 
 impl ReadHolder<IValue> for IValueKeyHolderRead {
     fn get_value(&self) -> RedisResult<Option<&IValue>> {
+        if let Some(unexpired) = &self.unexpired {
+            return Ok(unexpired.as_ref());
+        }
         let key_value = self.key.get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)?;
         key_value.map_or(Ok(None), |v| Ok(Some(&v.data)))
     }
+
+    fn member_expiry(&self) -> RedisResult<Option<&MemberExpiry>> {
+        let key_value = self.key.get_value::<RedisJSON<IValue>>(&REDIS_JSON_TYPE)?;
+        Ok(key_value.map(|v| &v.expiry))
+    }
 }
 
 fn can_merge(doc: &IValue, patch: &IValue, current_depth: usize) -> bool {
@@ -723,14 +1091,23 @@ fn can_merge(doc: &IValue, patch: &IValue, current_depth: usize) -> bool {
     })
 }
 
-fn merge(doc: &mut IValue, mut patch: IValue) {
+/// Merges `patch` into `doc`, which is at `at`, adding to `replaced` the paths of the values
+/// deleted by a null in `patch` or replaced rather than merged into
+fn merge(
+    doc: &mut IValue,
+    mut patch: IValue,
+    at: &mut Vec<String>,
+    replaced: &mut Vec<Vec<String>>,
+) {
     if !patch.is_object() {
         *doc = patch;
+        replaced.push(at.clone());
         return;
     }
 
     if !doc.is_object() {
         *doc = IObject::new().into();
+        replaced.push(at.clone());
     }
     let map = doc.as_object_mut().unwrap();
     patch
@@ -738,21 +1115,29 @@ fn merge(doc: &mut IValue, mut patch: IValue) {
         .unwrap()
         .into_iter()
         .for_each(|(key, value)| {
+            at.push(key.to_string());
             if value.is_null() {
-                map.remove(key.as_str());
+                if map.remove(key.as_str()).is_some() {
+                    replaced.push(at.clone());
+                }
             } else {
                 merge(
                     // Since entry now will only fail on allocation error, and the alternative is to propagate the error,
                     // which means copying the value before the operation, I prefer to unwrap here(same behavior as before).
                     map.entry(key.as_str()).unwrap().or_insert(IValue::NULL),
                     value.take(),
+                    at,
+                    replaced,
                 )
             }
+            at.pop();
         })
 }
 
 pub struct RedisIValueJsonKeyManager<'a> {
     pub phantom: PhantomData<&'a u64>,
+    /// The patches the command wrote, when its effects are replicated rather than the command
+    pub patches: effects::Patches,
 }
 
 impl<'a> Manager for RedisIValueJsonKeyManager<'a> {
@@ -763,7 +1148,7 @@ impl<'a> Manager for RedisIValueJsonKeyManager<'a> {
 
     fn open_key_read(&self, ctx: &Context, key: &RedisString) -> RedisResult<IValueKeyHolderRead> {
         let key = ctx.open_key(key);
-        Ok(IValueKeyHolderRead { key })
+        Ok(IValueKeyHolderRead::new(key))
     }
 
     fn open_key_read_with_flags(
@@ -773,7 +1158,7 @@ impl<'a> Manager for RedisIValueJsonKeyManager<'a> {
         flags: KeyFlags,
     ) -> RedisResult<Self::ReadHolder> {
         let key = ctx.open_key_with_flags(key, flags);
-        Ok(IValueKeyHolderRead { key })
+        Ok(IValueKeyHolderRead::new(key))
     }
 
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
@@ -793,18 +1178,34 @@ impl<'a> Manager for RedisIValueJsonKeyManager<'a> {
         key: RedisString,
     ) -> RedisResult<IValueKeyHolderWrite<'a>> {
         let key_ptr = ctx.open_key_writable(&key);
-        Ok(IValueKeyHolderWrite {
+        let cdc = cdc::capture(ctx, &key);
+        let mut holder = IValueKeyHolderWrite {
             key: key_ptr,
             key_name: key,
             val: None,
-        })
+            touched: Vec::new(),
+            adds_elements: false,
+            cdc,
+            effects: effects::capture(ctx, &self.patches),
+        };
+        holder.expire_members(ctx)?;
+        Ok(holder)
     }
     /**
      * This function is used to apply changes to the slave and AOF.
      * It is called after the command is executed.
      */
     fn apply_changes(&self, ctx: &Context) {
-        ctx.replicate_verbatim();
+        // A command that wrote no effects, e.g. JSON.TOHASH, is replicated as is
+        if !self.patches.replicate(ctx) {
+            ctx.replicate_verbatim();
+        }
+    }
+
+    fn apply_changes_as(&self, ctx: &Context, command: &str, args: &[&[u8]]) {
+        if !self.patches.replicate(ctx) {
+            ctx.replicate(command, args);
+        }
     }
 
     fn from_str(
@@ -850,6 +1251,16 @@ impl<'a> Manager for RedisIValueJsonKeyManager<'a> {
         }
     }
 
+    fn nest(&self, keys: &[String], v: Self::O) -> RedisResult<Self::O> {
+        keys.iter().rev().try_fold(v, |v, key| {
+            let mut object = IObject::new();
+            object
+                .insert(key.clone(), v)
+                .map_err(|e| RedisError::String(e.to_string()))?;
+            Ok(object.into())
+        })
+    }
+
     fn get_memory(v: &Self::V) -> RedisResult<usize> {
         Ok(v.mem_allocated() + size_of::<IValue>())
     }
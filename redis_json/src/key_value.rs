@@ -1,10 +1,14 @@
-use itertools::Itertools;
+use itertools::{Either, Itertools};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::Hasher;
 
 use json_path::{
-    calc_once, calc_once_paths, calc_once_projection, compile,
-    json_path::JsonPathToken,
-    select_value::{is_equal, SelectValue, SelectValueType, ValueRef},
+    calc_once, calc_once_paths, calc_once_projection, calc_once_with_paths, compile, create,
+    create_with_generator,
+    json_path::{JsonPathToken, Query, UserPathTracker},
+    select_value::{compare_values, hash_value, is_equal, SelectValue, SelectValueType, ValueRef},
 };
 use redis_module::{redisvalue::RedisValueKey, RedisError, RedisResult, RedisValue};
 use serde::Serialize;
@@ -17,7 +21,7 @@ use crate::{
         err_invalid_path, err_json, err_projection_readonly, AddUpdateInfo, SetUpdateInfo,
         UpdateInfo,
     },
-    redisjson::{normalize_arr_indices, Path, ReplyFormat, SetOptions},
+    redisjson::{normalize_arr_indices, Path, ReplyFormat, SetOptions, StrUnit},
 };
 
 pub struct KeyValue<'a, V: SelectValue> {
@@ -447,6 +451,55 @@ impl<'a, V: SelectValue + 'a> KeyValue<'a, V> {
         }
     }
 
+    /// Finds where to add the missing member at the end of a static `path`: the path of its
+    /// deepest existing ancestor, and the keys of the members missing below it, outermost
+    /// first. Ancestors other than the direct parent may only be missing with
+    /// `create_parents`, as they are then created as objects nested in the first missing
+    /// member. Returns `None` when nothing can be added, e.g. when the existing ancestor is
+    /// not an object.
+    pub fn find_add_path(
+        &self,
+        path: &str,
+        create_parents: bool,
+    ) -> RedisResult<Option<(Vec<String>, Vec<String>)>> {
+        let mut query = compile(path)?;
+        if query.is_projection() {
+            return Err(err_projection_readonly());
+        }
+        if !query.is_static() {
+            return Err(RedisError::Str("Err wrong static path"));
+        }
+
+        let size = query.size();
+        let mut missing = Vec::new();
+        for depth in (0..size).rev() {
+            // The ancestor `depth` elements below the root, and the key of its missing child
+            let mut ancestor = compile(path)?;
+            let mut last = None;
+            for _ in depth..size {
+                last = ancestor.pop_last();
+            }
+            let Some((key, JsonPathToken::String)) = last else {
+                // An array element can't be added by key
+                return Ok(None);
+            };
+            missing.push(key);
+            if let Some(found) = calc_once_with_paths(ancestor, self.val.as_ref()).pop() {
+                if found.res.get_type() != SelectValueType::Object {
+                    return Ok(None);
+                }
+                missing.reverse();
+                // SAFETY: `calc_once_with_paths` is guaranteed to return a path tracker
+                let found = found.path_tracker.unwrap().to_string_path();
+                return Ok(Some((found, missing)));
+            }
+            if !create_parents {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
     pub fn to_string_single(&self, path: &str, format: &ReplyFormatOptions) -> RedisResult<String> {
         let query = compile(path)?;
         // A projection (incl. a legacy path that normalizes to one, e.g. `a + 1` -> `$.a + 1`)
@@ -500,10 +553,12 @@ impl<'a, V: SelectValue + 'a> KeyValue<'a, V> {
         }
     }
 
-    pub fn str_len(&self, path: &str) -> RedisResult<usize> {
+    pub fn str_len(&self, path: &str, unit: StrUnit) -> RedisResult<usize> {
         let first = self.get_first(path)?;
         match first.get_type() {
-            SelectValueType::String => Ok(first.get_str().ok_or_else(|| err_json("string"))?.len()),
+            SelectValueType::String => {
+                Ok(unit.len(first.as_str().ok_or_else(|| err_json("string"))?))
+            }
             _ => Err(err_json("string")),
         }
     }
@@ -526,66 +581,229 @@ impl<'a, V: SelectValue + 'a> KeyValue<'a, V> {
         }
     }
 
-    pub fn arr_index(&self, path: &str, json_value: Value, start: i64, end: i64) -> RedisResult {
+    pub fn arr_index(&self, path: &str, query: &ArrIndexQuery) -> RedisResult {
         let res = self
             .get_values(path)?
             .into_iter()
             .map(|value| {
-                RedisValue::from(Self::arr_first_index_single(
-                    value.as_ref(),
-                    &json_value,
-                    start,
-                    end,
-                ))
+                let found = Self::arr_indices_single(value.as_ref(), query)?;
+                Ok(query.reply(found))
             })
-            .collect_vec();
+            .collect::<RedisResult<Vec<RedisValue>>>()?;
         Ok(res.into())
     }
 
-    pub fn arr_index_legacy(
-        &self,
-        path: &str,
-        json_value: Value,
-        start: i64,
-        end: i64,
-    ) -> RedisResult {
+    pub fn arr_index_legacy(&self, path: &str, query: &ArrIndexQuery) -> RedisResult {
         let arr = self.get_first(path)?;
-        match Self::arr_first_index_single(arr.as_ref(), &json_value, start, end) {
-            FoundIndex::NotArray => Err(err_json("array")),
-            i => Ok(i.into()),
+        match Self::arr_indices_single(arr.as_ref(), query)? {
+            None => Err(err_json("array")),
+            found => Ok(query.reply(found)),
         }
     }
 
-    /// Returns first array index of `v` in `arr`, or `NotFound` if not found in `arr`, or `NotArray` if `arr` is not an array
-    fn arr_first_index_single(arr: &V, v: &Value, start: i64, end: i64) -> FoundIndex {
+    /// Returns the indices of the elements of `arr` matching `query`, in the order they were
+    /// found and up to the requested count, or `None` if `arr` is not an array
+    fn arr_indices_single(arr: &V, query: &ArrIndexQuery) -> RedisResult<Option<Vec<i64>>> {
         if !arr.is_array() {
-            return FoundIndex::NotArray;
+            return Ok(None);
         }
 
         let Some(len_u) = arr.len() else {
-            return FoundIndex::NotArray;
+            return Ok(None);
         };
         let len = len_u as i64;
         if len == 0 {
-            return FoundIndex::NotFound;
+            return Ok(Some(vec![]));
         }
         // end=0 means INFINITY to support backward with RedisJSON
-        let (start, end) = normalize_arr_indices(start, end, len);
+        let (start, end) = normalize_arr_indices(query.start, query.end, len);
 
         if end < start {
             // don't search at all
-            return FoundIndex::NotFound;
+            return Ok(Some(vec![]));
         }
 
-        for index in start..end {
-            let Some(value) = arr.get_index(index as usize) else {
-                return FoundIndex::NotFound;
+        let limit = match query.count {
+            None => 1,
+            Some(0) => usize::MAX,
+            Some(n) => n,
+        };
+        let matches = match &query.matcher {
+            ArrIndexMatcher::Value(v) => {
+                let range = if query.from_end {
+                    Either::Left((start..end).rev())
+                } else {
+                    Either::Right(start..end)
+                };
+                range
+                    .filter(|&index| {
+                        arr.get_index(index as usize)
+                            .is_some_and(|value| is_equal(value.as_ref(), v))
+                    })
+                    .take(limit)
+                    .collect()
+            }
+            ArrIndexMatcher::Filter(filter) => {
+                // Let the filter select the elements, then keep those in range
+                let selected = create_with_generator(filter)
+                    .calc_paths(arr)
+                    .into_iter()
+                    .filter_map(|p| p.first().and_then(|i| i.parse::<i64>().ok()))
+                    .filter(|index| (start..end).contains(index));
+                if query.from_end {
+                    selected
+                        .collect_vec()
+                        .into_iter()
+                        .rev()
+                        .take(limit)
+                        .collect()
+                } else {
+                    selected.take(limit).collect()
+                }
+            }
+        };
+        Ok(Some(matches))
+    }
+
+    /// Returns the index at which `v` should be inserted to keep `arr` sorted, after any
+    /// element that compares equal to it, or `None` if `unique` is set and `arr` already holds
+    /// an element equal to `v`. `arr` is assumed to be sorted by `by` (the element itself when
+    /// `None`), ascending unless `desc` is set.
+    pub fn arr_insort_index(
+        arr: &V,
+        v: &Value,
+        by: Option<&Query>,
+        desc: bool,
+        unique: bool,
+    ) -> Option<usize> {
+        let key = sort_key(v, by);
+        let cmp = |index: usize| {
+            arr.get_index(index).map_or(Ordering::Less, |e| {
+                let ord = compare_values(sort_key(e.as_ref(), by).as_ref(), key.as_ref());
+                if desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            })
+        };
+
+        let (mut lo, mut hi) = (0, arr.len().unwrap_or_default());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if cmp(mid).is_gt() {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+
+        // Equal values share their sort key, so they are all in the run that ends at `lo`
+        let duplicate = unique
+            && (0..lo)
+                .rev()
+                .take_while(|&i| cmp(i).is_eq())
+                .any(|i| arr.get_index(i).is_some_and(|e| is_equal(e.as_ref(), v)));
+        (!duplicate).then_some(lo)
+    }
+
+    /// Returns the index of the first element of `arr` holding a value equal to `v` at `by`
+    pub fn arr_position_by(arr: &V, by: &Query, v: &Value) -> Option<usize> {
+        arr.values()?.position(|e| {
+            create(by)
+                .calc(e.as_ref())
+                .into_iter()
+                .next()
+                .is_some_and(|key| is_equal(key.as_ref(), v))
+        })
+    }
+
+    /// Returns which elements of `arr` to keep so that no two kept elements are equal (or have
+    /// equal values at `by`), keeping the first of each group or, with `keep_last`, the last.
+    /// The elements with no value at `by` are all kept
+    pub fn arr_unique_mask(arr: &V, by: Option<&Query>, keep_last: bool) -> Vec<bool> {
+        let elements: Vec<ValueRef<'_, V>> = arr.values().map_or_else(Vec::new, |it| it.collect());
+        let keys = elements
+            .iter()
+            .map(|e| match by {
+                Some(by) => create(by).calc(e.as_ref()).into_iter().next(),
+                None => Some(ValueRef::Borrowed(e.as_ref())),
+            })
+            .collect_vec();
+
+        let mut keep = vec![false; keys.len()];
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        let order = if keep_last {
+            Either::Left((0..keys.len()).rev())
+        } else {
+            Either::Right(0..keys.len())
+        };
+        for i in order {
+            let Some(key) = &keys[i] else {
+                keep[i] = true;
+                continue;
             };
-            if is_equal(value.as_ref(), v) {
-                return FoundIndex::Index(index);
+            let mut hasher = DefaultHasher::new();
+            hash_value(key.as_ref(), &mut hasher);
+            let bucket = seen.entry(hasher.finish()).or_default();
+            if !bucket.iter().any(|&j| {
+                keys[j]
+                    .as_ref()
+                    .is_some_and(|other| is_equal(other.as_ref(), key.as_ref()))
+            }) {
+                bucket.push(i);
+                keep[i] = true;
             }
         }
+        keep
+    }
+}
+
+/// Returns the value `by` (a path relative to `v`) points to, the value itself when `by` is
+/// `None`, or null when the path does not exist in `v`
+fn sort_key<'a, T: SelectValue>(v: &'a T, by: Option<&Query>) -> ValueRef<'a, T> {
+    let Some(by) = by else {
+        return ValueRef::Borrowed(v);
+    };
+    create(by)
+        .calc(v)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| ValueRef::Owned(T::default()))
+}
 
-        FoundIndex::NotFound
+/// What JSON.ARRINDEX looks for in an array, and how many of the matches it reports
+pub struct ArrIndexQuery<'a> {
+    pub matcher: ArrIndexMatcher<'a>,
+    pub start: i64,
+    pub end: i64,
+    /// Search from the end of the range, so the last matches are reported first
+    pub from_end: bool,
+    /// Reply with an array of up to `count` indices (0 for all of them) instead of a single index
+    pub count: Option<usize>,
+}
+
+pub enum ArrIndexMatcher<'a> {
+    /// Elements equal to the value
+    Value(Value),
+    /// Elements selected by a JSONPath filter expression such as `?(@.status=="open")`,
+    /// compiled as the lone selector of `$[...]`
+    Filter(Query<'a>),
+}
+
+impl ArrIndexQuery<'_> {
+    fn reply(&self, found: Option<Vec<i64>>) -> RedisValue {
+        match (found, self.count) {
+            (None, _) => FoundIndex::NotArray.into(),
+            (Some(indices), Some(_)) => indices
+                .into_iter()
+                .map(RedisValue::Integer)
+                .collect_vec()
+                .into(),
+            (Some(indices), None) => indices
+                .first()
+                .map_or(FoundIndex::NotFound, |&i| FoundIndex::Index(i))
+                .into(),
+        }
     }
 }
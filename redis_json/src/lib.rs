@@ -39,27 +39,42 @@ use crate::c_api::{
 };
 
 use crate::commands::{
-    json_arr_append_command_impl, json_arr_index_command_impl, json_arr_insert_command_impl,
+    json_arr_append_command_impl, json_arr_bpop_command_impl, json_arr_bpop_on_keys_impl,
+    json_arr_index_command_impl, json_arr_insert_command_impl, json_arr_insort_command_impl,
     json_arr_len_command_impl, json_arr_pop_command_impl, json_arr_trim_command_impl,
-    json_bool_toggle_command_impl, json_clear_command_impl, json_debug_command_impl,
-    json_del_command_impl, json_get_command_impl, json_merge_command_impl, json_mget_command_impl,
-    json_mset_command_impl, json_num_incrby_command_impl, json_num_multby_command_impl,
-    json_num_powby_command_impl, json_obj_keys_command_impl, json_obj_len_command_impl,
-    json_resp_command_impl, json_set_command_impl, json_str_append_command_impl,
-    json_str_len_command_impl, json_type_command_impl,
+    json_arr_unique_command_impl, json_arr_upsert_command_impl, json_bool_toggle_command_impl,
+    json_clear_command_impl, json_count_command_impl, json_debug_command_impl,
+    json_del_command_impl, json_exists_command_impl, json_expire_command_impl,
+    json_flatten_command_impl, json_fromhash_command_impl, json_get_command_impl,
+    json_getdel_command_impl, json_getset_command_impl, json_load_command_impl,
+    json_merge_command_impl, json_mget_command_impl, json_mset_command_impl,
+    json_num_incrby_command_impl, json_num_multby_command_impl, json_num_powby_command_impl,
+    json_obj_keys_command_impl, json_obj_len_command_impl, json_paths_command_impl,
+    json_persist_command_impl, json_resp_command_impl, json_set_command_impl,
+    json_str_append_command_impl, json_str_get_range_command_impl, json_str_len_command_impl,
+    json_str_prepend_command_impl, json_str_set_range_command_impl, json_tohash_command_impl,
+    json_ttl_command_impl, json_type_command_impl, json_unflatten_command_impl,
+    json_wait_command_impl, json_wait_on_keys_impl,
 };
 use crate::redisjson::Format;
 
 mod array_index;
 mod backward;
 pub mod c_api;
+pub mod cdc;
 pub mod commands;
 pub mod defrag;
+pub mod effects;
+pub mod expiry;
+mod flat;
 mod formatter;
 pub mod ivalue_manager;
 mod key_value;
+mod load;
 pub mod manager;
+pub mod notify;
 pub mod redisjson;
+mod wait;
 
 pub const GIT_SHA: Option<&str> = std::option_env!("GIT_SHA");
 pub const GIT_BRANCH: Option<&str> = std::option_env!("GIT_BRANCH");
@@ -84,9 +99,9 @@ pub static REDIS_JSON_TYPE: RedisType = RedisType::new(
         digest: None,
 
         // Auxiliary data (v2)
-        aux_load: None,
+        aux_load: Some(expiry::aux_load),
         aux_save: None,
-        aux_save_triggers: 0,
+        aux_save_triggers: expiry::AUX_SAVE_TRIGGERS,
 
         free_effort: None,
         unlink: None,
@@ -97,7 +112,7 @@ pub static REDIS_JSON_TYPE: RedisType = RedisType::new(
         unlink2: None,
         copy2: None,
         mem_usage2: None,
-        aux_save2: None,
+        aux_save2: Some(expiry::aux_save),
     },
 );
 /////////////////////////////////////////////////////
@@ -129,6 +144,7 @@ macro_rules! run_on_manager {
             None => {
                 let mngr = $crate::ivalue_manager::RedisIValueJsonKeyManager {
                     phantom: PhantomData,
+                    patches: Default::default(),
                 };
                 $run_expr(mngr)
             }
@@ -210,12 +226,97 @@ macro_rules! redis_json_module_create {
             }
         );
 
+        json_getdel_command!(
+            pub fn json_getdel(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_getdel_command_impl)(ctx, args)
+            }
+        );
+
+        json_getset_command!(
+            pub fn json_getset(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_getset_command_impl)(ctx, args)
+            }
+        );
+
         json_mget_command!(
             pub fn json_mget(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
                 json_command!(json_mget_command_impl)(ctx, args)
             }
         );
 
+        json_exists_command!(
+            pub fn json_exists(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_exists_command_impl)(ctx, args)
+            }
+        );
+
+        json_count_command!(
+            pub fn json_count(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_count_command_impl)(ctx, args)
+            }
+        );
+
+        json_paths_command!(
+            pub fn json_paths(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_paths_command_impl)(ctx, args)
+            }
+        );
+
+        json_flatten_command!(
+            pub fn json_flatten(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_flatten_command_impl)(ctx, args)
+            }
+        );
+
+        json_unflatten_command!(
+            pub fn json_unflatten(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_unflatten_command_impl)(ctx, args)
+            }
+        );
+
+        json_fromhash_command!(
+            pub fn json_fromhash(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_fromhash_command_impl)(ctx, args)
+            }
+        );
+
+        json_tohash_command!(
+            pub fn json_tohash(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_tohash_command_impl)(ctx, args)
+            }
+        );
+
+        json_load_command!(
+            pub fn json_load(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_load_command_impl)(ctx, args)
+            }
+        );
+
+        json_expire_command!(
+            pub fn json_expire(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_expire_command_impl)(ctx, args)
+            }
+        );
+
+        json_ttl_command!(
+            pub fn json_ttl(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_ttl_command_impl)(ctx, args)
+            }
+        );
+
+        json_persist_command!(
+            pub fn json_persist(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_persist_command_impl)(ctx, args)
+            }
+        );
+
+        json_patch_command!(
+            pub fn json_patch(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                // Effects are written by the default manager only
+                $crate::effects::json_patch_command_impl(ctx, args)
+            }
+        );
+
         json_type_command!(
             pub fn json_type(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
                 json_command!(json_type_command_impl)(ctx, args)
@@ -266,6 +367,24 @@ macro_rules! redis_json_module_create {
             }
         );
 
+        json_strprepend_command!(
+            pub fn json_strprepend(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_str_prepend_command_impl)(ctx, args)
+            }
+        );
+
+        json_strsetrange_command!(
+            pub fn json_strsetrange(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_str_set_range_command_impl)(ctx, args)
+            }
+        );
+
+        json_strgetrange_command!(
+            pub fn json_strgetrange(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_str_get_range_command_impl)(ctx, args)
+            }
+        );
+
         json_strlen_command!(
             pub fn json_strlen(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
                 json_command!(json_str_len_command_impl)(ctx, args)
@@ -290,6 +409,24 @@ macro_rules! redis_json_module_create {
             }
         );
 
+        json_arrinsort_command!(
+            pub fn json_arrinsort(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_arr_insort_command_impl)(ctx, args)
+            }
+        );
+
+        json_arrunique_command!(
+            pub fn json_arrunique(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_arr_unique_command_impl)(ctx, args)
+            }
+        );
+
+        json_arrupsert_command!(
+            pub fn json_arrupsert(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_arr_upsert_command_impl)(ctx, args)
+            }
+        );
+
         json_arrlen_command!(
             pub fn json_arrlen(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
                 json_command!(json_arr_len_command_impl)(ctx, args)
@@ -302,6 +439,76 @@ macro_rules! redis_json_module_create {
             }
         );
 
+        extern "C" fn json_arrbpop_on_keys(
+            ctx: *mut rawmod::RedisModuleCtx,
+            argv: *mut *mut rawmod::RedisModuleString,
+            argc: c_int,
+        ) -> c_int {
+            let context = Context::new(ctx);
+            let args = redis_module::decode_args(ctx, argv, argc);
+            let served = (|| {
+                run_on_manager!(
+                    pre_command: ||$pre_command_function_expr(&context, &args),
+                    get_manage: {
+                        $( $condition => $manager_ident { $($field: $value),* } ),*
+                        _ => $default_manager
+                    },
+                    run: |mngr|json_arr_bpop_on_keys_impl(mngr, &context, args.clone()),
+                )
+            })();
+            // Not serving the client keeps it blocked until the next signal or the timeout
+            served.map_or(Status::Err as c_int, |reply| context.reply(reply) as c_int)
+        }
+
+        fn json_arr_bpop_blocking<M: $crate::manager::Manager>(
+            manager: M,
+            ctx: &Context,
+            args: Vec<RedisString>,
+        ) -> RedisResult {
+            json_arr_bpop_command_impl(manager, ctx, args, Some(json_arrbpop_on_keys))
+        }
+
+        json_arrbpop_command!(
+            pub fn json_arrbpop(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_arr_bpop_blocking)(ctx, args)
+            }
+        );
+
+        extern "C" fn json_wait_on_keys(
+            ctx: *mut rawmod::RedisModuleCtx,
+            argv: *mut *mut rawmod::RedisModuleString,
+            argc: c_int,
+        ) -> c_int {
+            let context = Context::new(ctx);
+            let args = redis_module::decode_args(ctx, argv, argc);
+            let served = (|| {
+                run_on_manager!(
+                    pre_command: ||$pre_command_function_expr(&context, &args),
+                    get_manage: {
+                        $( $condition => $manager_ident { $($field: $value),* } ),*
+                        _ => $default_manager
+                    },
+                    run: |mngr|json_wait_on_keys_impl(mngr, &context, args.clone()),
+                )
+            })();
+            // Not serving the client keeps it blocked until the next signal or the timeout
+            served.map_or(Status::Err as c_int, |reply| context.reply(reply) as c_int)
+        }
+
+        fn json_wait_blocking<M: $crate::manager::Manager<V = ijson::IValue>>(
+            manager: M,
+            ctx: &Context,
+            args: Vec<RedisString>,
+        ) -> RedisResult {
+            json_wait_command_impl(manager, ctx, args, Some(json_wait_on_keys))
+        }
+
+        json_wait_command!(
+            pub fn json_wait(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
+                json_command!(json_wait_blocking)(ctx, args)
+            }
+        );
+
         json_arrtrim_command!(
             pub fn json_arrtrim(ctx: &Context, args: Vec<RedisString>) -> RedisResult {
                 json_command!(json_arr_trim_command_impl)(ctx, args)
@@ -381,6 +588,8 @@ macro_rules! redis_json_module_create {
             // Mirror Redis core's `hide-user-data-from-log` so our trace logs
             // redact user data whenever the server is configured to hide it.
             $crate::sync_hide_user_data_from_log(ctx);
+            $crate::expiry::start_active_cycle(ctx);
+            $crate::expiry::subscribe_to_key_moves(ctx);
             $init_func(ctx, args)
         }
 
@@ -394,6 +603,52 @@ macro_rules! redis_json_module_create {
                 args_map.insert(args[i].to_string_lossy(), args[i + 1].to_string_lossy());
             }
 
+            if let Some(value) = args_map.get("NOTIFY_PATHS") {
+                match value.to_ascii_lowercase().as_str() {
+                    "yes" => $crate::notify::set_notify_paths(true),
+                    "no" => $crate::notify::set_notify_paths(false),
+                    _ => {
+                        ctx.log(RedisLogLevel::Warning, "NOTIFY_PATHS must be yes or no");
+                        return Status::Err;
+                    }
+                }
+            }
+
+            if let Some(value) = args_map.get("REPLICATE_EFFECTS") {
+                match value.to_ascii_lowercase().as_str() {
+                    "yes" => $crate::effects::set_replicate_effects(true),
+                    "no" => $crate::effects::set_replicate_effects(false),
+                    _ => {
+                        ctx.log(RedisLogLevel::Warning, "REPLICATE_EFFECTS must be yes or no");
+                        return Status::Err;
+                    }
+                }
+            }
+
+            if let Some(pattern) = args_map.get("CDC_PATTERN") {
+                let stream = args_map
+                    .get("CDC_STREAM")
+                    .map_or($crate::cdc::DEFAULT_STREAM, String::as_str);
+                let values = match args_map.get("CDC_VALUES").map(|v| v.to_ascii_lowercase()) {
+                    None => false,
+                    Some(v) if v == "no" => false,
+                    Some(v) if v == "yes" => true,
+                    Some(_) => {
+                        ctx.log(RedisLogLevel::Warning, "CDC_VALUES must be yes or no");
+                        return Status::Err;
+                    }
+                };
+                // The per-slot streams are named by adding a hash tag to it
+                if ctx.get_flags().contains(redis_module::ContextFlags::CLUSTER)
+                    && stream.contains(['{', '}'])
+                {
+                    ctx.log(RedisLogLevel::Warning, "CDC_STREAM can't have braces in cluster mode");
+                    return Status::Err;
+                }
+                $crate::cdc::enable(pattern, stream, values);
+                ctx.log_notice(&format!("Capturing the writes to {pattern} into {stream}"));
+            }
+
             Status::Ok
         }
 
@@ -530,6 +785,7 @@ redis_json_module_create! {
     get_manage: {
     _ => Some(crate::ivalue_manager::RedisIValueJsonKeyManager {
         phantom: PhantomData,
+        patches: Default::default(),
     })
     },
     version: version(),
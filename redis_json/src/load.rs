@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Key templates of JSON.LOAD, and the cluster slots of the keys they build.
+//!
+//! A key template is literal text with fields such as `{$.id}`, each a JSONPath evaluated on
+//! the document stored under the key. A `{` that isn't followed by `$` is literal, so the
+//! template can carry a hash tag, as in `{users}:{$.id}`.
+
+use json_path::select_value::{SelectValue, SelectValueType};
+use json_path::{calc_once, compile};
+use redis_module::{raw, RedisError, RedisResult, RedisString};
+
+#[derive(Debug, PartialEq, Eq)]
+enum TemplatePart<'a> {
+    Literal(&'a str),
+    Field(&'a str),
+}
+
+#[derive(Debug)]
+pub struct KeyTemplate<'a> {
+    parts: Vec<TemplatePart<'a>>,
+}
+
+impl<'a> KeyTemplate<'a> {
+    pub fn parse(template: &'a str) -> RedisResult<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{$") {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(&rest[..start]));
+            }
+            let end = start
+                + 1
+                + field_len(&rest[start + 1..])
+                    .ok_or(RedisError::Str("ERR unterminated key template field"))?;
+            let path = &rest[start + 1..end];
+            if compile(path)?.is_projection() {
+                return Err(RedisError::Str(
+                    "ERR key template fields can't be projections",
+                ));
+            }
+            parts.push(TemplatePart::Field(path));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest));
+        }
+        if !parts.iter().any(|p| matches!(p, TemplatePart::Field(_))) {
+            return Err(RedisError::Str("ERR key template must have a field"));
+        }
+        Ok(KeyTemplate { parts })
+    }
+
+    /// Builds the key of `doc`, each field matching a single string, number or boolean
+    pub fn render<T: SelectValue>(&self, doc: &T) -> RedisResult<String> {
+        let mut key = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => key.push_str(literal),
+                TemplatePart::Field(path) => {
+                    let err = |expected: &str| {
+                        RedisError::String(format!(
+                            "ERR key template field {path} must match {expected}"
+                        ))
+                    };
+                    let values = calc_once(compile(path)?, doc);
+                    let [value] = values.as_slice() else {
+                        return Err(err("a single value"));
+                    };
+                    match value.get_type() {
+                        SelectValueType::String => key.push_str(value.as_str().unwrap_or_default()),
+                        SelectValueType::Long | SelectValueType::Double | SelectValueType::Bool => {
+                            key.push_str(&serde_json::to_string(value.as_ref())?);
+                        }
+                        _ => return Err(err("a string, a number or a boolean")),
+                    }
+                }
+            }
+        }
+        Ok(key)
+    }
+}
+
+/// The length of the JSONPath at the start of `s`, up to the `}` that closes its field. A `}`
+/// in a quoted member name doesn't close it
+fn field_len(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '}') => return Some(i),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// The cluster hash slot of `key`
+pub fn key_slot(key: &RedisString) -> RedisResult<u32> {
+    let cluster_key_slot = raw::RedisModule_ClusterKeySlot
+        .ok_or(RedisError::Str("ERR cluster key slots are not available"))?;
+    Ok(unsafe { cluster_key_slot(key.inner) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_template() {
+        let doc = json!({"id": 42, "name": "a}b", "tags": ["x", "y"], "o": {"k}": true}});
+        let render = |template: &str| KeyTemplate::parse(template).unwrap().render(&doc);
+        assert_eq!(render("user:{$.id}").unwrap(), "user:42");
+        assert_eq!(render("{users}:{$.name}:{$.id}").unwrap(), "{users}:a}b:42");
+        assert_eq!(render("{$['o']['k}']}").unwrap(), "true");
+        assert!(render("user:{$.tags}").is_err());
+        assert!(render("user:{$.tags[*]}").is_err());
+        assert!(render("user:{$.nosuch}").is_err());
+
+        assert!(KeyTemplate::parse("user:{$.id").is_err());
+        assert!(KeyTemplate::parse("user:{id}").is_err());
+        assert!(KeyTemplate::parse("user:{$.id + 1}").is_err());
+    }
+}
@@ -16,6 +16,8 @@ use redis_module::raw::RedisModuleKey;
 use redis_module::RedisError;
 use redis_module::{Context, RedisResult, RedisString};
 
+use crate::expiry::MemberExpiry;
+use crate::redisjson::StrUnit;
 use crate::Format;
 
 pub struct SetUpdateInfo {
@@ -34,11 +36,14 @@ pub enum UpdateInfo {
 
 pub trait ReadHolder<V: SelectValue> {
     fn get_value(&self) -> RedisResult<Option<&V>>;
+    fn member_expiry(&self) -> RedisResult<Option<&MemberExpiry>>;
 }
 
 pub trait WriteHolder<O: Clone, V: SelectValue> {
     fn delete(&mut self) -> RedisResult<()>;
+    fn set_expire_at(&mut self, at: i64) -> RedisResult<()>;
     fn get_value(&mut self) -> RedisResult<Option<&mut V>>;
+    fn member_expiry(&mut self) -> RedisResult<Option<&mut MemberExpiry>>;
     fn set_value(&mut self, path: Vec<String>, v: O) -> RedisResult<bool>;
     fn merge_value(&mut self, path: Vec<String>, v: O) -> RedisResult<bool>;
     fn dict_add(&mut self, path: Vec<String>, key: &str, v: O) -> RedisResult<bool>;
@@ -48,6 +53,14 @@ pub trait WriteHolder<O: Clone, V: SelectValue> {
     fn pow_by(&mut self, path: Vec<String>, num: &str) -> RedisResult<Number>;
     fn bool_toggle(&mut self, path: Vec<String>) -> RedisResult<bool>;
     fn str_append(&mut self, path: Vec<String>, val: String) -> RedisResult<usize>;
+    fn str_prepend(&mut self, path: Vec<String>, val: String) -> RedisResult<usize>;
+    fn str_set_range(
+        &mut self,
+        path: Vec<String>,
+        offset: usize,
+        val: String,
+        unit: StrUnit,
+    ) -> RedisResult<usize>;
     fn arr_append(&mut self, path: Vec<String>, args: Vec<O>) -> RedisResult<usize>;
     fn arr_insert(&mut self, path: Vec<String>, args: &[O], index: i64) -> RedisResult<usize>;
     fn arr_pop<C: FnOnce(Option<&V>) -> RedisResult>(
@@ -57,6 +70,7 @@ pub trait WriteHolder<O: Clone, V: SelectValue> {
         serialize_callback: C,
     ) -> RedisResult;
     fn arr_trim(&mut self, path: Vec<String>, start: i64, stop: i64) -> RedisResult<usize>;
+    fn arr_retain(&mut self, path: Vec<String>, keep: &[bool]) -> RedisResult<usize>;
     fn clear(&mut self, path: Vec<String>) -> RedisResult<usize>;
     fn notify_keyspace_event(&mut self, ctx: &Context, command: &str) -> RedisResult<()>;
 }
@@ -81,6 +95,9 @@ pub trait Manager {
     fn get_value_from_handle(&self, key: *mut RedisModuleKey) -> *const Self::V;
     fn open_key_write(&self, ctx: &Context, key: RedisString) -> RedisResult<Self::WriteHolder>;
     fn apply_changes(&self, ctx: &Context);
+    /// Same as [`Self::apply_changes`], replicating the command as `command` with `args`
+    /// rather than as it was called
+    fn apply_changes_as(&self, ctx: &Context, command: &str, args: &[&[u8]]);
     #[allow(clippy::wrong_self_convention)]
     fn from_str(
         &self,
@@ -89,6 +106,8 @@ pub trait Manager {
         limit_depth: bool,
         fpha_type: Option<FloatType>,
     ) -> RedisResult<Self::O>;
+    /// Nests `v` in objects of a single member each, keyed by `keys` from the outermost one
+    fn nest(&self, keys: &[String], v: Self::O) -> RedisResult<Self::O>;
     fn get_memory(v: &Self::V) -> RedisResult<usize>;
     fn is_json(&self, key: *mut RedisModuleKey) -> RedisResult<bool>;
 }
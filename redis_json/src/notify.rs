@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Notifications of the paths touched by writes.
+//!
+//! With the `NOTIFY_PATHS yes` module argument, each write is followed by one keyspace event
+//! per normalized path it touched, named after the event of the write, e.g.
+//! `json.set:$['a'][0]`. Modules subscribed through the shared API get the same paths,
+//! whether or not these events are enabled.
+
+use itertools::Itertools;
+use json_path::json_path::{PTracker, UserPathTracker};
+use json_path::select_value::{SelectValue, SelectValueType};
+use libc::size_t;
+use redis_module::raw as rawmod;
+use redis_module::{Context, NotifyEvent, RedisString};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Called after each write to a JSON key with the event of the write, the key, and the
+/// normalized paths the write touched
+pub type PathEventCallback = extern "C" fn(
+    ctx: *mut rawmod::RedisModuleCtx,
+    event: *const c_char,
+    key: *mut rawmod::RedisModuleString,
+    paths: *const *const c_char,
+    len: size_t,
+);
+
+static NOTIFY_PATHS: AtomicBool = AtomicBool::new(false);
+static SUBSCRIBERS: Mutex<Vec<PathEventCallback>> = Mutex::new(Vec::new());
+
+/// Enables or disables the keyspace events of the touched paths
+pub fn set_notify_paths(enabled: bool) {
+    NOTIFY_PATHS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn subscribe(callback: PathEventCallback) {
+    SUBSCRIBERS.lock().unwrap().push(callback);
+}
+
+/// Notifies the `paths` touched by a write to `key` notified as `event`. `doc` is the document
+/// after the write, telling array indices apart from member names
+pub fn notify_paths<T: SelectValue>(
+    ctx: &Context,
+    event: &str,
+    key: &RedisString,
+    doc: Option<&T>,
+    paths: &[Vec<String>],
+) {
+    let notify_paths = NOTIFY_PATHS.load(Ordering::Relaxed);
+    let subscribers = SUBSCRIBERS.lock().unwrap().clone();
+    if paths.is_empty() || (!notify_paths && subscribers.is_empty()) {
+        return;
+    }
+
+    let paths = paths
+        .iter()
+        .map(|path| normalized_path(doc, path))
+        .unique()
+        .collect_vec();
+    if notify_paths {
+        for path in &paths {
+            ctx.notify_keyspace_event(NotifyEvent::MODULE, &format!("{event}:{path}"), key);
+        }
+    }
+    if !subscribers.is_empty() {
+        // A normalized path escapes control characters, so it has no NUL
+        let event = CString::new(event).unwrap_or_default();
+        let paths = paths
+            .into_iter()
+            .map(|path| CString::new(path).unwrap_or_default())
+            .collect_vec();
+        let ptrs = paths.iter().map(|path| path.as_ptr()).collect_vec();
+        for callback in subscribers {
+            callback(
+                ctx.ctx,
+                event.as_ptr(),
+                key.inner,
+                ptrs.as_ptr(),
+                ptrs.len(),
+            );
+        }
+    }
+}
+
+/// The normalized path of `path`, whose elements are array indices where `doc` has arrays.
+/// Elements below the values missing from `doc`, such as deleted ones, are member names
+pub fn normalized_path<T: SelectValue>(doc: Option<&T>, path: &[String]) -> String {
+    fn walk<T: SelectValue>(node: Option<&T>, path: &[String], tracker: &mut PTracker) {
+        let Some((element, rest)) = path.split_first() else {
+            return;
+        };
+        let index = node
+            .filter(|node| node.get_type() == SelectValueType::Array)
+            .and_then(|_| element.parse().ok());
+        let child = match index {
+            Some(index) => {
+                tracker.add_index(index);
+                node.and_then(|node| node.get_index(index))
+            }
+            None => {
+                tracker.add_str(element);
+                node.and_then(|node| node.get_key(element))
+            }
+        };
+        walk(child.as_deref(), rest, tracker);
+    }
+
+    let mut tracker = PTracker {
+        elements: Vec::new(),
+    };
+    walk(doc, path, &mut tracker);
+    tracker.to_normalized_path()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalized_path() {
+        let doc = json!({"a": [{"b": 1}, 2], "0": {"1": true}});
+        let path = |elements: &[&str]| elements.iter().map(ToString::to_string).collect_vec();
+        assert_eq!(normalized_path(Some(&doc), &[]), "$");
+        assert_eq!(
+            normalized_path(Some(&doc), &path(&["a", "0", "b"])),
+            "$['a'][0]['b']"
+        );
+        assert_eq!(
+            normalized_path(Some(&doc), &path(&["0", "1"])),
+            "$['0']['1']"
+        );
+        // A deleted array element is still an index, its parent being an array
+        assert_eq!(normalized_path(Some(&doc), &path(&["a", "5"])), "$['a'][5]");
+        assert_eq!(
+            normalized_path(Some(&doc), &path(&["x", "5"])),
+            "$['x']['5']"
+        );
+        assert_eq!(
+            normalized_path::<serde_json::Value>(None, &path(&["a"])),
+            "$['a']"
+        );
+    }
+}
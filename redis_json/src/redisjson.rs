@@ -18,9 +18,11 @@ use redis_module::{raw, RedisError};
 use std::os::raw::{c_int, c_void};
 
 use crate::backward;
+use crate::expiry::MemberExpiry;
 use crate::ivalue_manager::RedisIValueJsonKeyManager;
 use crate::manager::Manager;
 use serde::Serialize;
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Display;
 use std::marker::PhantomData;
@@ -51,7 +53,7 @@ pub fn normalize_arr_indices(start: i64, end: i64, len: i64) -> (i64, i64) {
     (start, end)
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SetOptions {
     NotExists,
     AlreadyExists,
@@ -59,7 +61,7 @@ pub enum SetOptions {
     None,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     STRING,
     JSON,
@@ -99,6 +101,105 @@ impl FromStr for ReplyFormat {
     }
 }
 
+/// Unit string offsets and lengths are counted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrUnit {
+    Bytes,
+    CodePoints,
+    /// UTF-16 code units, the unit of JavaScript's `String.prototype.length`
+    Utf16,
+}
+impl FromStr for StrUnit {
+    type Err = RedisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "BYTES" => Ok(Self::Bytes),
+            "CODEPOINTS" => Ok(Self::CodePoints),
+            "UTF16" => Ok(Self::Utf16),
+            _ => Err(RedisError::Str("ERR wrong string unit")),
+        }
+    }
+}
+
+impl StrUnit {
+    #[must_use]
+    pub fn len(self, s: &str) -> usize {
+        match self {
+            Self::Bytes => s.len(),
+            Self::CodePoints => s.chars().count(),
+            Self::Utf16 => s.encode_utf16().count(),
+        }
+    }
+
+    /// Converts `offset`, counted in this unit, into a byte offset into `s`.
+    /// `offset` may point right past the end of `s`, but not further.
+    pub fn byte_offset(self, s: &str, offset: usize) -> Result<usize, RedisError> {
+        match self {
+            Self::Bytes if offset > s.len() => Err(RedisError::Str("ERR offset is out of range")),
+            Self::Bytes if !s.is_char_boundary(offset) => {
+                Err(RedisError::Str("ERR offset is not at a character boundary"))
+            }
+            Self::Bytes => Ok(offset),
+            Self::CodePoints => s
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(s.len()))
+                .nth(offset)
+                .ok_or(RedisError::Str("ERR offset is out of range")),
+            Self::Utf16 => {
+                let mut units = 0;
+                for (i, c) in s.char_indices() {
+                    match units.cmp(&offset) {
+                        Ordering::Less => units += c.len_utf16(),
+                        Ordering::Equal => return Ok(i),
+                        // `offset` points between the two halves of a surrogate pair
+                        Ordering::Greater => break,
+                    }
+                }
+                match units.cmp(&offset) {
+                    Ordering::Less => Err(RedisError::Str("ERR offset is out of range")),
+                    Ordering::Equal => Ok(s.len()),
+                    Ordering::Greater => {
+                        Err(RedisError::Str("ERR offset is not at a character boundary"))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the part of `s` between `start` and `end`, both inclusive and counted in this
+    /// unit, where negative offsets count from the end like in `GETRANGE`
+    pub fn substring(self, s: &str, start: i64, end: i64) -> Result<&str, RedisError> {
+        let len = self.len(s) as i64;
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let end = if end < 0 { len + end } else { end.min(len - 1) };
+        if start > end {
+            return Ok("");
+        }
+        let from = self.byte_offset(s, start as usize)?;
+        let to = self.byte_offset(s, end as usize + 1)?;
+        Ok(&s[from..to])
+    }
+
+    /// Overwrites `s` with `value` starting at `offset`, counted in this unit, extending `s` if
+    /// needed. `offset` may point right past the end of `s`, to append `value`.
+    pub fn set_range(self, s: &str, offset: usize, value: &str) -> Result<String, RedisError> {
+        let from = self.byte_offset(s, offset)?;
+        let overwritten = offset + self.len(value);
+        let to = if overwritten >= self.len(s) {
+            s.len()
+        } else {
+            self.byte_offset(s, overwritten)?
+        };
+        Ok([&s[..from], value, &s[to..]].concat())
+    }
+}
+
 ///
 /// Backwards compatibility converter for `RedisJSON` 1.x clients
 ///
@@ -229,6 +330,16 @@ impl Display for Path<'_> {
 pub struct RedisJSON<T> {
     //FIXME: make private and expose array/object Values without requiring a path
     pub data: T,
+    pub expiry: MemberExpiry,
+}
+
+impl<T> RedisJSON<T> {
+    pub fn new(data: T) -> Self {
+        RedisJSON {
+            data,
+            expiry: MemberExpiry::default(),
+        }
+    }
 }
 
 pub mod type_methods {
@@ -252,16 +363,17 @@ pub mod type_methods {
             4 => {
                 let buf = raw::load_string_buffer(rdb).ok()?;
                 let data = ijson::decode(buf.as_ref()).ok()?;
-                Some(RedisJSON { data })
+                Some(RedisJSON::new(data))
             }
             0 | 2 | 3 => {
                 let json_string = value_rdb_load_json(rdb, encver).ok()?;
                 let m = RedisIValueJsonKeyManager {
                     phantom: PhantomData,
+                    patches: Default::default(),
                 };
                 m.from_str(&json_string, Format::JSON, true, None)
                     .ok()
-                    .map(|data| RedisJSON { data })
+                    .map(RedisJSON::new)
             }
             _ => None,
         }
@@ -323,6 +435,7 @@ pub mod type_methods {
         let v = unsafe { &*value.cast::<RedisJSON<ijson::IValue>>() };
         let binary = ijson::encode(&v.data);
         raw::save_slice(rdb, &binary);
+        // The member expiries are saved apart, see expiry::aux_save
     }
 
     /// # Safety
@@ -333,7 +446,10 @@ pub mod type_methods {
         value: *const c_void,
     ) -> *mut c_void {
         let v = unsafe { &*value.cast::<RedisJSON<ijson::IValue>>() };
-        let value = v.data.clone();
+        let value = RedisJSON {
+            data: v.data.clone(),
+            expiry: v.expiry.clone(),
+        };
         Box::into_raw(Box::new(value)).cast::<c_void>()
     }
 
@@ -344,3 +460,52 @@ pub mod type_methods {
         RedisIValueJsonKeyManager::get_memory(&json.data).unwrap_or(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_str_unit_substring() {
+        let s = "h\u{e9}llo \u{1f600}!";
+        assert_eq!(StrUnit::CodePoints.len(s), 8);
+        assert_eq!(StrUnit::Bytes.len(s), 12);
+        assert_eq!(StrUnit::CodePoints.substring(s, 1, 3).unwrap(), "\u{e9}ll");
+        assert_eq!(
+            StrUnit::CodePoints.substring(s, -2, -1).unwrap(),
+            "\u{1f600}!"
+        );
+        assert_eq!(StrUnit::CodePoints.substring(s, 0, 100).unwrap(), s);
+        assert_eq!(StrUnit::CodePoints.substring(s, 5, 2).unwrap(), "");
+        assert_eq!(StrUnit::Bytes.substring(s, 0, 2).unwrap(), "h\u{e9}");
+        assert!(StrUnit::Bytes.substring(s, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_str_unit_utf16() {
+        let s = "h\u{e9}llo \u{1f600}!";
+        assert_eq!(StrUnit::Utf16.len(s), 9);
+        assert_eq!(StrUnit::Utf16.substring(s, 6, 7).unwrap(), "\u{1f600}");
+        assert_eq!(StrUnit::Utf16.substring(s, -1, -1).unwrap(), "!");
+        assert!(StrUnit::Utf16.substring(s, 6, 6).is_err());
+        assert!(StrUnit::Utf16.byte_offset(s, 10).is_err());
+        assert_eq!(StrUnit::Utf16.byte_offset(s, 9).unwrap(), s.len());
+    }
+
+    #[test]
+    fn test_str_unit_set_range() {
+        let s = "h\u{e9}llo";
+        assert_eq!(StrUnit::CodePoints.set_range(s, 1, "e").unwrap(), "hello");
+        assert_eq!(
+            StrUnit::CodePoints.set_range(s, 3, "p!!").unwrap(),
+            "h\u{e9}lp!!"
+        );
+        assert_eq!(
+            StrUnit::CodePoints.set_range(s, 5, "!").unwrap(),
+            "h\u{e9}llo!"
+        );
+        assert!(StrUnit::CodePoints.set_range(s, 6, "!").is_err());
+        assert_eq!(StrUnit::Bytes.set_range(s, 3, "L").unwrap(), "h\u{e9}Llo");
+        assert!(StrUnit::Bytes.set_range(s, 2, "e").is_err());
+    }
+}
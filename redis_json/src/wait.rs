@@ -0,0 +1,223 @@
+/*
+ * Copyright (c) 2006-Present, Redis Ltd.
+ * All rights reserved.
+ *
+ * Licensed under your choice of (a) the Redis Source Available License 2.0
+ * (RSALv2); or (b) the Server Side Public License v1 (SSPLv1); or (c) the
+ * GNU Affero General Public License v3 (AGPLv3).
+ */
+
+//! Clients blocked by JSON.WAIT.
+//!
+//! A waiting client is woken by the writes touching the values at its path: the writes changing
+//! them, and the writes at, above or below one of them, whether or not they change it. Such a
+//! write signals its key as ready, and the client is then served by the blocking callback,
+//! unless it waits with `IFNE` for its values to differ from some JSON and they still do not.
+
+use crate::key_value::KeyValue;
+use ijson::IValue;
+use json_path::json_path::UserPathTracker;
+use json_path::select_value::{is_equal, ValueRef};
+use json_path::{compile, create_with_generator};
+use redis_module::raw;
+use redis_module::{Context, RedisResult, RedisString};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::os::raw::{c_int, c_void};
+use std::rc::Rc;
+
+struct Waiter {
+    db: c_int,
+    key: Vec<u8>,
+    path: String,
+    /// The values at the path when the client blocked
+    values: Vec<IValue>,
+    woken: Cell<bool>,
+}
+
+thread_local! {
+    /// The clients blocked by JSON.WAIT by database and key, which only the main thread serves
+    static WAITERS: RefCell<HashMap<(c_int, Vec<u8>), Vec<Rc<Waiter>>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// The values at `path` in `doc`
+pub fn values_at(doc: Option<&IValue>, path: &str) -> RedisResult<Vec<IValue>> {
+    let Some(doc) = doc else {
+        return Ok(Vec::new());
+    };
+    Ok(KeyValue::new(doc)
+        .get_values(path)?
+        .iter()
+        .map(ValueRef::inner_cloned)
+        .collect())
+}
+
+/// Whether `values` differ from `json`, which they do as well when there are none
+pub fn differ(values: &[IValue], json: &IValue) -> bool {
+    values.is_empty() || values.iter().any(|v| !is_equal(v, json))
+}
+
+/// Registers a client about to block on the values at `path` in `doc`, the value of `key`,
+/// returning the private data of the blocked client, freed by [`free_waiter`]
+pub fn register(
+    ctx: &Context,
+    key: &RedisString,
+    path: &str,
+    doc: Option<&IValue>,
+) -> RedisResult<*mut c_void> {
+    let db = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+    let waiter = Rc::new(Waiter {
+        db,
+        key: key.as_slice().to_vec(),
+        path: path.to_string(),
+        values: values_at(doc, path)?,
+        woken: Cell::new(false),
+    });
+    WAITERS.with_borrow_mut(|waiters| {
+        waiters
+            .entry((db, waiter.key.clone()))
+            .or_default()
+            .push(Rc::clone(&waiter));
+    });
+    Ok(Rc::into_raw(waiter).cast_mut().cast())
+}
+
+/// Unregisters a client blocked by JSON.WAIT once it is unblocked
+pub extern "C" fn free_waiter(_ctx: *mut raw::RedisModuleCtx, privdata: *mut c_void) {
+    let waiter = unsafe { Rc::from_raw(privdata.cast_const().cast::<Waiter>()) };
+    WAITERS.with_borrow_mut(|waiters| {
+        let entry = (waiter.db, waiter.key.clone());
+        if let Some(others) = waiters.get_mut(&entry) {
+            others.retain(|other| !Rc::ptr_eq(other, &waiter));
+            if others.is_empty() {
+                waiters.remove(&entry);
+            }
+        }
+    });
+}
+
+/// Whether a write woke the client blocked by JSON.WAIT served from `ctx`
+pub fn is_woken(ctx: &Context) -> bool {
+    let privdata = unsafe { raw::RedisModule_GetBlockedClientPrivateData.unwrap()(ctx.ctx) };
+    !privdata.is_null()
+        && unsafe { &*privdata.cast_const().cast::<Waiter>() }
+            .woken
+            .get()
+}
+
+/// Wakes the clients waiting on `key` whose values are touched by a write of `paths`, `doc`
+/// being the document after the write, if any is left. Returns whether any was woken, for the
+/// key to be signaled as ready, including the ones already woken and still waiting for a
+/// difference
+pub fn on_write(
+    ctx: &Context,
+    key: &RedisString,
+    doc: Option<&IValue>,
+    paths: &[Vec<String>],
+) -> bool {
+    WAITERS.with_borrow(|waiters| {
+        if waiters.is_empty() || paths.is_empty() {
+            return false;
+        }
+        let db = unsafe { raw::RedisModule_GetSelectedDb.unwrap()(ctx.ctx) };
+        let Some(waiters) = waiters.get(&(db, key.as_slice().to_vec())) else {
+            return false;
+        };
+        let mut woken = false;
+        for waiter in waiters {
+            if touches(waiter, doc, paths) {
+                waiter.woken.set(true);
+                woken = true;
+            }
+        }
+        woken
+    })
+}
+
+fn touches(waiter: &Waiter, doc: Option<&IValue>, paths: &[Vec<String>]) -> bool {
+    // A deleted key has no value left at the path
+    let Some(doc) = doc else {
+        return true;
+    };
+    // The path was already compiled when the client blocked
+    let Ok(query) = compile(&waiter.path) else {
+        return true;
+    };
+    let matches = create_with_generator(&query).calc_with_paths(ValueRef::Borrowed(doc));
+    if matches.len() != waiter.values.len()
+        || matches
+            .iter()
+            .zip(&waiter.values)
+            .any(|(m, v)| !is_equal(m.res.as_ref(), v))
+    {
+        return true;
+    }
+    let matches = matches
+        .into_iter()
+        .filter_map(|m| Some(m.path_tracker?.to_string_path()))
+        .collect::<Vec<_>>();
+    paths.iter().any(|path| {
+        matches
+            .iter()
+            .any(|m| m.starts_with(path) || path.starts_with(m))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ivalue(json: serde_json::Value) -> IValue {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn waiter_on(path: &str, doc: &IValue) -> Waiter {
+        Waiter {
+            db: 0,
+            key: b"k".to_vec(),
+            path: path.to_string(),
+            values: values_at(Some(doc), path).unwrap(),
+            woken: Cell::new(false),
+        }
+    }
+
+    #[test]
+    fn test_values_at() {
+        let doc = ivalue(json!({"a": [1, {"b": "x"}], "c": {"b": 2}}));
+        assert_eq!(
+            values_at(Some(&doc), "$.a[*]").unwrap(),
+            [ivalue(json!(1)), ivalue(json!({"b": "x"}))]
+        );
+        assert!(values_at(Some(&doc), "$.d").unwrap().is_empty());
+        assert!(values_at(None, "$.a").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_touches() {
+        let doc = ivalue(json!({"a": [1, {"b": "x"}], "c": 2}));
+        let path = |elements: &[&str]| elements.iter().map(ToString::to_string).collect();
+        let waiter = waiter_on("$.a[*]", &doc);
+        // At, above or below the values, whether or not they change
+        assert!(touches(&waiter, Some(&doc), &[path(&["a", "1"])]));
+        assert!(touches(&waiter, Some(&doc), &[path(&[])]));
+        assert!(touches(&waiter, Some(&doc), &[path(&["a", "1", "b"])]));
+        assert!(!touches(&waiter, Some(&doc), &[path(&["c"])]));
+        // Changed by a write elsewhere
+        let changed = ivalue(json!({"a": [1]}));
+        assert!(touches(&waiter, Some(&changed), &[path(&["c"])]));
+        // Deleted along with the key, even when there was no value at the path
+        assert!(touches(&waiter, None, &[path(&[])]));
+        assert!(touches(&waiter_on("$.d", &doc), None, &[path(&[])]));
+    }
+
+    #[test]
+    fn test_differ() {
+        let one = ivalue(json!(1));
+        assert!(!differ(&[one.clone(), one.clone()], &one));
+        assert!(differ(&[one.clone(), ivalue(json!(2))], &one));
+        assert!(differ(&[ivalue(json!(1.0))], &one));
+        assert!(differ(&[], &ivalue(json!(null))));
+    }
+}
@@ -509,8 +509,52 @@ static int OpenGetWithPathCmd(RedisModuleCtx *ctx, RedisModuleString **argv, int
     return REDISMODULE_OK;
 }
 
+/* The last path event received through subscribeToPathEvents: the event, the key and the
+ * touched paths, all owned by this module. */
+static char **last_path_event = NULL;
+static size_t last_path_event_len = 0;
+
+static void free_last_path_event(void) {
+    for (size_t i = 0; i < last_path_event_len; i++) RedisModule_Free(last_path_event[i]);
+    RedisModule_Free(last_path_event);
+    last_path_event = NULL;
+    last_path_event_len = 0;
+}
+
+static char *copy_str(const char *s, size_t len) {
+    char *copy = RedisModule_Alloc(len + 1);
+    memcpy(copy, s, len);
+    copy[len] = '\0';
+    return copy;
+}
+
+static void OnPathEvent(RedisModuleCtx *ctx, const char *event, RedisModuleString *key,
+                        const char *const *paths, size_t len) {
+    REDISMODULE_NOT_USED(ctx);
+    free_last_path_event();
+    last_path_event = RedisModule_Alloc(sizeof(char *) * (len + 2));
+    last_path_event[0] = copy_str(event, strlen(event));
+    size_t key_len;
+    const char *key_str = RedisModule_StringPtrLen(key, &key_len);
+    last_path_event[1] = copy_str(key_str, key_len);
+    for (size_t i = 0; i < len; i++) last_path_event[i + 2] = copy_str(paths[i], strlen(paths[i]));
+    last_path_event_len = len + 2;
+}
+
+/* LLAPI.LAST_PATH_EVENT -> [event, key, path...] of the last write to a JSON key, or null. */
+static int LastPathEventCmd(RedisModuleCtx *ctx, RedisModuleString **argv, int argc) {
+    REDISMODULE_NOT_USED(argv);
+    if (argc != 1) return RedisModule_WrongArity(ctx);
+    if (!last_path_event) return RedisModule_ReplyWithNull(ctx);
+    RedisModule_ReplyWithArray(ctx, last_path_event_len);
+    for (size_t i = 0; i < last_path_event_len; i++) {
+        RedisModule_ReplyWithCString(ctx, last_path_event[i]);
+    }
+    return REDISMODULE_OK;
+}
+
 /* Bind the latest shared-API version. This module exercises functions across
- * all API versions (V1..V9), so it requires a provider exporting the full,
+ * all API versions (V1..V10), so it requires a provider exporting the full,
  * current struct; binding an older version could leave later fields undefined
  * and dereferencing them would read past the provider's struct. */
 static int fetch_japi(RedisModuleCtx *ctx) {
@@ -545,6 +589,8 @@ int RedisModule_OnLoad(RedisModuleCtx *ctx, RedisModuleString **argv, int argc)
     }
     RedisModule_Log(ctx, "notice", "llapi_test: bound RedisJSON shared API V%d", japi_ver);
 
+    if (japi->subscribeToPathEvents(OnPathEvent) != REDISMODULE_OK) return REDISMODULE_ERR;
+
     REGISTER("LLAPI.VERSION", VersionCmd);
     REGISTER("LLAPI.OPEN_GET", OpenGetCmd);
     REGISTER("LLAPI.ITER_JSON", IterJsonCmd);
@@ -563,6 +609,7 @@ int RedisModule_OnLoad(RedisModuleCtx *ctx, RedisModuleString **argv, int argc)
     REGISTER("LLAPI.ISJSON", IsJsonCmd);
     REGISTER("LLAPI.PATHPARSE", PathParseCmd);
     REGISTER("LLAPI.OPEN_GET_WITH_PATH", OpenGetWithPathCmd);
+    REGISTER("LLAPI.LAST_PATH_EVENT", LastPathEventCmd);
 
     return REDISMODULE_OK;
 }
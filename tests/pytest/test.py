@@ -7,10 +7,12 @@ import sys
 import os
 import redis
 import json
+import threading
 
 import time
 from RLTest import Env
 from includes import *
+from common import TimeLimit
 from redis.client import NEVER_DECODE
 from RLTest import Defaults
 
@@ -162,6 +164,79 @@ def testSetAddNewImmediateChild(env):
     # Do not add a new none-direct/none-immediate child
     r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.basic.dict.new_child_3.new_grandchild_1', '"new_grandchild_3_val"'))
 
+def testSetMergeCreatePath(env):
+    """Test JSON.SET and JSON.MERGE CREATEPATH"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"s":"x","arr":[]}'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a.b.c', '1'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a.b.c', '1', 'CREATEPATH'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '.a.d["e f"]', '{"g":2}', 'NX', 'CREATEPATH'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a.b.c', '3', 'NX', 'CREATEPATH'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.x.y', '3', 'XX', 'CREATEPATH'))
+    r.assertOk(r.execute_command('JSON.MERGE', 'test', '$.m.n', '{"o":1}', 'CREATEPATH'))
+    r.assertOk(r.execute_command('JSON.MERGE', 'test', '$.m.n', '{"p":2}', 'CREATEPATH'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')),
+                  [{"s":"x","arr":[],"a":{"b":{"c":1},"d":{"e f":{"g":2}}},"m":{"n":{"o":1,"p":2}}}])
+
+    # Nothing is created below a non-object
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.s.t.u', '1', 'CREATEPATH'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.arr[0].u', '1', 'CREATEPATH'))
+    r.expect('JSON.SET', 'test', '$..v.w', '1', 'CREATEPATH').error().contains('static path')
+    r.expect('JSON.MERGE', 'test', '$.a[*].w', '1', 'CREATEPATH').error().contains('static path')
+
+    # The containers count toward the depth limit, and are not created on failure
+    deep = '.'.join(['$'] + ['k'] * 100)
+    r.assertOk(r.execute_command('JSON.SET', 'test', deep, '1', 'CREATEPATH'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.z.z', '{"a":' * 126 + '1' + '}' * 126, 'CREATEPATH'))
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.z'), '[]')
+    r.expect('JSON.SET', 'test', '.'.join(['$'] + ['k'] * 128), '1', 'CREATEPATH').error().contains('recursion limit')
+
+def testSetMultiplePaths(env):
+    """Test JSON.SET with several path/value pairs"""
+    r = env
+
+    r.expect('JSON.SET', 'test', '$.a', '1', '$.b', '2').error().contains('created at the root')
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a', '1', '$.b', '2', 'XX'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":1,"arr":[1,2]}'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a', '2', '$.b', '"x"', '$.arr[*]', '0'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{"a":2,"b":"x","arr":[0,0]}])
+
+    # Nothing is set unless every pair can be
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.c', '1', '$.a', '3', 'NX'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a', '3', '$.c', '1', 'XX'))
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a', '3', '$.x.y', '1'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.c', '1', '.d', '{"e":[]}', 'NX'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a', '3', '.c', '4', 'XX'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{"a":3,"b":"x","arr":[0,0],"c":4,"d":{"e":[]}}])
+
+    # Missing parents are created for every pair, and none is if any pair can't be set
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.f.g', '1', '$.s.t', '1', '$.b.h', '1', 'CREATEPATH'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.f', '$.s')), {"$.f":[],"$.s":[]})
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.f.g', '1', '$.s.t', '[2]', 'CREATEPATH'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.f', '$.s')), {"$.f":[{"g":1}],"$.s":[{"t":[2]}]})
+
+    # A pair can't write at, above or below a path written by another
+    r.expect('JSON.SET', 'test', '$.d', '{"x":1}', '$.d.e', '[1]').error().contains('overlap')
+    r.expect('JSON.SET', 'test', '$.arr[*]', '1', '$.arr[0]', '2').error().contains('overlap')
+    r.expect('JSON.SET', 'test', '$.p.q', '1', '$.p.r', '2', 'CREATEPATH').error().contains('overlap')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.d', '$.arr', '$.p')), {"$.d":[{"e":[]}],"$.arr":[[0,0]],"$.p":[]})
+
+    # Nothing is set when a value would nest too deep
+    deep = '[' * 126 + ']' * 126
+    r.assertIsNone(r.execute_command('JSON.SET', 'test', '$.a', '5', '$.d.e', deep))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.a')), [3])
+
+    # The paths after the first one start with `$` or `.`, so a bare word is an option
+    r.assertOk(r.execute_command('JSON.SET', 'test', 'a', '4', '.c', '5', 'XX'))
+    r.expect('JSON.SET', 'test', '$.a', '1', 'NX', '2').error().contains('syntax error')
+    r.expect('JSON.SET', 'test', '$.a', '1', 'b', '2').error().contains('syntax error')
+
+    r.expect('JSON.SET', 'test', '$', '{}', '$.a', '1').error().contains('root path')
+    r.expect('JSON.SET', 'test', '$.a', '1', '$.b').error()
+    r.expect('JSON.SET', 'test', '$.a', '1', '$.b', '{', 'NX').error()
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.a')), [4])
+
 def testSetReplaceRootShouldSucceed(env):
     """Test replacing the root of an existing key with a valid object succeeds"""
     r = env
@@ -494,6 +569,36 @@ def testSetBSON(env):
     r.assertOk(r.execute_command('JSON.SET', 'test', '.', bson, 'FORMAT', 'BSON'))
     r.expect('JSON.GET', 'test', *docs['values'].keys()).raiseError()
 
+def testGetDelGetSet(env):
+    """Test JSON.GETDEL and JSON.GETSET"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":1,"b":{"a":[2]},"c":"x"}'))
+    r.assertEqual(r.execute_command('JSON.GETSET', 'test', '$..a', '3'), '[1,[2]]')
+    r.assertEqual(r.execute_command('JSON.GETSET', 'test', '.c', '"y"'), '"x"')
+    r.assertIsNone(r.execute_command('JSON.GETSET', 'test', '.d', '4'))
+    r.assertEqual(r.execute_command('JSON.GETSET', 'test', '$.e', '5'), '[]')
+    r.assertIsNone(r.execute_command('JSON.GETSET', 'test', '$.x.y', '6'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{"a":3,"b":{"a":3},"c":"y","d":4,"e":5}])
+
+    r.assertEqual(r.execute_command('JSON.GETDEL', 'test', '$..a'), '[3,3]')
+    r.assertEqual(r.execute_command('JSON.GETDEL', 'test', '.c'), '"y"')
+    r.assertEqual(r.execute_command('JSON.GETDEL', 'test', '$.nosuch'), '[]')
+    r.expect('JSON.GETDEL', 'test', '.nosuch').raiseError()
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{"b":{},"d":4,"e":5}])
+    r.assertEqual(json.loads(r.execute_command('JSON.GETDEL', 'test')), {"b":{},"d":4,"e":5})
+    r.assertEqual(r.execute_command('EXISTS', 'test'), 0)
+    r.assertIsNone(r.execute_command('JSON.GETDEL', 'test', '$'))
+
+    # A new key is only created at the root, with no previous value
+    r.expect('JSON.GETSET', 'test', '$.a', '1').raiseError().contains('created at the root')
+    r.assertIsNone(r.execute_command('JSON.GETSET', 'test', '$', '{"a":1}'))
+    r.assertEqual(r.execute_command('JSON.GETSET', 'test', '$', '[]'), '[{"a":1}]')
+    r.expect('JSON.GETSET', 'test', '$', '{').raiseError()
+    r.expect('JSON.GETSET', 'test', '$', '1', 'NX').raiseError().contains('syntax error')
+    r.expect('JSON.GETDEL', 'test', '$', 'FORMAT', 'EXPAND').raiseError().contains('not supported on RESP2')
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$'), '[[]]')
+
 def testMgetCommand(env):
     """Test REJSON.MGET command"""
     r = env
@@ -889,6 +994,35 @@ def testArrIndexMixCommand(env):
     r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '.arr', '["a", "b", 8]'), 11)
     r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', '[3, 4, 8]'), [10])
 
+def testArrIndexModesCommand(env):
+    """Test JSON.ARRINDEX with FIRST/LAST/ALL, COUNT and filters"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"arr":[0,1,2,1,0,1],"n":1}'))
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 'FIRST'), [1])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 'LAST'), [5])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 'ALL'), [[1, 3, 5]])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 'ALL', 'COUNT', 2), [[1, 3]])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 'LAST', 'COUNT', 2), [[5, 3]])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 1, 0, 4, 'LAST'), [3])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', 7, 'ALL'), [[]])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.*', 0, 'ALL'), [[0, 4], None])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '.arr', 1, 'LAST'), 5)
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '.arr', 0, 'ALL'), [0, 4])
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.arr', '[{"id":1,"status":"open"},{"id":2,"status":"closed"},{"id":3,"status":"open"}]'))
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', '?(@.status=="open")'), [0])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', '?(@.status=="open")', 'LAST'), [2])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', '?(@.status=="open")', 1, 'ALL'), [[2]])
+    r.assertEqual(r.execute_command('JSON.ARRINDEX', 'test', '$.arr', '?(@.id>5)'), [-1])
+
+    r.expect('JSON.ARRINDEX', 'test', '$.arr', 1, 'COUNT', -1).raiseError()
+    r.expect('JSON.ARRINDEX', 'test', '$.arr', 1, 'SOME').raiseError()
+    r.expect('JSON.ARRINDEX', 'test', '$.arr', '?(@.id>').raiseError()
+    # The filter can't close its brackets to select anything else
+    r.expect('JSON.ARRINDEX', 'test', '$.arr', '?(@.id>1)]..id[?(@)').raiseError().contains('invalid filter')
+    r.expect('JSON.ARRINDEX', 'test', '$.arr', '?(@.id>1)].status').raiseError().contains('invalid filter')
+
 def testArrTrimCommand(env):
     """Test JSON.ARRTRIM command"""
 
@@ -944,6 +1078,236 @@ def testArrPopErrors(env):
     r.assertOk(r.execute_command('JSON.SET', 'test','.', '1'))
     r.expect('JSON.ARRPOP', 'test').error().contains("not an array")
 
+def testArrBPopCommand(env):
+    """Test JSON.ARRBPOP command"""
+    r = env
+
+    # The side is not taken as a key
+    r.assertEqual(r.execute_command('COMMAND', 'GETKEYS', 'JSON.ARRBPOP', 'k1', 'k2', '$.q', 'LEFT', 0), ['k1', 'k2'])
+    r.assertEqual(r.execute_command('COMMAND', 'GETKEYS', 'JSON.ARRBPOP', 'k1', 'k2', '$.q', 0), ['k1', 'k2'])
+    r.assertEqual(r.execute_command('COMMAND', 'GETKEYS', 'JSON.ARRBPOP', 'LEFT', '$.q', 0), ['LEFT'])
+
+    # The keys share a slot for the command to run on a cluster
+    k1, k2 = '{q}1', '{q}2'
+    conn = env.getConnectionByKey(k2, 'JSON.ARRBPOP')
+    r.assertOk(conn.execute_command('JSON.SET', k2, '$', '{"q":[1,2,3]}'))
+    # First key has no array at path, second one does
+    r.assertEqual(conn.execute_command('JSON.ARRBPOP', k1, k2, '$.q', 1), [k2, '3'])
+    r.assertEqual(conn.execute_command('JSON.ARRBPOP', k1, k2, '$.q', 'LEFT', 1), [k2, '1'])
+    r.assertEqual(conn.execute_command('JSON.ARRBPOP', k2, '.q', 'RIGHT', 0), [k2, '2'])
+    r.assertEqual(conn.execute_command('JSON.GET', k2, '$.q'), '[[]]')
+
+    # Times out on an empty array
+    r.assertIsNone(conn.execute_command('JSON.ARRBPOP', k1, k2, '$.q', 0.1))
+
+    # Inside MULTI the command does not block
+    r.assertEqual(conn.execute_command('MULTI'), True)
+    conn.execute_command('JSON.ARRBPOP', k2, '$.q', 0)
+    r.assertEqual(conn.execute_command('EXEC'), [None])
+
+    # Unblocked by a write from another client
+    blocked = env.getConnectionByKey(k2, 'JSON.ARRBPOP')
+    client_id = blocked.execute_command('CLIENT', 'ID')
+    result = []
+    t = threading.Thread(target=lambda: result.append(
+        blocked.execute_command('JSON.ARRBPOP', k1, k2, '$.q', 'LEFT', 10)))
+    t.start()
+    with TimeLimit(5):
+        while conn.execute_command('CLIENT', 'LIST', 'ID', client_id).find('cmd=json.arrbpop') < 0:
+            time.sleep(0.01)
+    r.assertEqual(conn.execute_command('JSON.ARRAPPEND', k2, '$.q', '"a"', '"b"', '"c"'), [3])
+    t.join()
+    r.assertEqual(result, [[k2, '"a"']])
+    r.assertEqual(conn.execute_command('JSON.GET', k2, '$.q'), '[["b","c"]]')
+
+    # Any write adding elements unblocks it, whatever the command
+    r.assertOk(conn.execute_command('JSON.SET', k1, '$', '{"q":[]}'))
+    result = []
+    t = threading.Thread(target=lambda: result.append(
+        blocked.execute_command('JSON.ARRBPOP', k1, '$.q', 10)))
+    t.start()
+    with TimeLimit(5):
+        while conn.execute_command('CLIENT', 'LIST', 'ID', client_id).find('cmd=json.arrbpop') < 0:
+            time.sleep(0.01)
+    r.assertEqual(conn.execute_command('JSON.ARRINSORT', k1, '$.q', '1'), [0])
+    t.join()
+    r.assertEqual(result, [[k1, '1']])
+
+    # The pops are replicated as JSON.ARRPOP of the array they popped from
+    if env.useSlaves and not env.isCluster():
+        env.cmd('WAIT', '1', '10000')
+        slave_conn = env.getSlaveConnection()
+        r.assertEqual(slave_conn.execute_command('JSON.GET', k2, '$.q'), '[["b","c"]]')
+
+def testArrBPopErrors(env):
+    r = env
+
+    r.expect('JSON.ARRBPOP', 'k', '$').raiseError()
+    r.expect('JSON.ARRBPOP', 'k', '$', 'abc').error().contains("timeout is not a float")
+    r.expect('JSON.ARRBPOP', 'k', '$', -1).error().contains("timeout is negative")
+
+def testWaitCommand(env):
+    """Test JSON.WAIT command"""
+    env.skipOnCluster()
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{"a":{"b":1},"c":2}'))
+    # Returns right away when the value already differs
+    r.assertEqual(r.execute_command('JSON.WAIT', 'doc', '$.a.b', 'IFNE', '2', 0), '[1]')
+    r.assertEqual(r.execute_command('JSON.WAIT', 'doc', '.a.b', 'IFNE', '2', 0), '1')
+    r.assertIsNone(r.execute_command('JSON.WAIT', 'missing', '$', 'IFNE', '1', 0))
+
+    # Times out when nothing touches the path
+    r.assertIsNone(r.execute_command('JSON.WAIT', 'doc', '$.a.b', 0.1))
+    r.assertIsNone(r.execute_command('JSON.WAIT', 'doc', '$.a.b', 'IFNE', '1', 0.1))
+
+    # Inside MULTI the command does not block
+    r.assertEqual(r.execute_command('MULTI'), True)
+    r.execute_command('JSON.WAIT', 'doc', '$.a.b', 0)
+    r.assertEqual(r.execute_command('EXEC'), [None])
+
+    blocked = env.getConnection()
+    client_id = blocked.execute_command('CLIENT', 'ID')
+
+    def wait_for(writes, *args):
+        result = []
+        t = threading.Thread(target=lambda: result.append(
+            blocked.execute_command('JSON.WAIT', 'doc', *args)))
+        t.start()
+        with TimeLimit(5):
+            while r.execute_command('CLIENT', 'LIST', 'ID', client_id).find('cmd=json.wait') < 0:
+                time.sleep(0.01)
+        for write in writes:
+            r.execute_command(*write)
+        t.join()
+        return result[0]
+
+    # A write to another path leaves the client blocked
+    r.assertEqual(wait_for([('JSON.SET', 'doc', '$.c', '3'), ('JSON.NUMINCRBY', 'doc', '$.a.b', 1)],
+                           '$.a.b', 10), '[2]')
+    # So does a write above the path
+    r.assertEqual(wait_for([('JSON.SET', 'doc', '$.a', '{"b":"x"}')], '$.a.b', 10), '["x"]')
+    # A write of the same value wakes it up as well
+    r.assertEqual(wait_for([('JSON.SET', 'doc', '$.a.b', '"x"')], '$.a.b', 10), '["x"]')
+    # Unless it waits for the value to differ
+    r.assertEqual(wait_for([('JSON.SET', 'doc', '$.a.b', '"x"'), ('JSON.SET', 'doc', '$.a.b', '"y"')],
+                           '.a.b', 'IFNE', '"x"', 10), '"y"')
+    # Deleting the key wakes it up with a null
+    r.assertIsNone(wait_for([('JSON.DEL', 'doc')], '$.a.b', 10))
+    # Whatever deletes it
+    for delete in [('DEL', 'doc'), ('UNLINK', 'doc'), ('PEXPIRE', 'doc', 1)]:
+        r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{"a":{"b":1}}'))
+        r.assertIsNone(wait_for([delete], '$.a.b', 10))
+
+def testWaitErrors(env):
+    r = env
+
+    r.expect('JSON.WAIT', 'k', '$').raiseError()
+    r.expect('JSON.WAIT', 'k', '$', 'abc').error().contains("timeout is not a float")
+    r.expect('JSON.WAIT', 'k', '$', -1).error().contains("timeout is negative")
+    r.expect('JSON.WAIT', 'k', '$', 'IFNE', '1').raiseError()
+    r.expect('JSON.WAIT', 'k', '$', 'FOO', '1', 0).error().contains("syntax error")
+    r.expect('JSON.WAIT', 'k', '$', 'IFNE', '{', 0).raiseError()
+
+def testArrInSortCommand(env):
+    """Test JSON.ARRINSORT command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":[1,3,5],"b":[9,4,2],"c":"x"}'))
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 4), [2])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 0), [0])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 9), [5])
+    # Equal values go after the existing ones
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 3.0), [3])
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.a'), '[[0,1,3,3.0,4,5,9]]')
+
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.b', 5, 'DESC'), [1])
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.b'), '[[9,5,4,2]]')
+
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 4, 'UNIQUE'), [-1])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 3, 'UNIQUE'), [-1])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.a', 2, 'UNIQUE'), [2])
+
+    # Multiple matches, non-arrays yield null
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.b', '[2,4]'))
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '$.*', 6), [7, 2, None])
+
+    # Legacy path
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'test', '.a', 7), 8)
+    r.expect('JSON.ARRINSORT', 'test', '.c', 1).raiseError()
+    r.expect('JSON.ARRINSORT', 'test', '$.a', 1, 'FOO').raiseError()
+
+def testArrInSortByCommand(env):
+    """Test JSON.ARRINSORT with BY"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'board', '$', '[{"n":"a","score":30},{"n":"b","score":20},{"n":"c"}]'))
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'board', '$', '{"n":"d","score":25}', 'BY', '$.score', 'DESC'), [1])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'board', '$', '{"n":"e","score":20}', 'BY', '.score', 'DESC'), [3])
+    # Elements missing the sort key sort like null
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'board', '$', '{"n":"f"}', 'BY', '$.score', 'DESC'), [5])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'board', '$[*].n')), ['a', 'd', 'b', 'e', 'c', 'f'])
+    r.assertEqual(r.execute_command('JSON.ARRINSORT', 'board', '$', '{"n":"e","score":20}', 'BY', '$.score', 'DESC', 'UNIQUE'), [-1])
+
+    r.expect('JSON.ARRINSORT', 'board', '$', '1', 'BY', '$.score + 1').raiseError()
+    r.expect('JSON.ARRINSORT', 'board', '$', '1', 'BY', '$.score[').raiseError()
+
+def testArrUniqueCommand(env):
+    """Test JSON.ARRUNIQUE command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":[1,"1",1,2,{"x":1,"y":2},{"y":2,"x":1},null,[1],[1],null],"b":[],"c":3}'))
+    r.assertEqual(r.execute_command('JSON.ARRUNIQUE', 'test', '$.*'), [6, 0, None])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.a')), [[1, "1", 2, {"x": 1, "y": 2}, None, [1]]])
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$.a', '[3,1,3,2,1]'))
+    r.assertEqual(r.execute_command('JSON.ARRUNIQUE', 'test', '$.a', 'KEEP', 'LAST'), [3])
+    r.assertEqual(r.execute_command('JSON.GET', 'test', '$.a'), '[[3,2,1]]')
+    # Nothing left to remove
+    r.assertEqual(r.execute_command('JSON.ARRUNIQUE', 'test', '.a'), 3)
+
+    r.expect('JSON.ARRUNIQUE', 'test', '.c').raiseError()
+    r.expect('JSON.ARRUNIQUE', 'test', '$.a', 'KEEP', 'MIDDLE').raiseError()
+    r.expect('JSON.ARRUNIQUE', 'missing', '$.a').raiseError()
+
+def testArrUniqueByCommand(env):
+    """Test JSON.ARRUNIQUE with BY"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '[{"id":1,"v":"a"},{"id":2,"v":"b"},{"id":1,"v":"c"},{"v":"d"},{"v":"e"}]'))
+    # The elements with no id are not duplicates of each other
+    r.assertEqual(r.execute_command('JSON.ARRUNIQUE', 'test', '$', 'BY', '$.id', 'KEEP', 'LAST'), [4])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$[*].v')), ['b', 'c', 'd', 'e'])
+
+    # A null id is a value like any other
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '[{"id":null,"v":"a"},{"v":"b"},{"id":null,"v":"c"}]'))
+    r.assertEqual(r.execute_command('JSON.ARRUNIQUE', 'test', '$', 'BY', '$.id'), [2])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$[*].v')), ['a', 'b'])
+
+    r.expect('JSON.ARRUNIQUE', 'test', '$', 'BY', '$.id[').raiseError()
+
+def testArrUpsertCommand(env):
+    """Test JSON.ARRUPSERT command"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"users":[{"id":1,"name":"a","tags":["x"]},{"id":2,"name":"b"}],"n":1}'))
+    r.assertEqual(r.execute_command('JSON.ARRUPSERT', 'test', '$.users', 'id', '{"id":2,"name":"bb","age":3}'), ['updated'])
+    r.assertEqual(r.execute_command('JSON.ARRUPSERT', 'test', '$.users', '$.id', '{"id":3,"name":"c"}'), ['inserted'])
+    # Merge semantics: null removes a field
+    r.assertEqual(r.execute_command('JSON.ARRUPSERT', 'test', '.users', 'id', '{"id":1,"tags":null}'), 'updated')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.users')),
+                  [[{"id": 1, "name": "a"}, {"id": 2, "name": "bb", "age": 3}, {"id": 3, "name": "c"}]])
+
+    r.assertEqual(r.execute_command('JSON.ARRUPSERT', 'test', '$.*', 'id', '{"id":4}'), ['inserted', None])
+    r.expect('JSON.ARRUPSERT', 'test', '$.users', 'id', '{"name":"d"}').error().contains('id field')
+    r.expect('JSON.ARRUPSERT', 'test', '.n', 'id', '{"id":1}').raiseError()
+
+    # Nothing is written unless the value fits in every matched array
+    r.assertOk(r.execute_command('JSON.SET', 'deep', '$', '{"a":[{"id":1}],"b":{"c":{"a":[]}}}'))
+    deep = '{"id":1,"n":' + '{"n":' * 123 + '1' + '}' * 123 + '}'
+    r.expect('JSON.ARRUPSERT', 'deep', '$..a', 'id', deep).error().contains('recursion limit')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'deep', '$')), [{"a": [{"id": 1}], "b": {"c": {"a": []}}}])
+
 def testArrWrongChars(env):
     r = env
 
@@ -966,6 +1330,391 @@ def testTypeCommand(env):
         reply = r.execute_command('JSON.TYPE', 'test', '.')
         r.assertEqual(reply, k)
 
+def testExistsCommand(env):
+    """Test JSON.EXISTS command"""
+    r = env
+
+    r.assertIsNone(r.execute_command('JSON.EXISTS', 'test', '.'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":{"b":null},"c":[{"b":1},{"d":2}]}'))
+    r.assertEqual(r.execute_command('JSON.EXISTS', 'test', '.a.b', '.a.x', 'c[1].d', '.'), [1, 0, 1, 1])
+    r.assertEqual(r.execute_command('JSON.EXISTS', 'test', '$..b', '$.x', '$.c[*]', '$'), [2, 0, 2, 1])
+    r.assertEqual(r.execute_command('JSON.EXISTS', 'test', '$.c[?(@.b==1)]'), [1])
+    r.expect('JSON.EXISTS', 'test', '$.a +').raiseError()
+    r.expect('JSON.EXISTS', 'test', '$.c.length()').raiseError()
+    r.expect('JSON.EXISTS', 'test').raiseError()
+
+def testCountCommand(env):
+    """Test JSON.COUNT command"""
+    r = env
+
+    r.assertIsNone(r.execute_command('JSON.COUNT', 'test', '$'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"items":[{"x":1},{"x":2},{"x":3}],"y":{"x":4}}'))
+    r.assertEqual(r.execute_command('JSON.COUNT', 'test', '$.items[?(@.x>1)]'), 2)
+    r.assertEqual(r.execute_command('JSON.COUNT', 'test', '$..x'), 4)
+    r.assertEqual(r.execute_command('JSON.COUNT', 'test', '$.items[*]'), 3)
+    r.assertEqual(r.execute_command('JSON.COUNT', 'test', '$.nosuch'), 0)
+    r.assertEqual(r.execute_command('JSON.COUNT', 'test', '.y.x'), 1)
+    r.expect('JSON.COUNT', 'test', '$.items.length()').raiseError().contains('projection')
+    r.expect('JSON.COUNT', 'test', '$.items[').raiseError()
+    r.expect('JSON.COUNT', 'test', '$', '$').raiseError()
+
+def testPathsCommand(env):
+    """Test JSON.PATHS command"""
+    r = env
+
+    r.assertIsNone(r.execute_command('JSON.PATHS', 'test'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":{"b":[1,"x"],"c":{}},"d\'e":null}'))
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test'),
+                  ["$['a']['b'][0]", "$['a']['b'][1]", "$['a']['c']", "$['d\\'e']"])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', 'ALL'),
+                  ["$", "$['a']", "$['a']['b']", "$['a']['b'][0]", "$['a']['b'][1]", "$['a']['c']", "$['d\\'e']"])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', '$.a', 'DEPTH', '1', 'WITHTYPES'),
+                  [["$['a']['b']", 'array'], ["$['a']['c']", 'object']])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', '.a.b', 'ALL', 'WITHTYPES'),
+                  [["$['a']['b']", 'array'], ["$['a']['b'][0]", 'integer'], ["$['a']['b'][1]", 'string']])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', '$..b[*]', 'DEPTH', '0'), ["$['a']['b'][0]", "$['a']['b'][1]"])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', 'ALL', 'LIMIT', '2'), ["$", "$['a']"])
+    r.assertEqual(r.execute_command('JSON.PATHS', 'test', '$.nosuch'), [])
+
+    r.expect('JSON.PATHS', 'test', '$', 'DEPTH', '-1').raiseError().contains('negative')
+    r.expect('JSON.PATHS', 'test', '$', 'LIMIT').raiseError()
+    r.expect('JSON.PATHS', 'test', '$', 'FOO').raiseError().contains('syntax error')
+    r.expect('JSON.PATHS', 'test', '$.a.b.length()').raiseError().contains('projection')
+
+def testFlattenUnflatten(env):
+    """Test JSON.FLATTEN and JSON.UNFLATTEN commands"""
+    r = env
+
+    doc = {"a": {"b.c": [1, "x"]}, "d": None, "e": []}
+    r.assertIsNone(r.execute_command('JSON.FLATTEN', 'test', '$'))
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', json.dumps(doc)))
+    flat = r.execute_command('JSON.FLATTEN', 'test', '.')
+    r.assertEqual(flat, ['a.b\\.c[0]', 1, 'a.b\\.c[1]', 'x', 'd', None, 'e', []])
+    r.assertEqual(r.execute_command('JSON.FLATTEN', 'test', '.', 'SEPARATOR', '/'),
+                  ['a/b.c[0]', 1, 'a/b.c[1]', 'x', 'd', None, 'e', []])
+    r.assertEqual(r.execute_command('JSON.FLATTEN', 'test', '$.a'), [['b\\.c[0]', 1, 'b\\.c[1]', 'x']])
+    r.assertEqual(r.execute_command('JSON.FLATTEN', 'test', '$.d'), [None])
+
+    # A round trip gives back the original document
+    pairs = dict(zip(flat[::2], flat[1::2]))
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '$', json.dumps(pairs)))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy', '$')), [doc])
+
+    r.assertOk(r.execute_command('JSON.UNFLATTEN', 'copy', '$.a', '{"x::y[1]":true}', 'SEPARATOR', '::'))
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'copy', '$.a')), [{"x": {"y": [None, True]}}])
+    r.assertIsNone(r.execute_command('JSON.UNFLATTEN', 'copy', '$.nosuch.x', '{"a":1}'))
+
+    r.expect('JSON.FLATTEN', 'test', '.d').raiseError().contains('expected object or array')
+    r.expect('JSON.FLATTEN', 'test', '$', 'SEPARATOR', '[').raiseError().contains('invalid separator')
+    r.expect('JSON.FLATTEN', 'test', '$', 'FOO').raiseError().contains('syntax error')
+    r.expect('JSON.UNFLATTEN', 'copy', '$', '[1]').raiseError().contains('must be a JSON object')
+    r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a":1,"a.b":2}').raiseError().contains('conflicting flat keys')
+    for leaf in ['{}', '[]', 'null']:
+        r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a":%s,"a.b":1}' % leaf).raiseError().contains('conflicting flat keys')
+        r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a.b":1,"a":%s}' % leaf).raiseError().contains('conflicting flat keys')
+        r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a[0]":1,"a":%s}' % leaf).raiseError().contains('conflicting flat keys')
+    r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a[9]":1}').raiseError().contains('out of range')
+    r.expect('JSON.UNFLATTEN', 'copy', '$', '{"a]":1}').raiseError().contains('invalid flat key')
+    r.expect('JSON.UNFLATTEN', 'nokey', '$.a', '{"a":1}').raiseError().contains('at the root')
+
+def testFromHashToHash(env):
+    """Test JSON.FROMHASH and JSON.TOHASH commands"""
+    r = env
+
+    r.assertIsNone(r.execute_command('JSON.FROMHASH', 'doc', 'hash'))
+    r.assertEqual(r.execute_command('HSET', 'hash', 's', 'str', 'i', '42', 'f', '1.5', 'b', 'true', 'z', '007'), 5)
+    r.assertEqual(r.execute_command('JSON.FROMHASH', 'doc', 'hash'), 5)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'doc', '$')),
+                  [{"s": "str", "i": "42", "f": "1.5", "b": "true", "z": "007"}])
+    r.assertEqual(r.execute_command('JSON.FROMHASH', 'doc', 'hash', 'TYPES', 'auto'), 5)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'doc', '$')),
+                  [{"s": "str", "i": 42, "f": 1.5, "b": True, "z": "007"}])
+
+    # A round trip gives back the hash
+    r.assertEqual(r.execute_command('JSON.TOHASH', 'copy', 'doc', '$'), 5)
+    r.assertEqual(r.execute_command('HGETALL', 'copy'), r.execute_command('HGETALL', 'hash'))
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{"a":{"x":false},"n":null}'))
+    r.assertEqual(r.execute_command('JSON.TOHASH', 'copy', 'doc', '.a'), 1)
+    r.assertEqual(r.execute_command('HGETALL', 'copy'), {'x': 'false'})
+    r.assertIsNone(r.execute_command('JSON.TOHASH', 'copy', 'nokey', '$'))
+
+    r.expect('JSON.TOHASH', 'copy', 'doc', '$').raiseError().contains('strings, numbers or booleans')
+    r.expect('JSON.TOHASH', 'copy', 'doc', '$.n').raiseError().contains('expected object')
+    r.expect('JSON.TOHASH', 'doc', 'doc', '$.a').raiseError().contains('WRONGTYPE')
+    r.expect('JSON.FROMHASH', 'hash', 'hash').raiseError().contains('WRONGTYPE')
+    r.expect('JSON.FROMHASH', 'hash', 'doc').raiseError().contains('WRONGTYPE')
+    r.expect('JSON.FROMHASH', 'doc', 'hash', 'TYPES', 'foo').raiseError().contains('AUTO or STRING')
+
+def testLoadCommand(env):
+    """Test JSON.LOAD command"""
+    r = env
+    # The keys aren't arguments, so the command is sent to the node serving them
+    load = env.getConnectionByKey('{user}', 'JSON.LOAD')
+
+    payload = '{"id":1,"n":"a"}\n{"id":"b"}\n\nnot json\n{"x":1}\n{"id":[1]}\n'
+    res = load.execute_command('JSON.LOAD', '{user}:{$.id}', payload)
+    r.assertEqual(res[0], 2)
+    r.assertEqual([line for line, _ in res[1]], [4, 5, 6])
+    r.assertTrue('must match a single value' in res[1][1][1])
+    r.assertTrue('must match a string, a number or a boolean' in res[1][2][1])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', '{user}:1')), {"id": 1, "n": "a"})
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', '{user}:b')), {"id": "b"})
+
+    # Existing documents are replaced, other types of keys aren't
+    r.assertOk(r.execute_command('SET', '{user}:2', 'foo'))
+    res = load.execute_command('JSON.LOAD', '{user}:{$.id}', '{"id":1}\n{"id":2}')
+    r.assertEqual(res[0], 1)
+    r.assertEqual(res[1][0][0], 2)
+    r.assertTrue('WRONGTYPE' in res[1][0][1])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', '{user}:1')), {"id": 1})
+
+    r.expect('JSON.LOAD', 'user', '{"id":1}').raiseError().contains('must have a field')
+    r.expect('JSON.LOAD', 'user:{$.id', '{"id":1}').raiseError().contains('unterminated')
+
+    if env.isCluster():
+        # The command is routed by the template, so the keys must share its hash tag
+        routed = env.getConnectionByKey('user:{$.id}', 'JSON.LOAD')
+        err = None
+        try:
+            routed.execute_command('JSON.LOAD', 'user:{$.id}', '{"id":1}')
+        except redis.exceptions.ResponseError as e:
+            err = str(e)
+        r.assertTrue(err is not None and 'CROSSSLOT' in err)
+        moved = 0
+        for shard in range(1, env.shardsCount + 1):
+            try:
+                env.getConnection(shard).execute_command('JSON.LOAD', '{user}:{$.id}', '{"id":3}')
+            except redis.exceptions.ResponseError:
+                moved += 1
+        r.assertEqual(moved, env.shardsCount - 1)
+        r.assertEqual(json.loads(r.execute_command('JSON.GET', '{user}:3')), {"id": 3})
+
+def testMemberExpiry(env):
+    """Test JSON.EXPIRE, JSON.TTL and JSON.PERSIST"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{"token":"t","user":{"name":"a","code":1},"arr":[1]}'))
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '$.token', 100), [1])
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '.user.code', 100), 1)
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc', '$.*'), [100, -1, -1])
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc', '.token'), 100)
+    r.assertEqual(r.execute_command('JSON.TTL', 'nosuch', '$.token'), None)
+
+    # The expiry survives a reload
+    r.execute_command('DEBUG', 'RELOAD')
+    r.assertTrue(r.execute_command('JSON.TTL', 'doc', '.user.code') > 90)
+
+    r.assertEqual(r.execute_command('JSON.PERSIST', 'doc', '$..code'), [1])
+    r.assertEqual(r.execute_command('JSON.PERSIST', 'doc', '$..code'), [0])
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc', '.user.code'), -1)
+
+    # Replacing a member forgets its expiry
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$.token', '"u"'))
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc', '.token'), -1)
+
+    # Deleting a member by merging null forgets its expiry
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '$.user.code', 100), [1])
+    r.assertOk(r.execute_command('JSON.MERGE', 'doc', '$', '{"user":{"code":null}}'))
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$.user.code', 1))
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc', '.user.code'), -1)
+
+    # So does emptying its object, or merging a value replacing it rather than merged into it
+    r.assertOk(r.execute_command('JSON.SET', 'doc3', '$', '{"o":{"a":1}}'))
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc3', '$.o.a', 100), [1])
+    r.assertEqual(r.execute_command('JSON.CLEAR', 'doc3', '$.o'), 1)
+    r.assertOk(r.execute_command('JSON.SET', 'doc3', '$.o.a', 2))
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc3', '.o.a'), -1)
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc3', '$.o.a', 100), [1])
+    r.assertOk(r.execute_command('JSON.MERGE', 'doc3', '$', '{"o":1}'))
+    r.assertOk(r.execute_command('JSON.MERGE', 'doc3', '$', '{"o":{"a":3}}'))
+    r.assertEqual(r.execute_command('JSON.TTL', 'doc3', '.o.a'), -1)
+
+    # A time in the past deletes the member right away
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '$.user.name', 0), [2])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'doc', '$.user')), [{"code": 1}])
+
+    # Expired members are deleted, and the key with its last member
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '$.token', 1), [1])
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc', '$.user', 'PXAT', int(time.time() * 1000) + 1000), [1])
+    r.assertOk(r.execute_command('JSON.SET', 'doc2', '$', '{"a":1}'))
+    r.assertEqual(r.execute_command('JSON.EXPIRE', 'doc2', '$.a', 1), [1])
+    with TimeLimit(5):
+        while json.loads(r.execute_command('JSON.GET', 'doc')) != {"arr": [1]}:
+            time.sleep(0.1)
+    # Deleted by the active expiry cycle, without being accessed
+    with TimeLimit(5):
+        while r.execute_command('EXISTS', 'doc2'):
+            time.sleep(0.1)
+
+    r.expect('JSON.EXPIRE', 'doc', '$.arr[0]', 10).raiseError().contains('members of objects')
+    r.expect('JSON.EXPIRE', 'doc', '$', 10).raiseError().contains('members of objects')
+    r.expect('JSON.EXPIRE', 'doc', '$.arr', 'soon').raiseError().contains('invalid expire time')
+    r.expect('JSON.TTL', 'doc', '.nosuch').raiseError().contains('does not exist')
+
+def testMemberExpiryEncodingVersion(env):
+    """Test that member expiries keep documents in encoding version 4"""
+    env.skipOnCluster()
+    conn = env.getConnection()
+
+    conn.execute_command('JSON.SET', 'doc', '$', '{"a":1,"b":2}')
+    conn.execute_command('JSON.EXPIRE', 'doc', '$.a', 100)
+    dump = conn.execute_command('DUMP', 'doc', NEVER_DECODE=True)
+    env.assertEqual(dump[0], RDB_TYPE_MODULE_2)
+    module_id, _ = _rdb_load_len(dump, 1)
+    env.assertEqual(module_id & 0x3FF, 4)
+
+    # A version 4 payload loads, without the member expiries DUMP leaves out
+    env.assertTrue(conn.execute_command('RESTORE', 'copy', '0', dump))
+    env.assertEqual(json.loads(conn.execute_command('JSON.GET', 'copy')), {"a": 1, "b": 2})
+    env.assertEqual(conn.execute_command('JSON.TTL', 'copy', '$.a'), [-1])
+
+    # The member expiries are saved apart from the documents, and loaded back onto them
+    env.execute_command('DEBUG', 'RELOAD')
+    env.assertGreater(conn.execute_command('JSON.TTL', 'doc', '.a'), 90)
+    env.assertEqual(conn.execute_command('JSON.TTL', 'copy', '.a'), -1)
+
+def testKeyExpirationOptions(env):
+    """Test the EX, PX, EXAT, PXAT and KEEPTTL options of JSON.SET, JSON.MERGE and JSON.MSET"""
+    env.skipOnVersionSmaller('7.0')
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{"a":1}', 'EX', 100))
+    r.assertTrue(90 < r.execute_command('TTL', 'doc') <= 100)
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$.a', '2', 'PX', 50000))
+    r.assertTrue(40 < r.execute_command('TTL', 'doc') <= 50)
+
+    # The time to live is kept by default, and with KEEPTTL
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$.b', '3'))
+    r.assertTrue(r.execute_command('TTL', 'doc') > 40)
+    r.assertOk(r.execute_command('JSON.MERGE', 'doc', '$', '{"c":4}', 'KEEPTTL'))
+    r.assertTrue(r.execute_command('TTL', 'doc') > 40)
+
+    at = int(time.time()) + 200
+    r.assertOk(r.execute_command('JSON.MERGE', 'doc', '$', '{"d":5}', 'EXAT', at))
+    r.assertEqual(r.execute_command('EXPIRETIME', 'doc'), at)
+
+    # Nothing is written, so the expiration time is unchanged
+    r.assertEqual(r.execute_command('JSON.SET', 'doc', '$', '{}', 'NX', 'EX', 10), None)
+    r.assertEqual(r.execute_command('EXPIRETIME', 'doc'), at)
+
+    r.assertEqual(r.execute_command('JSON.MSET', 'PXAT', at * 1000, '{k}1', '$', '1', 'EX', 100, '{k}2', '$', '2'), ['OK', 'OK'])
+    r.assertEqual(r.execute_command('PEXPIRETIME', '{k}1'), at * 1000)
+    r.assertTrue(90 < r.execute_command('TTL', '{k}2') <= 100)
+
+    if env.useSlaves and not env.isCluster():
+        # Replicas get the expiration time of the master, rather than a time relative to theirs
+        env.cmd('WAIT', '1', '10000')
+        slave_conn = env.getSlaveConnection()
+        for key in ['doc', '{k}1', '{k}2']:
+            r.assertEqual(slave_conn.execute_command('PEXPIRETIME', key), r.execute_command('PEXPIRETIME', key))
+
+    # A time in the past expires the key right away
+    r.assertOk(r.execute_command('JSON.SET', 'doc', '$', '{}', 'PXAT', 1))
+    r.assertEqual(r.execute_command('EXISTS', 'doc'), 0)
+
+    r.expect('JSON.SET', 'doc', '$', '1', 'EX', 0).raiseError().contains("invalid expire time in 'json.set'")
+    # A time overflowing milliseconds since the epoch is just as invalid
+    r.expect('JSON.SET', 'doc', '$', '1', 'EX', 2 ** 62).raiseError().contains("invalid expire time in 'json.set'")
+    r.expect('JSON.MERGE', 'doc', '$', '1', 'PX', 2 ** 63 - 1).raiseError().contains("invalid expire time in 'json.merge'")
+    r.expect('JSON.MSET', 'EXAT', 2 ** 62, 'doc', '$', '1').raiseError().contains("invalid expire time in 'json.mset'")
+    r.expect('JSON.SET', 'doc', '$', '1', 'EX', 10, 'PX', 10).raiseError().contains('syntax error')
+    r.expect('JSON.MERGE', 'doc', '$', '1', 'EX').raiseError()
+    r.expect('JSON.MSET', 'KEEPTTL', 'EX', 10, 'doc', '$', '1').raiseError().contains('syntax error')
+
+def testChangeDataCapture():
+    env = Env(moduleArgs='CDC_PATTERN user:* CDC_VALUES yes')
+    env.skipOnCluster()
+
+    env.expect('JSON.SET', 'user:1', '$', '{"name": "a", "tags": []}').ok()
+    env.expect('JSON.SET', 'order:1', '$', '1').ok()
+    env.expect('JSON.ARRAPPEND', 'user:1', '$.tags', '"x"').equal([1])
+    env.expect('JSON.DEL', 'user:1', '$.name').equal(1)
+    # Writes that change nothing are not captured
+    env.expect('JSON.DEL', 'user:1', '$.missing').equal(0)
+
+    entries = env.cmd('XRANGE', 'json:cdc', '-', '+')
+    env.assertEqual(len(entries), 3)
+    fields = [entry[1] for entry in entries]
+    env.assertEqual([f['key'] for f in fields], ['user:1'] * 3)
+    env.assertEqual([f['command'] for f in fields], ['json.set', 'json.arrappend', 'json.del'])
+    env.assertEqual([json.loads(f['paths']) for f in fields], [['$'], ["$['tags']"], ["$['name']"]])
+    env.assertEqual([json.loads(f['old']) for f in fields],
+                    [{}, {"$['tags']": []}, {"$['name']": 'a'}])
+    env.assertEqual([json.loads(f['new']) for f in fields],
+                    [{'$': {'name': 'a', 'tags': []}}, {"$['tags']": ['x']}, {}])
+
+    if env.useSlaves:
+        # The replica gets the entries of the master rather than appending its own
+        env.cmd('WAIT', '1', '10000')
+        slave_conn = env.getSlaveConnection()
+        env.assertEqual(slave_conn.execute_command('XRANGE', 'json:cdc', '-', '+'), entries)
+
+    # So are the deletions of expired members
+    env.expect('JSON.SET', 'user:2', '$', '{"a": 1, "b": 2}').ok()
+    env.expect('JSON.EXPIRE', 'user:2', '$.a', '1').equal([1])
+    def last_entry():
+        return env.cmd('XREVRANGE', 'json:cdc', '+', '-', 'COUNT', '1')[0][1]
+    with TimeLimit(5):
+        while last_entry()['command'] != 'json.expired':
+            time.sleep(0.1)
+    last = last_entry()
+    env.assertEqual(last['key'], 'user:2')
+    env.assertEqual(json.loads(last['paths']), ["$['a']"])
+    env.assertEqual(json.loads(last['old']), {"$['a']": 1})
+
+def testChangeDataCaptureCluster():
+    env = Env(moduleArgs='CDC_PATTERN user:*')
+    if not env.isCluster():
+        env.skip()
+
+    # Each entry goes to the stream of the slot of its key
+    for key in ['user:{a}1', 'user:{a}2', 'user:3']:
+        env.getConnectionByKey(key, 'JSON.SET').execute_command('JSON.SET', key, '$', '1')
+    conn = env.getConnectionByKey('json:cdc{a}', 'XRANGE')
+    entries = conn.execute_command('XRANGE', 'json:cdc{a}', '-', '+')
+    env.assertEqual([entry[1]['key'] for entry in entries], ['user:{a}1', 'user:{a}2'])
+    conn = env.getConnectionByKey('json:cdc{user:3}', 'XRANGE')
+    entries = conn.execute_command('XRANGE', 'json:cdc{user:3}', '-', '+')
+    env.assertEqual([entry[1]['key'] for entry in entries], ['user:3'])
+
+def testEffectReplication():
+    env = Env(moduleArgs='REPLICATE_EFFECTS yes')
+    env.skipOnCluster()
+
+    env.expect('JSON.SET', 'doc', '$', '{"items": [{"x": 1}, {"x": 2}, {"x": 3}], "n": 1, "o": {"a": 1}}').ok()
+    env.expect('JSON.SET', 'doc', '$.items[?(@.x>1)].x', '0').ok()
+    env.expect('JSON.NUMINCRBY', 'doc', '$..n', '2').equal('[3]')
+    env.expect('JSON.ARRAPPEND', 'doc', '$.items', '{"x": 4}').equal([4])
+    # The elements shift as they are deleted
+    env.expect('JSON.DEL', 'doc', '$.items[?(@.x==0)]').equal(2)
+    env.expect('JSON.MERGE', 'doc', '$', '{"o": {"a": null, "b": 2}}').ok()
+    env.expect('JSON.EXPIRE', 'doc', '$.n', '1000').equal([1])
+    # A write in place keeps the expiry of the member
+    env.expect('JSON.NUMINCRBY', 'doc', '$.n', '1').equal('[4]')
+    # Writes of a few elements of an array or of the end of a string don't ship the whole of it
+    env.expect('JSON.SET', 'doc', '$.l', '[1, 2]').ok()
+    env.expect('JSON.ARRINSERT', 'doc', '$.l', '1', '3', '4').equal([4])
+    env.expect('JSON.ARRPOP', 'doc', '$.l', '0').equal(['1'])
+    env.expect('JSON.ARRAPPEND', 'doc', '$.l', '5').equal([4])
+    env.expect('JSON.SET', 'doc', '$.o.s', '"a"').ok()
+    env.expect('JSON.STRAPPEND', 'doc', '$.o.s', '"é"').equal([3])
+    # A merge replacing a member forgets its expiry
+    env.expect('JSON.EXPIRE', 'doc', '$.o.b', '1000').equal([1])
+    env.expect('JSON.MERGE', 'doc', '$', '{"o": {"b": 2}}').ok()
+    expected = {'items': [{'x': 1}, {'x': 4}], 'n': 4, 'o': {'b': 2, 's': 'aé'}, 'l': [3, 4, 2, 5]}
+    env.assertEqual(json.loads(env.cmd('JSON.GET', 'doc', '$')), [expected])
+
+    if env.useSlaves:
+        env.cmd('WAIT', '1', '10000')
+        slave_conn = env.getSlaveConnection()
+        env.assertEqual(json.loads(slave_conn.execute_command('JSON.GET', 'doc', '$')), [expected])
+        env.assertGreater(slave_conn.execute_command('JSON.TTL', 'doc', '$.n')[0], 0)
+        env.assertEqual(slave_conn.execute_command('JSON.TTL', 'doc', '$.o.b'), [-1])
+
+    # The patches are only applied from the replication stream or the AOF
+    env.expect('_JSON.PATCH', 'doc', 'json.del', 'DEL', '[]').raiseError().contains('only applied from the replication stream')
+    env.expect('EXISTS', 'doc').equal(1)
+
 def testLenCommands(env):
     """Test the JSON.ARRLEN, JSON.OBJLEN and JSON.STRLEN commands"""
     r = env
@@ -1039,6 +1788,69 @@ def testNumIncrCommand(env):
     r.assertEqual(1, res['foo'])
     r.assertEqual(84, res['bar'])
 
+def testNumBoundedCommands(env):
+    """Test JSON.NUMINCRBY and JSON.NUMMULTBY with MIN/MAX bounds"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"stock":3,"budget":10,"name":"x","arr":[1,5]}'))
+    # CLAMP is the default
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.stock', -5, 'MIN', 0), ['[0]', ['MIN']])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.stock')), [0])
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.stock', 2, 'MIN', 0, 'MAX', 4), ['[2]', [None]])
+
+    # FAIL keeps the value
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.stock', 5, 'MAX', 4, 'FAIL'), ['[2]', ['MAX']])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.stock')), [2])
+
+    # Each path is bounded on its own
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.*', 3, 'MAX', 5, 'CLAMP'),
+                  ['[5,5,null,null]', [None, 'MAX', None, None]])
+    r.assertEqual(r.execute_command('JSON.NUMMULTBY', 'test', '$.arr[*]', 2.5, 'MAX', 10), ['[2.5,10]', [None, 'MAX']])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')), [{"stock":5,"budget":5,"name":"x","arr":[2.5,10]}])
+
+    # With FAIL, no path is written when any of them falls past a bound
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.arr[*]', 1, 'MAX', 10, 'FAIL'), ['[2.5,10]', [None, 'MAX']])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.arr')), [[2.5,10]])
+
+    # Legacy paths reply with the value and the bound it hit
+    r.assertEqual(r.execute_command('JSON.NUMMULTBY', 'test', '.budget', 3, 'MAX', 12), ['12', 'MAX'])
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.budget', -20, 'MIN', -5, 'FAIL'), ['12', 'MIN'])
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.budget', -2, 'MIN', -5), ['10', None])
+
+    r.expect('JSON.NUMINCRBY', 'test', '$.stock', 1, 'MIN', 5, 'MAX', 1).error().contains('MIN is greater than MAX')
+    r.expect('JSON.NUMINCRBY', 'test', '$.stock', 1, 'MIN', 'abc').error().contains('bound is not a number')
+    r.expect('JSON.NUMINCRBY', 'test', '$.stock', 1, 'MAX').raiseError()
+    r.expect('JSON.NUMINCRBY', 'test', '$.stock', 1, 'LIMIT', 1).error().contains('syntax error')
+
+    # Integers are compared exactly, past the precision of doubles
+    r.assertOk(r.execute_command('JSON.SET', 'big', '$', '9007199254740992'))
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'big', '$', 1, 'MAX', 9007199254740992), ['[9007199254740992]', ['MAX']])
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'big', '$', 1, 'MAX', 9007199254740993), ['[9007199254740993]', [None]])
+    r.expect('JSON.NUMINCRBY', 'big', '$', 1, 'MIN', 9007199254740993, 'MAX', 9007199254740992).error().contains('MIN is greater than MAX')
+
+def testNumIncrCreate(env):
+    """Test JSON.NUMINCRBY CREATE"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":{},"n":"x"}'))
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.a.hits', 1, 'CREATE'), '[1]')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.a.hits', 1, 'CREATE'), '[2]')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '.a.views', 5, 'CREATE', 10), '15')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.a.budget', -3, 'CREATE', 2, 'MIN', 0), ['[0]', ['MIN']])
+
+    # Missing parents are only created with CREATEPATH
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.b.c.d', 1, 'CREATE'), '[]')
+    r.expect('JSON.NUMINCRBY', 'test', '.b.c.d', 1, 'CREATE').raiseError()
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.b.c.d', 1, 'CREATE', 'CREATEPATH'), '[1]')
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.b.e', 2, 'CREATEPATH'), '[2]')
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$')),
+                  [{"a":{"hits":2,"views":15,"budget":0},"n":"x","b":{"c":{"d":1},"e":2}}])
+
+    # Nothing is created below a non-object, or for a non-static path
+    r.assertEqual(r.execute_command('JSON.NUMINCRBY', 'test', '$.n.x', 1, 'CREATEPATH'), '[]')
+    r.expect('JSON.NUMINCRBY', 'test', '$..missing', 1, 'CREATE').error().contains('static path')
+    r.expect('JSON.NUMMULTBY', 'test', '$.a.x', 2, 'CREATE').error().contains('syntax error')
+
 def testNumCommandOverflow(env):
     """Test JSON.NUMINCRBY and JSON.NUMMULTBY commands overflow """
     r = env
@@ -1252,6 +2064,62 @@ def testStrCommands(env):
     r.assertEqual(6, r.execute_command('JSON.STRAPPEND', 'test', '.', '"bar"'))
     r.assertEqual('"foobar"', r.execute_command('JSON.GET', 'test', '.'))
 
+def testStrLenUnit(env):
+    """Test JSON.STRLEN UNIT"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":"h\u00e9llo \ud83d\ude00","b":"abc","c":1}'))
+    # Bytes by default, for backward compatibility
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.a'), [11])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.a', 'UNIT', 'BYTES'), [11])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.a', 'UNIT', 'CODEPOINTS'), [7])
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '.a', 'UNIT', 'utf16'), 8)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '$.*', 'UNIT', 'UTF16'), [8, 3, None])
+
+    # The path is optional
+    r.assertOk(r.execute_command('JSON.SET', 'str', '$', '"\u00e9\u00e9"'))
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'str'), 4)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'str', 'UNIT', 'CODEPOINTS'), 2)
+
+    # length() counts code points, like UNIT CODEPOINTS
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$[?length(@) == 7]')), ['h\u00e9llo \U0001f600'])
+
+    r.expect('JSON.STRLEN', 'test', '$.a', 'UNIT', 'CHARS').error().contains('wrong string unit')
+    r.expect('JSON.STRLEN', 'test', '$.a', 'UNIT').raiseError()
+    r.expect('JSON.STRLEN', 'test', '$.a', 'SIZE', 'BYTES').error().contains('syntax error')
+
+def testStrEditCommands(env):
+    """Test JSON.STRPREPEND, JSON.STRSETRANGE and JSON.STRGETRANGE commands"""
+    r = env
+
+    r.assertOk(r.execute_command('JSON.SET', 'test', '$', '{"a":"world","b":"h\u00e9llo","c":1}'))
+    r.assertEqual(r.execute_command('JSON.STRPREPEND', 'test', '$.a', '"hello "'), [11])
+    r.assertEqual(r.execute_command('JSON.STRPREPEND', 'test', '.a', '">"'), 12)
+    r.assertEqual(r.execute_command('JSON.STRPREPEND', 'test', '$.*', '"_"'), [13, 7, None])
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.a')), ['_>hello world'])
+
+    # Offsets count bytes by default, like JSON.STRLEN
+    r.assertEqual(r.execute_command('JSON.STRGETRANGE', 'test', '$.b', 1, 3), ['"h\u00e9"'])
+    r.expect('JSON.STRGETRANGE', 'test', '.b', 1, 2).error().contains('character boundary')
+    r.assertEqual(json.loads(r.execute_command('JSON.STRGETRANGE', 'test', '.b', 2, -1, 'UNIT', 'CODEPOINTS')), '\u00e9llo')
+    r.assertEqual(json.loads(r.execute_command('JSON.STRGETRANGE', 'test', '.b', 1, 2, 'UNIT', 'UTF16')), 'h\u00e9')
+    r.assertEqual(json.loads(r.execute_command('JSON.STRGETRANGE', 'test', '.b', 1, 3, 'UNIT', 'BYTES')), 'h\u00e9')
+    r.assertEqual(r.execute_command('JSON.STRGETRANGE', 'test', '$.*', 0, 0), ['"_"', '"_"', None])
+
+    # The new lengths are in bytes whatever the unit, like JSON.STRAPPEND replies
+    r.assertEqual(r.execute_command('JSON.STRSETRANGE', 'test', '$.b', 2, '"e"', 'UNIT', 'CODEPOINTS'), [6])
+    r.assertEqual(r.execute_command('JSON.STRSETRANGE', 'test', '.b', 6, '"!"'), 7)
+    r.assertEqual(json.loads(r.execute_command('JSON.GET', 'test', '$.b')), ['_hello!'])
+    r.assertEqual(r.execute_command('JSON.STRSETRANGE', 'test', '$.b', 7, '"?"', 'UNIT', 'UTF16'), [8])
+    r.assertEqual(r.execute_command('JSON.STRSETRANGE', 'test', '.b', 0, '"\u00e9"', 'UNIT', 'CODEPOINTS'), 9)
+    r.assertEqual(r.execute_command('JSON.STRLEN', 'test', '.b'), 9)
+    r.expect('JSON.STRSETRANGE', 'test', '$.b', 10, '"?"').error().contains('out of range')
+    r.expect('JSON.STRSETRANGE', 'test', '$.b', -1, '"?"').error().contains('out of range')
+    r.expect('JSON.STRSETRANGE', 'test', '.c', 0, '"?"').raiseError()
+    r.expect('JSON.STRSETRANGE', 'test', '$.b', 0, '1').raiseError()
+    r.expect('JSON.STRSETRANGE', 'test', '$.b', 0, '"?"', 'BYTES').error().contains('syntax error')
+    r.expect('JSON.STRGETRANGE', 'test', '$.b', 0, 1, 'UNIT', 'CHARS').error().contains('wrong string unit')
+
 def testRespCommand(env):
     """Test JSON.RESP command"""
     r = env
@@ -1364,36 +2232,35 @@ def testMultiPathResults(env):
 
 
 def testMSET(env):
-    env.expect("JSON.MSET", "a{s}", '$', '"a_val"').ok()
+    env.expect("JSON.MSET", "a{s}", '$', '"a_val"').equal(['OK'])
     env.expect("JSON.GET", "a{s}", '$').equal('["a_val"]')
 
-    env.expect("JSON.MSET", "a{s}", '$', '{"aa":"a_val"}', "b{s}", '$', '{"bb":"b_val"}').ok()
+    env.expect("JSON.MSET", "a{s}", '$', '{"aa":"a_val"}', "b{s}", '$', '{"bb":"b_val"}').equal(['OK', 'OK'])
     env.expect("JSON.MGET", "a{s}", "b{s}", '$').equal(['[{"aa":"a_val"}]', '[{"bb":"b_val"}]'])
 
-    env.expect("JSON.MSET", "a{s}", '$.ab', '"a_val2"', "b{s}", '$..bb', '"b_val2"').ok()
+    env.expect("JSON.MSET", "a{s}", '$.ab', '"a_val2"', "b{s}", '$..bb', '"b_val2"').equal(['OK', 'OK'])
     env.expect("JSON.MGET", "a{s}", "b{s}", '$').equal(['[{"aa":"a_val","ab":"a_val2"}]', '[{"bb":"b_val2"}]'])
 
 
 def testMSET_Partial(env):
     # MSET doesn't stop processing on the first update that can't be applied
-    # (later keys still get updated)... but since not every update in the
-    # command succeeded, the overall reply must be nil, not OK.
+    # (later keys still get updated)... but the reply tells the update that
+    # failed apart from those that succeeded.
     env.expect("JSON.SET", "a{s}", '$', '{"x": {"y":[10,20], "z":[30,40]}}').ok()
     env.expect("JSON.SET", "b{s}", '$', '{"x": 60}').ok()
-    env.assertEqual(env.cmd("JSON.MSET", "a{s}", '$.x', '{}', "a{s}", '$.x.z[1]', '50', 'b{s}', '$.x', '70'), None)
+    env.assertEqual(env.cmd("JSON.MSET", "a{s}", '$.x', '{}', "a{s}", '$.x.z[1]', '50', 'b{s}', '$.x', '70'), ['OK', None, 'OK'])
     env.expect("JSON.GET", "a{s}", '$').equal('[{"x":{}}]')
     env.expect("JSON.GET", "b{s}", '$').equal('[{"x":70}]')
 
     # Update the same key twice with a failure in the middle
     env.expect("JSON.SET", "a{s}", '$', '{"x": {"y":[10,20], "z":[30,40]}}').ok()
-    env.assertEqual(env.cmd("JSON.MSET", "a{s}", '$.x', '{}', "a{s}", '$.x.z[1]', '50', "a{s}",  '$.u', '70'), None)
+    env.assertEqual(env.cmd("JSON.MSET", "a{s}", '$.x', '{}', "a{s}", '$.x.z[1]', '50', "a{s}",  '$.u', '70'), ['OK', None, 'OK'])
     env.expect("JSON.GET", "a{s}", '$').equal('[{"x":{},"u":70}]')
 
-def testMSET_FailsOverallIfAnyKeyFailed(env):
+def testMSET_ReportsFailedKey(env):
     # key a{s}: succeeds. key b{s}: path doesn't match anything -> fails.
-    # key c{s}: succeeds. Overall reply must be nil, but a{s}/c{s} must still
-    # be updated and notified/replicated -- only the reply reflects the
-    # all-or-nothing contract, not the underlying per-key writes.
+    # key c{s}: succeeds. The reply is nil for b{s} only, and a{s}/c{s} must
+    # be updated and notified/replicated.
     env.expect("JSON.SET", "a{s}", '$', '{"x": 1}').ok()
     env.expect("JSON.SET", "b{s}", '$', '{"x": 1}').ok()
     env.expect("JSON.SET", "c{s}", '$', '{"x": 1}').ok()
@@ -1413,7 +2280,7 @@ def testMSET_FailsOverallIfAnyKeyFailed(env):
             "b{s}", '$.no.such.path', '2',  # missing intermediate object -> can't be created
             "c{s}", '$.x', '2',
         )
-        env.assertEqual(res, None, message="JSON.MSET reported success even though key b{s} failed")
+        env.assertEqual(res, ['OK', None, 'OK'], message="JSON.MSET didn't report the failure of key b{s}")
 
         env.expect("JSON.GET", "a{s}", '$').equal('[{"x":2}]')
         env.expect("JSON.GET", "b{s}", '$').equal('[{"x":1}]')
@@ -1473,6 +2340,41 @@ def testMSET_Error(env):
     env.expect("JSON.GET", "a{s}", '$').equal('["a_val"]')
     env.expect("JSON.GET", "b{s}", '$').equal(None)
 
+def testMSET_Modifiers(env):
+    env.expect("JSON.SET", "a{s}", '$', '{"x": 1}').ok()
+
+    # The reply tells which triplets were skipped
+    env.expect("JSON.MSET", 'NX', "a{s}", '$.x', '2', "a{s}", '$.y', '3', 'XX', "b{s}", '$', '{}').equal([None, 'OK', None])
+    env.expect("JSON.GET", "a{s}", '$').equal('[{"x":1,"y":3}]')
+    env.expect("JSON.GET", "b{s}", '$').equal(None)
+
+    # A modifier applies to the triplets following it, up to one overriding it
+    env.expect("JSON.MSET", 'XX', "a{s}", '$.x', '4', "a{s}", '$.z', '5', 'NX', "b{s}", '$', '{"z":6}').equal(['OK', None, 'OK'])
+    env.expect("JSON.MGET", "a{s}", "b{s}", '$').equal(['[{"x":4,"y":3}]', '[{"z":6}]'])
+    env.expect("JSON.MSET", 'NX', "a{s}", '$', '{}', "b{s}", '$', '{}').equal([None, None])
+
+    # FORMAT and FPHA apply to the values following them
+    bson = open(os.path.join(JSON_PATH , 'bson_bytes_1.bson'), 'rb').read()
+    env.expect("JSON.MSET", 'FORMAT', 'BSON', "c{s}", '$', bson, 'FORMAT', 'JSON', 'FPHA', 'FP16', "d{s}", '$', '[0.5, 1.5]').equal(['OK', 'OK'])
+    env.expect("JSON.GET", "d{s}", '$').equal('[[0.5,1.5]]')
+    env.expect("JSON.MSET", "a{s}", '$.x', '[1e100]', 'FPHA', 'FP16', "a{s}", '$.y', '[1e100]').raiseError().contains("value out of range")
+    env.expect("JSON.GET", "a{s}", '$.x').equal('[4]')
+
+    # Nothing is written when a triplet is invalid
+    env.expect("JSON.MSET", 'NX', 'XX', "a{s}", '$.x', '7').raiseError().contains("syntax error")
+    env.expect("JSON.MSET", 'FORMAT', 'XML', "a{s}", '$.x', '7').raiseError().contains("wrong format")
+    env.expect("JSON.MSET", "a{s}", '$.x', '7', 'FPHA', 'FP8', "a{s}", '$.y', '7').raiseError().contains("invalid FPHA type")
+    env.expect("JSON.MSET", "a{s}", '$.x', '7', 'NX').raiseError()
+    env.expect("JSON.MSET", 'NX', "a{s}", '$.x').raiseError()
+    env.expect("JSON.GET", "a{s}", '$.x').equal('[4]')
+
+    # The keys are found wherever the modifiers move them. A triplet starting with a word named
+    # like a modifier takes it as one, whatever follows it.
+    env.expect("COMMAND", "GETKEYS", "JSON.MSET", 'XX', "a{s}", '$.x', '1', 'FORMAT', 'JSON', 'EX', '10', "b{s}", '$', '1').equal(['a{s}', 'b{s}'])
+    env.expect("COMMAND", "GETKEYS", "JSON.MSET", "a{s}", '$.x', '1', 'XX', "b{s}", '$', '1').equal(['a{s}', 'b{s}'])
+    env.expect("JSON.MSET", 'NX', '$', '1').raiseError()
+    env.expect("JSON.MSET", "a{s}", '$.x', '1', 'XX', '$', '1').raiseError()
+
 def testIssue_597(env):
     env.expect("JSON.SET", "test", ".", "[0]").ok()
     env.assertEqual(env.execute_command("JSON.SET", "test", ".[0]", "[0]", "NX"), None)
@@ -2233,7 +3135,7 @@ def test_mset_replication_in_aof(env):
             command.append(key.encode())
             command.append(b'$')
             command.append(value.encode())
-        env.expect(*command).ok()
+        env.expect(*command).equal(['OK'] * num_params)
 
         for i in range(0, num_params):
             msg = pubsub.get_message(timeout=1)
@@ -2411,8 +3313,8 @@ def testJsonDicLimitsInMSet(env):
     doc_2 = nest_object(depth, 5, "__leaf", 128)
 
     # test_1 doc is not updated due to depth limit exceeded, so MSET must
-    # report overall failure even though test_2 did get updated.
-    r.assertEqual(r.execute_command('JSON.MSET', 'test_1{s}', '$..__leaf', doc_2, 'test_2{s}', '$', doc_2), None)
+    # report its failure even though test_2 did get updated.
+    r.assertEqual(r.execute_command('JSON.MSET', 'test_1{s}', '$..__leaf', doc_2, 'test_2{s}', '$', doc_2), [None, 'OK'])
 
     r.assertEqual(r.execute_command("JSON.GET", 'test_1{s}', '$..__leaf'), '[42]')
 
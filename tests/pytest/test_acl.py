@@ -15,7 +15,19 @@ def test_acl_json_commands(env):
         'json.del', 'json.get', 'json.mget', 'json.set', 'json.mset', 'json.type', 'json.numincrby', 'json.toggle',
         'json.nummultby', 'json.numpowby', 'json.strappend', 'json.strlen', 'json.arrappend', 'json.arrindex',
         'json.arrinsert', 'json.arrlen', 'json.arrpop', 'json.arrtrim', 'json.objkeys', 'json.objlen', 'json.clear',
-        'json.debug', 'json.forget', 'json.resp', 'json.merge',
+        'json.debug', 'json.forget', 'json.resp', 'json.merge', 'json.arrbpop', 'json.arrinsort',
+        'json.arrunique',
+        'json.arrupsert',
+        'json.strprepend', 'json.strsetrange', 'json.strgetrange',
+        'json.getdel', 'json.getset',
+        'json.exists',
+        'json.count',
+        'json.paths',
+        'json.flatten', 'json.unflatten',
+        'json.fromhash', 'json.tohash',
+        'json.load',
+        'json.expire', 'json.ttl', 'json.persist',
+        'json.wait',
     ]
 
     # Use a set since the order of the response is not consistent.
@@ -75,6 +75,76 @@ class testCommandDocsAndHelp:
             group="module",
         )
 
+    def test_command_docs_json_arrbpop(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.arrbpop",
+            summary="Remove and return an element from the first non-empty array at path in the given keys, or block until one is available",
+            complexity="O(N) where N is the number of provided keys, plus the complexity of JSON.ARRPOP on the array an element is popped from",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_wait(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.wait",
+            summary="Block until a write touches the value at path, or until it differs from the given JSON, and return the new value",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_arrinsort(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.arrinsort",
+            summary="Insert the json value into the sorted array at path, keeping it sorted",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_arrunique(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.arrunique",
+            summary="Remove duplicate elements from the array at path, keeping the order of the remaining elements",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_arrupsert(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.arrupsert",
+            summary="Merge the json value into the element of the array at path with the same id field, or append it if there is none",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the array, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=5,
+            since="8.8.0",
+            group="module",
+        )
+
     def test_command_docs_json_arrtrim(self):
         env = self.env
         if server_version_is_less_than("7.0.0"):
@@ -173,6 +243,188 @@ class testCommandDocsAndHelp:
             group="module",
         )
 
+    def test_command_docs_json_getdel(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.getdel",
+            summary="Get the JSON values at path in key and delete them",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the deleted value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-2,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_getset(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.getset",
+            summary="Set the JSON value at path in key and return the values it replaces",
+            complexity="O(M+N) where M is the size of the original value (if it exists) and N is the size of the new value",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_exists(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.exists",
+            summary="Report whether paths exist in the JSON value at key",
+            complexity="O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_count(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.count",
+            summary="Count the JSON values matching path in key",
+            complexity="O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_paths(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.paths",
+            summary="List the normalized paths of the values at and below path in key",
+            complexity="O(N) where N is the number of values below the values matching path",
+            arity=-2,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_flatten(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.flatten",
+            summary="Return a map of the flat keys of the leaves of the values at path in key to their values",
+            complexity="O(N) where N is the size of the values matching path",
+            arity=-3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_unflatten(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.unflatten",
+            summary="Set the JSON value at path in key to the nested value described by a map of flat keys",
+            complexity="O(M+N) where M is the size of the original value (if it exists) and N is the size of the new value",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_fromhash(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.fromhash",
+            summary="Set a JSON key to an object built from the fields of a hash",
+            complexity="O(N) where N is the size of the hash",
+            arity=-3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_tohash(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.tohash",
+            summary="Set a hash to the scalar members of the JSON object at path in a key",
+            complexity="O(N) where N is the number of members of the object at path",
+            arity=4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_load(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.load",
+            summary="Store each document of a newline-delimited JSON payload under a key built from a template",
+            complexity="O(N) where N is the size of the payload",
+            arity=3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_expire(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.expire",
+            summary="Set the expiration time of the object members at path in key",
+            complexity="O(N) where N is the number of members matching the path",
+            arity=-4,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_ttl(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.ttl",
+            summary="Return the remaining time to live, in seconds, of the object members at path in key",
+            complexity="O(N) where N is the number of members matching the path",
+            arity=3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_persist(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.persist",
+            summary="Remove the expiration time of the object members at path in key",
+            complexity="O(N) where N is the number of members matching the path",
+            arity=3,
+            since="8.8.0",
+            group="module",
+        )
+
     def test_command_docs_json_mget(self):
         env = self.env
         if server_version_is_less_than("7.0.0"):
@@ -210,7 +462,7 @@ class testCommandDocsAndHelp:
             "json.numincrby",
             summary="Increment the number value stored at path by number",
             complexity="O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-            arity=4,
+            arity=-4,
             since="1.0.0",
             group="module",
         )
@@ -224,7 +476,7 @@ class testCommandDocsAndHelp:
             "json.nummultby",
             summary="Multiply the number value stored at path by number",
             complexity="O(1) when path is evaluated to a single value, O(N) when path is evaluated to multiple values, where N is the size of the key",
-            arity=4,
+            arity=-4,
             since="1.0.0",
             group="module",
         )
@@ -313,6 +565,48 @@ class testCommandDocsAndHelp:
             group="module",
         )
 
+    def test_command_docs_json_strprepend(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.strprepend",
+            summary="Prepend the json-string values to the string at path",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-3,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_strsetrange(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.strsetrange",
+            summary="Overwrite part of the string at path, starting at the specified offset, with the json-string value",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-5,
+            since="8.8.0",
+            group="module",
+        )
+
+    def test_command_docs_json_strgetrange(self):
+        env = self.env
+        if server_version_is_less_than("7.0.0"):
+            env.skip()
+        assert_docs(
+            env,
+            "json.strgetrange",
+            summary="Return the part of the string at path between the start and end offsets (both inclusive)",
+            complexity="O(N) when path is evaluated to a single value where N is the size of the string, O(N) when path is evaluated to multiple values, where N is the size of the key",
+            arity=-5,
+            since="8.8.0",
+            group="module",
+        )
+
     def test_command_docs_json_strlen(self):
         env = self.env
         if server_version_is_less_than("7.0.0"):
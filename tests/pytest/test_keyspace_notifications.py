@@ -30,7 +30,7 @@ def test_keyspace_set(env):
         assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.set')
         assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key')
 
-        env.assertEqual('OK', r.execute_command('JSON.MSET', 'test_key', '$.foo', '"gogo"', 'test_key{test_key}', '$', '{"a":"fufu"}'))
+        env.assertEqual(['OK', 'OK'], r.execute_command('JSON.MSET', 'test_key', '$.foo', '"gogo"', 'test_key{test_key}', '$', '{"a":"fufu"}'))
         assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.mset')
         assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key')
         assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.mset')
@@ -120,6 +120,8 @@ def test_keyspace_arr(env):
 
         env.assertEqual([None], r.execute_command('JSON.ARRPOP', 'test_key_arr', '$.foo'))  # Empty array
         env.assertEqual(None, pubsub.get_message(timeout=1))
+        env.assertEqual(None, r.execute_command('JSON.ARRPOP', 'test_key_arr', '.foo'))  # Empty array, legacy path
+        env.assertEqual(None, pubsub.get_message(timeout=1))
         env.assertEqual([None], r.execute_command('JSON.ARRPOP', 'test_key_arr', '$'))  # Not an array
         env.assertEqual(None, pubsub.get_message(timeout=1))
 
@@ -160,6 +162,100 @@ def test_keyspace_del(env):
         env.assertEqual(0, r.execute_command('JSON.DEL', 'test_key', '$.foo'))
         env.assertEqual(None, pubsub.get_message(timeout=1))      
 
+def test_keyspace_hash(env):
+    env.skipOnVersionSmaller('6.2')
+    with env.getClusterConnectionIfNeeded() as r:
+        r.execute_command('config', 'set', 'notify-keyspace-events', 'KEA')
+
+        pubsub = r.pubsub()
+        pubsub.psubscribe('__key*')
+
+        time.sleep(1)
+        env.assertEqual('psubscribe', pubsub.get_message(timeout=1)['type'])
+
+        r.execute_command('HSET', 'hash{tag}', 'foo', 'bar')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'hset')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'hash{tag}')
+
+        env.assertEqual(1, r.execute_command('JSON.FROMHASH', 'test_key{tag}', 'hash{tag}'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.fromhash')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+
+        # The hash is replaced
+        env.assertEqual(1, r.execute_command('JSON.TOHASH', 'hash{tag}', 'test_key{tag}', '$'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'del')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'hash{tag}')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'hset')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'hash{tag}')
+
+        env.assertEqual(None, r.execute_command('JSON.FROMHASH', 'test_key{tag}', 'nohash{tag}'))
+        env.assertEqual(None, pubsub.get_message(timeout=1))
+
+def test_keyspace_member_expiry(env):
+    env.skipOnVersionSmaller('6.2')
+    with env.getClusterConnectionIfNeeded() as r:
+        r.execute_command('config', 'set', 'notify-keyspace-events', 'KEA')
+
+        pubsub = r.pubsub()
+        pubsub.psubscribe('__key*')
+
+        time.sleep(1)
+        env.assertEqual('psubscribe', pubsub.get_message(timeout=1)['type'])
+
+        r.execute_command('JSON.SET', 'test_key{tag}', '$', '{"foo": 1, "bar": 2}')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+
+        env.assertEqual([1], r.execute_command('JSON.EXPIRE', 'test_key{tag}', '$.foo', 1))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.expire')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+
+        # Expired by the active expiry cycle
+        assert_msg(env, pubsub.get_message(timeout=2), 'pmessage', 'json.expired')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+        env.assertEqual('{"bar":2}', r.execute_command('JSON.GET', 'test_key{tag}'))
+
+        env.assertEqual([1], r.execute_command('JSON.EXPIRE', 'test_key{tag}', '$.bar', 100))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.expire')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+        env.assertEqual([1], r.execute_command('JSON.PERSIST', 'test_key{tag}', '$.bar'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.persist')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'test_key{tag}')
+
+def test_keyspace_paths():
+    env = Env(moduleArgs='NOTIFY_PATHS yes')
+    env.skipOnVersionSmaller('6.2')
+    with env.getClusterConnectionIfNeeded() as r:
+        r.execute_command('config', 'set', 'notify-keyspace-events', 'KEA')
+
+        pubsub = r.pubsub()
+        pubsub.psubscribe('__keyspace*')
+
+        time.sleep(1)
+        env.assertEqual('psubscribe', pubsub.get_message(timeout=1)['type'])
+
+        r.execute_command('JSON.SET', 'test_key', '$', '{"a": [1, {"b": 2}], "c": {"b": 3}}')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.set:$')
+
+        # Each touched path is notified once
+        env.assertEqual('OK', r.execute_command('JSON.SET', 'test_key', '$..b', '4'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.set')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', "json.set:$['a'][1]['b']")
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', "json.set:$['c']['b']")
+
+        env.assertEqual([3], r.execute_command('JSON.ARRAPPEND', 'test_key', '$.a', '5'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.arrappend')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', "json.arrappend:$['a']")
+
+        env.assertEqual(1, r.execute_command('JSON.DEL', 'test_key', '$.a[0]'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.del')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', "json.del:$['a'][0]")
+
+        env.assertEqual(1, r.execute_command('JSON.DEL', 'test_key', '$'))
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.del')
+        assert_msg(env, pubsub.get_message(timeout=1), 'pmessage', 'json.del:$')
+
 def test_keyspace_num(env):
     env.skipOnVersionSmaller('6.2')
     with env.getClusterConnectionIfNeeded() as r:
@@ -57,7 +57,7 @@ def _env_with_doc():
 def testLLAPIVersion():
     env = _new_env()
     ver = env.cmd('LLAPI.VERSION')
-    # RedisJSON exports up to V9; other modules may export a lower version.
+    # RedisJSON exports up to V10; other modules may export a lower version.
     env.assertTrue(isinstance(ver, int) and ver >= 1)
 
 
@@ -185,6 +185,17 @@ def testLLAPIIsJson():
     env.assertEqual(env.cmd('LLAPI.ISJSON', 'plain'), 0)
 
 
+def testLLAPIPathEvents():
+    env = _env_with_doc()
+    env.assertEqual(env.cmd('LLAPI.LAST_PATH_EVENT'), ['json.set', 'doc', '$'])
+    env.expect('JSON.SET', 'doc', '$.object.new', '[1]').ok()
+    env.assertEqual(env.cmd('LLAPI.LAST_PATH_EVENT'), ['json.set', 'doc', "$['object']['new']"])
+    env.assertEqual(env.cmd('JSON.NUMINCRBY', 'doc', '$.object.new[0]', 1), '[2]')
+    env.assertEqual(env.cmd('LLAPI.LAST_PATH_EVENT'), ['json.numincrby', 'doc', "$['object']['new'][0]"])
+    env.assertEqual(env.cmd('JSON.DEL', 'doc', '$..new'), 1)
+    env.assertEqual(env.cmd('LLAPI.LAST_PATH_EVENT'), ['json.del', 'doc', "$['object']['new']"])
+
+
 # --------------------------------------------------------------------------- #
 # Negative / error paths
 # --------------------------------------------------------------------------- #
@@ -152,6 +152,24 @@ class testResp3():
         r.expect('JSON.NUMMULTBY', 'test_not_JSON', '$.a1.b', '1').raiseError()
 
 
+    def test_resp_json_getdel_getset(self):
+        r = self.env
+        r.skipOnVersionSmaller('7.0')
+
+        r.assertOk(r.execute_command('JSON.SET', 'test_resp3', '$', '{"a":{"b":[1,2.5]},"c":"x"}'))
+        r.assertEqual(r.execute_command('JSON.GETSET', 'test_resp3', '$.a.b', '{}', 'FORMAT', 'EXPAND'), [[[1, 2.5]]])
+        r.assertEqual(r.execute_command('JSON.GETSET', 'test_resp3', '$.c', '"y"'), '["x"]')
+        r.assertEqual(r.execute_command('JSON.GETDEL', 'test_resp3', '$.a', 'FORMAT', 'EXPAND1'), [['{"b":{}}']])
+        r.assertEqual(r.execute_command('JSON.GETDEL', 'test_resp3', '$', 'FORMAT', 'EXPAND'), [[{'c': 'y'}]])
+        r.assertEqual(r.execute_command('EXISTS', 'test_resp3'), 0)
+
+    def test_resp_json_exists(self):
+        r = self.env
+        r.skipOnVersionSmaller('7.0')
+
+        r.assertOk(r.execute_command('JSON.SET', 'test_resp3', '$', '{"a":1}'))
+        r.assertEqual(r.execute_command('JSON.EXISTS', 'test_resp3', '.a', '.b', '$..a'), [True, False, 1])
+
     # Test JSON.MSET RESP3
     def test_resp_json_mset(self):
         r = self.env
@@ -159,7 +177,7 @@ class testResp3():
 
         r.assertTrue(r.execute_command('SET', 'test_not_JSON', 'test_not_JSON'))
 
-        r.assertOk(r.execute_command('JSON.MSET', 'test_resp3_1{s}', '$', '{"a1":{"b":{"c":1}},"a2":{"b":{"c":2}}}', 'test_resp3_2{s}', '$', '{"a1":{"b":{"c":1}},"a2":{"b":{"c":2}}}'))
+        r.assertEqual(r.execute_command('JSON.MSET', 'test_resp3_1{s}', '$', '{"a1":{"b":{"c":1}},"a2":{"b":{"c":2}}}', 'test_resp3_2{s}', '$', '{"a1":{"b":{"c":1}},"a2":{"b":{"c":2}}}'), ['OK', 'OK'])
 
         # Test none existing key
         r.expect('JSON.MSET', 'test_no_such_key', '$.a1.b', '1').raiseError()